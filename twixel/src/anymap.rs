@@ -51,6 +51,7 @@ impl AnyMap {
             .and_then(|b| b.as_ref().as_any().downcast_ref::<T>())
     }
 
+    #[allow(dead_code)]
     pub fn get_mut<T: Any>(&mut self) -> Option<&mut T> {
         self.inner
             .get_mut(&TypeId::of::<T>())
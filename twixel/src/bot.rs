@@ -1,17 +1,38 @@
-use std::{any::Any, sync::Arc};
+use std::{
+    any::Any,
+    collections::VecDeque,
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use futures::StreamExt;
+use ::metrics::{gauge, histogram};
+use futures::{Stream, StreamExt};
 use hashbrown::HashMap;
-use tokio::signal::unix::{SignalKind, signal};
+use tokio::{
+    signal::unix::{SignalKind, signal},
+    sync::{
+        broadcast::error::RecvError,
+        mpsc::{self, error::SendError},
+    },
+};
+use tracing::{Instrument, Span, info_span};
 use twixel_core::{
-    auth::OAuth, irc_message::{tags::OwnedTag, AnySemantic, PrivMsg}, ConnectionPool, MessageBuilder
+    ChannelLogin, ConnectionPool, IrcMessage, MessageBuilder,
+    auth::{AuthProvider, OAuth},
+    connection::{
+        ConnectionEvent, Dedup, LifecycleEvent, Proxy, RateLimiter, ServerProfile, error::PoolError,
+    },
+    irc_message::{AnySemantic, PrivMsg, moderation::ModerationEvent, tags::OwnedTag},
+    metrics,
+    recording::{Recorder, ReplayError},
 };
 
 use crate::{
     anymap::AnyMap,
     guard::GuardContext,
     handler::{Command, CommandHandler, DynHandler, HandlerContext},
-    util::limit_str_at_graphemes,
+    util::{metrics::HANDLER_DURATION, split_message},
 };
 
 #[derive(Default, Clone)]
@@ -34,24 +55,150 @@ impl BotData {
         self.data.insert::<Arc<T>>(Arc::new(value))
     }
 
+    #[allow(dead_code)]
     fn remove<T: Any + Send + Sync>(&mut self) -> Option<T> {
         self.data.remove::<T>()
     }
 }
 
-pub struct Bot {
-    conn_pool: ConnectionPool<OAuth>,
+pub struct Bot<A: AuthProvider + Clone = OAuth> {
+    conn_pool: ConnectionPool<A>,
     commands: Vec<Command>,
     catchall: Vec<DynHandler>,
     data: BotData,
-    cmd_rx: tokio::sync::mpsc::Receiver<BotCommand>,
-    cmd_tx: tokio::sync::mpsc::Sender<BotCommand>,
+    shared_chat_mode: SharedChatMode,
+    split_marker: Option<String>,
+    rate_limiter: RateLimiter,
+    replay: Option<ReplayStream>,
+    cmd_rx: mpsc::Receiver<(BotCommand, Span)>,
+    cmd_tx: BotSender,
+}
+
+/// How messages are handled during Shared Chat sessions, where every
+/// participating channel receives its own copy of each message
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SharedChatMode {
+    /// Every copy of a message is handled
+    #[default]
+    All,
+    /// Each message is handled once, in whichever channel its first copy is
+    /// received in
+    OncePerSource,
+    /// Messages are only handled in the channel they were originally sent in
+    OriginOnly,
+}
+
+/// How many source message IDs are remembered for [SharedChatMode::OncePerSource]
+const SHARED_CHAT_DEDUP_SIZE: usize = 1024;
+
+/// Keeps track of recently handled Shared Chat messages
+struct SharedChatFilter {
+    mode: SharedChatMode,
+    seen: Dedup,
+}
+
+impl SharedChatFilter {
+    fn new(mode: SharedChatMode) -> Self {
+        Self {
+            mode,
+            seen: Dedup::new()
+                .capacity(SHARED_CHAT_DEDUP_SIZE)
+                .ttl(Duration::MAX)
+                .once_per_source(true),
+        }
+    }
+
+    /// Returns whether the message should be handled
+    fn check(&mut self, msg: &AnySemantic) -> bool {
+        let AnySemantic::PrivMsg(msg) = msg else {
+            return true;
+        };
+        match self.mode {
+            SharedChatMode::All => true,
+            SharedChatMode::OriginOnly => msg.is_from_current_channel(),
+            SharedChatMode::OncePerSource => msg.shared_chat().is_none() || self.seen.check(&**msg),
+        }
+    }
 }
 
+/// Longest time waited between attempts to reconnect a connection
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+/// Time waited before retrying to reconnect a connection the first time
+const MIN_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Connections that must be restarted, with when they are next tried and how
+/// long to wait if that fails
+#[derive(Default)]
+struct Reconnects(HashMap<usize, (Instant, Duration)>);
+
+impl Reconnects {
+    /// Restarts a connection as soon as possible, unless it already will be
+    fn schedule(&mut self, idx: usize) {
+        self.0
+            .entry(idx)
+            .or_insert((Instant::now(), MIN_RECONNECT_DELAY));
+    }
+
+    /// The connections due to be restarted at `now`
+    fn due(&self, now: Instant) -> Vec<usize> {
+        self.0
+            .iter()
+            .filter(|(_, (at, _))| *at <= now)
+            .map(|(idx, _)| *idx)
+            .collect()
+    }
+
+    /// Waits longer before the next attempt each time one fails
+    fn failed(&mut self, idx: usize, now: Instant) -> Duration {
+        let (at, delay) = self.0.entry(idx).or_insert((now, MIN_RECONNECT_DELAY));
+        let wait = *delay;
+        *at = now + wait;
+        *delay = (wait * 2).min(MAX_RECONNECT_DELAY);
+        wait
+    }
+
+    /// How long until the next connection is due to be restarted
+    fn next_wait(&self) -> Option<Duration> {
+        let now = Instant::now();
+        self.0
+            .values()
+            .map(|(at, _)| at.saturating_duration_since(now))
+            .min()
+    }
+
+    /// Tries to restart the connections that are due. Other connections keep
+    /// being handled in the meantime
+    async fn retry<A: AuthProvider + Clone>(&mut self, conn_pool: &mut ConnectionPool<A>) {
+        for idx in self.due(Instant::now()) {
+            match conn_pool.restart_connection(idx).await {
+                Ok(()) => {
+                    self.0.remove(&idx);
+                }
+                Err(e) => {
+                    let wait = self.failed(idx, Instant::now());
+                    tracing::warn!(
+                        "failed to reconnect connection {idx}, retrying in {wait:?}: {e}"
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// A message to a channel waiting for the rate limiter, with the span of the
+/// command that sent it
+struct QueuedMessage {
+    channel: ChannelLogin,
+    msg: MessageBuilder<'static>,
+    span: Span,
+}
+
+type ReplayStream = Pin<Box<dyn Stream<Item = Result<(IrcMessage, usize), ReplayError>> + Send>>;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum BotCommand {
     SendMessage {
-        channel_login: String,
+        channel_login: ChannelLogin,
         message: String,
         reply_id: Option<String>,
     },
@@ -63,44 +210,100 @@ pub enum BotCommand {
 }
 
 impl BotCommand {
-    pub fn respond(msg: &PrivMsg, response: String, reply: bool) -> Self {
+    /// Responds in the channel `msg` was sent in, `None` if its channel param
+    /// is missing or malformed
+    pub fn respond(msg: &PrivMsg, response: String, reply: bool) -> Option<Self> {
         let reply_id = if reply {
             msg.reply_to_id().map(|s| s.to_owned())
         } else {
             None
         };
 
-        Self::SendMessage {
-            channel_login: msg.channel_login().into(),
+        Some(Self::SendMessage {
+            channel_login: msg.channel_login()?.into_owned(),
             message: response,
             reply_id,
-        }
+        })
     }
 }
 
+/// Sends [BotCommand]s to the bot, along with the span they were sent from so
+/// what the bot does follows from it
+#[derive(Debug, Clone)]
+pub struct BotSender(mpsc::Sender<(BotCommand, Span)>);
+
+impl BotSender {
+    pub async fn send(&self, cmd: BotCommand) -> Result<(), SendError<BotCommand>> {
+        self.0
+            .send((cmd, Span::current()))
+            .await
+            .map_err(|SendError((cmd, _))| SendError(cmd))
+    }
+}
+
+/// Span following a received message through its handlers and what they send
+fn message_span(msg: &AnySemantic, connection: usize) -> Span {
+    info_span!(
+        "message",
+        connection,
+        command = %msg.get_command(),
+        channel = msg.get_param(0).and_then(|c| c.strip_prefix('#')),
+        msg_id = msg.get_tag_raw(OwnedTag::Id),
+        sender = msg.get_nickname(),
+    )
+}
+
 const CMD_CHANNEL_SIZE: usize = 128;
+/// Twitch drops messages longer than this
+const MAX_MESSAGE_CHARS: usize = 500;
+/// Appended to a message identical to the previous one, which Twitch would drop
+const DEDUP_SUFFIX: &str = " \u{e0000}";
 
 impl Bot {
     pub async fn new(username: String, token: String) -> Self {
-        let (tx, rx) = tokio::sync::mpsc::channel(CMD_CHANNEL_SIZE);
+        Self::with_server(
+            OAuth {
+                nick: username,
+                oauth: token,
+            },
+            ServerProfile::Twitch,
+        )
+        .await
+    }
+}
+
+impl<A: AuthProvider + Clone + Send + Sync + 'static> Bot<A> {
+    /// Creates a bot for any IRC server, such as a
+    /// [GenericServer](twixel_core::connection::GenericServer) with
+    /// [SaslPlain](twixel_core::auth::SaslPlain) auth
+    pub async fn with_server(auth: A, profile: impl Into<ServerProfile>) -> Self {
+        let (tx, rx) = mpsc::channel(CMD_CHANNEL_SIZE);
         Self {
-            conn_pool: ConnectionPool::new(
-                core::iter::empty::<String>(),
-                OAuth{ nick: username, oauth: token },
+            conn_pool: ConnectionPool::with_profile(
+                core::iter::empty::<ChannelLogin>(),
+                auth,
+                profile,
             )
             .await
             .unwrap(),
             commands: vec![],
             catchall: vec![],
             data: BotData::new(),
+            shared_chat_mode: SharedChatMode::default(),
+            split_marker: None,
+            rate_limiter: RateLimiter::twitch_user(),
+            replay: None,
             cmd_rx: rx,
-            cmd_tx: tx,
+            cmd_tx: BotSender(tx),
         }
     }
 
     pub async fn add_channels(mut self, channels: impl IntoIterator<Item = &str>) -> Self {
         for i in channels {
-            self.conn_pool.join_channel(i).await.unwrap();
+            match ChannelLogin::parse(i) {
+                Ok(channel) => self.conn_pool.join_channel(channel).await.unwrap(),
+                Err(e) => tracing::error!("can't join {i:?}: {e}"),
+            }
         }
         self
     }
@@ -115,69 +318,159 @@ impl Bot {
         self
     }
 
+    pub fn shared_chat_mode(mut self, mode: SharedChatMode) -> Self {
+        self.shared_chat_mode = mode;
+        self
+    }
+
+    /// Text appended to every part of a message that was too long and had to be
+    /// split, except the last one
+    pub fn split_marker(mut self, marker: impl Into<String>) -> Self {
+        self.split_marker = Some(marker.into());
+        self
+    }
+
+    /// Sets how fast messages are sent to channels, [RateLimiter::twitch_user]
+    /// by default
+    #[allow(dead_code)]
+    pub fn rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.rate_limiter = limiter;
+        self
+    }
+
+    /// Tunnels the bot's connections through `proxy`
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.conn_pool.set_proxy(Some(proxy));
+        self
+    }
+
+    /// Drops messages the bot already received on another of its connections.
+    /// The copies of Shared Chat messages are handled by [SharedChatMode]
+    /// instead
+    pub fn dedup(mut self, dedup: Dedup) -> Self {
+        self.conn_pool.set_dedup(Some(dedup));
+        self
+    }
+
+    /// Records every raw IRC line received and sent by the bot's connections
+    pub fn recorder(mut self, recorder: Recorder) -> Self {
+        self.conn_pool.record_to(recorder);
+        self
+    }
+
+    /// Handles messages from a replayed chat log instead of the ones received
+    /// from the bot's connections, such as a
+    /// [Replayer](twixel_core::recording::Replayer) stream
+    pub fn replay(
+        mut self,
+        messages: impl Stream<Item = Result<(IrcMessage, usize), ReplayError>> + Send + 'static,
+    ) -> Self {
+        self.replay = Some(Box::pin(messages));
+        self
+    }
+
     pub fn data<T: Any + Send + Sync>(mut self, data: T) -> Self {
         self.data.insert(data);
         self
     }
 
-    /// Returns whether to shut down or not
+    /// Returns whether to shut down or not. Messages to channels are queued to
+    /// be sent once the rate limit allows it, see [Self::send_queued]
     async fn handle_cmd(
-        conn_pool: &mut ConnectionPool<OAuth>,
+        conn_pool: &mut ConnectionPool<A>,
         cmd: BotCommand,
-        last_sent_msg: &mut HashMap<String, String>,
+        last_sent_msg: &mut HashMap<ChannelLogin, String>,
+        queue: &mut VecDeque<QueuedMessage>,
+        reconnects: &mut Reconnects,
+        split_marker: Option<&str>,
     ) -> bool {
         match cmd {
             BotCommand::SendMessage {
                 channel_login,
-                mut message,
+                message,
                 reply_id,
             } => {
-                log::debug!("sending {} to {}", message, channel_login);
-                if let Some(idx) = conn_pool.get_conn_idx(&channel_login) {
-                    let entry = last_sent_msg.entry_ref(&channel_login);
-                    entry
-                        .and_modify(|v| {
-                            if v == &message {
-                                message += " \u{e0000}";
-                                *v = message.clone();
-                            } else {
-                                *v = message.clone();
-                            }
-                        })
-                        .or_insert(message.clone());
-                    let msg = MessageBuilder::privmsg(
-                        &channel_login,
-                        limit_str_at_graphemes(&message, 500),
-                    )
-                    .add_tag(OwnedTag::ReplyParentMsgId, reply_id.unwrap_or_default());
-                    conn_pool.send_to_connection(msg, idx).await.unwrap();
+                let span = info_span!("send_message", channel = %channel_login);
+                let _entered = span.enter();
+                tracing::debug!("queueing {} to {}", message, channel_login);
+                let parts = split_message(
+                    &message,
+                    MAX_MESSAGE_CHARS - DEDUP_SUFFIX.chars().count(),
+                    split_marker,
+                );
+                for mut part in parts {
+                    if last_sent_msg.get(&channel_login) == Some(&part) {
+                        part += DEDUP_SUFFIX;
+                    }
+                    let msg = MessageBuilder::privmsg(&channel_login, &part)
+                        .add_tag(
+                            OwnedTag::ReplyParentMsgId,
+                            reply_id.clone().unwrap_or_default(),
+                        )
+                        .to_owned();
+                    queue.push_back(QueuedMessage {
+                        channel: channel_login.clone(),
+                        msg,
+                        span: span.clone(),
+                    });
+                    last_sent_msg.insert(channel_login.clone(), part);
                 }
             }
             BotCommand::SendRawIrc(raw, idx) => {
-                log::debug!("sending {} to connetion {}", raw.command, idx);
+                tracing::debug!("sending {} to connetion {}", raw.command, idx);
                 conn_pool.send_to_connection(raw, idx).await.unwrap();
             }
-            BotCommand::Reconnect(idx) => {
-                conn_pool.restart_connection(idx).await.unwrap();
-            }
-            BotCommand::JoinChannel(channel) => {
-                conn_pool.join_channel(&channel).await.unwrap();
-            }
-            BotCommand::PartChannel(channel) => {
-                conn_pool.part_channel(&channel).await.unwrap();
-            }
+            BotCommand::Reconnect(idx) => reconnects.schedule(idx),
+            BotCommand::JoinChannel(channel) => match ChannelLogin::parse(&channel) {
+                Ok(channel) => conn_pool.join_channel(channel).await.unwrap(),
+                Err(e) => tracing::warn!("can't join {channel:?}: {e}"),
+            },
+            BotCommand::PartChannel(channel) => match ChannelLogin::parse(&channel) {
+                Ok(channel) => conn_pool.part_channel(channel).await.unwrap(),
+                Err(e) => tracing::warn!("can't part {channel:?}: {e}"),
+            },
             BotCommand::Shutdown => {
-                log::info!("shutting down");
+                tracing::info!("shutting down");
                 return true;
             }
         };
         false
     }
 
+    /// Sends the queued messages the rate limit allows, returns how long to
+    /// wait before the next one can be sent if any are left
+    async fn send_queued(
+        conn_pool: &mut ConnectionPool<A>,
+        queue: &mut VecDeque<QueuedMessage>,
+        rate_limiter: &mut RateLimiter,
+    ) -> Option<Duration> {
+        while !queue.is_empty() {
+            if let Err(wait) = rate_limiter.try_acquire() {
+                return Some(wait);
+            }
+            let QueuedMessage { channel, msg, span } = queue.pop_front().unwrap();
+            async {
+                match conn_pool.get_write_conn_idx(&channel) {
+                    Ok(idx) => {
+                        if let Err(e) = conn_pool.send_to_connection(msg, idx).await {
+                            tracing::warn!("failed to send message to {channel}: {e}");
+                        }
+                    }
+                    Err(_) => tracing::info!("not joined to {channel}, not sending: {msg:?}"),
+                }
+            }
+            .instrument(span)
+            .await;
+        }
+        None
+    }
+
     pub async fn run(mut self) {
         let (tx, rx) = async_channel::bounded(CMD_CHANNEL_SIZE);
         let data_store = Arc::new(self.data);
-        let mut msgs = HashMap::<String, String>::new();
+        let mut msgs = HashMap::<ChannelLogin, String>::new();
+        let mut send_queue = VecDeque::<QueuedMessage>::new();
+        let mut shared_chat = SharedChatFilter::new(self.shared_chat_mode);
 
         tokio::spawn({
             let tx = self.cmd_tx.clone();
@@ -186,42 +479,129 @@ impl Bot {
             async move {
                 tokio::select! {
                     _ = sigterm.recv() => {
-                        log::info!("SIGTERM received, shutting down");
+                        tracing::info!("SIGTERM received, shutting down");
                         tx.send(BotCommand::Shutdown).await
                     }
                     _ = sigint.recv() => {
-                        log::info!("SIGINT received, shutting down");
+                        tracing::info!("SIGINT received, shutting down");
                         tx.send(BotCommand::Shutdown).await
                     }
                 }
             }
         });
 
+        tokio::spawn({
+            let mut events = self.conn_pool.subscribe();
+            async move {
+                loop {
+                    let LifecycleEvent { connection, event } = match events.recv().await {
+                        Ok(event) => event,
+                        Err(RecvError::Lagged(missed)) => {
+                            tracing::warn!("missed {missed} connection events");
+                            continue;
+                        }
+                        Err(RecvError::Closed) => break,
+                    };
+                    match event {
+                        ConnectionEvent::Disconnected(reason) => {
+                            tracing::warn!("connection {connection} disconnected: {reason}")
+                        }
+                        ConnectionEvent::LatencyMeasured(latency) => {
+                            tracing::debug!("connection {connection} latency: {latency:?}")
+                        }
+                        event => tracing::info!("connection {connection}: {event:?}"),
+                    }
+                }
+            }
+        });
+
         let receiver = tokio::spawn(async move {
+            let replaying = self.replay.is_some();
+            let mut reconnects = Reconnects::default();
             loop {
-                tokio::select! {
+                reconnects.retry(&mut self.conn_pool).await;
+                let next_reconnect = reconnects.next_wait();
+                // sending never waits for the rate limit here, so PINGs keep
+                // being answered while messages are queued
+                let wait =
+                    Self::send_queued(&mut self.conn_pool, &mut send_queue, &mut self.rate_limiter)
+                        .await;
+                gauge!(metrics::SEND_QUEUE_DEPTH, "queue" => "bot").set(send_queue.len() as f64);
+
+                let (msg, idx) = tokio::select! {
                     // Handle message received from twitch IRC
-                    Some(msg) = self.conn_pool.next() => {
-                        let (msg, idx) = msg.unwrap();
-                        let cx = HandlerContext {
-                            msg: msg.into(),
-                            connection_idx: idx,
-                            bot_tx: self.cmd_tx.clone(),
-                            data_store: Arc::clone(&data_store)
-                        };
-
-                        let new_tx = tx.clone();
-                        tokio::spawn(async move { new_tx.send(cx).await.unwrap(); });
+                    Some(received) = self.conn_pool.next(), if !replaying => match received {
+                        Ok(received) => received,
+                        // such as the server not answering keepalive PINGs
+                        Err(PoolError::ReceiveFailed(idx, e)) => {
+                            tracing::warn!("connection {idx} failed, reconnecting: {e}");
+                            reconnects.schedule(idx);
+                            continue;
+                        }
+                        Err(e) => panic!("failed to receive: {e}"),
+                    },
+                    // Handle message from a replayed log. select! evaluates the
+                    // expressions of disabled branches too, so the stream is
+                    // only unwrapped inside the future
+                    msg = async { self.replay.as_mut().unwrap().next().await }, if self.replay.is_some() => {
+                        match msg {
+                            Some(Ok(msg)) => msg,
+                            Some(Err(e)) => {
+                                tracing::warn!("skipping replayed message: {e}");
+                                continue;
+                            }
+                            None => {
+                                tracing::info!("replay finished");
+                                self.replay = None;
+                                continue;
+                            }
+                        }
                     }
                     // Handle bot actions
                     cmd = self.cmd_rx.recv() => { match cmd {
-                        Some(cmd) => if Self::handle_cmd(&mut self.conn_pool, cmd, &mut msgs).await { break },
+                        Some((cmd, span)) => {
+                            if Self::handle_cmd(
+                                &mut self.conn_pool,
+                                cmd,
+                                &mut msgs,
+                                &mut send_queue,
+                                &mut reconnects,
+                                self.split_marker.as_deref(),
+                            ).instrument(span).await { break } else { continue }
+                        }
                         None => {
-                            log::error!("COMMAND CHANNEL BROKEN");
+                            tracing::error!("COMMAND CHANNEL BROKEN");
                             break;
                         },
-                    }}
+                    }},
+                    // Send queued messages once the rate limit allows it
+                    _ = tokio::time::sleep(wait.unwrap_or_default()), if wait.is_some() => continue,
+                    // Retry reconnecting once it's due
+                    _ = tokio::time::sleep(next_reconnect.unwrap_or_default()), if next_reconnect.is_some() => continue,
+                };
+
+                let msg = AnySemantic::from(msg);
+                let span = message_span(&msg, idx);
+                if !shared_chat.check(&msg) {
+                    tracing::debug!(
+                        parent: &span,
+                        "skipping shared chat message: {:?}",
+                        msg.inner()
+                    );
+                    continue;
                 }
+                let cx = HandlerContext {
+                    msg,
+                    connection_idx: idx,
+                    bot_tx: self.cmd_tx.clone(),
+                    data_store: Arc::clone(&data_store),
+                    span,
+                };
+
+                let new_tx = tx.clone();
+                tokio::spawn(async move {
+                    new_tx.send(cx).await.unwrap();
+                });
             }
         });
 
@@ -245,7 +625,7 @@ impl Bot {
         }
 
         if let Err(e) = receiver.await {
-            log::error!("{e}");
+            tracing::error!("{e}");
         }
     }
 }
@@ -257,78 +637,321 @@ async fn bot_worker(
 ) {
     let catchall: Vec<Arc<Command>> = catchall.into_iter().map(Arc::new).collect();
     while let Ok(cx) = rx.recv().await {
-        let msg = cx.msg;
-        match &msg {
-            AnySemantic::Notice(msg) => {
-                match msg.kind() {
-                    Some(Ok(k)) => log::info!("received notice of kind: {k}"),
-                    Some(Err(_)) => log::error!(
-                        "unknown notice kind: {}",
-                        msg.get_tag(OwnedTag::MsgId).unwrap()
-                    ),
-                    None => log::warn!("NOTICE message had no kind"),
-                };
-                continue;
-            }
-            AnySemantic::Ping(msg) => {
-                cx.bot_tx
-                    .send(BotCommand::SendRawIrc(
-                        msg.respond().to_owned(),
-                        cx.connection_idx,
-                    ))
-                    .await
-                    .unwrap();
-                continue;
-            }
-            AnySemantic::AuthSuccessful(_msg) => {
-                log::info!("auth successful");
-                continue;
-            }
-            AnySemantic::Reconnect(_msg) => {
-                cx.bot_tx
-                    .send(BotCommand::Reconnect(cx.connection_idx))
-                    .await
-                    .unwrap();
-                continue;
-            }
-            AnySemantic::PrivMsg(_msg) => (),
-            AnySemantic::Useless(_msg) => continue,
-            AnySemantic::UserState(msg) => {
-                log::debug!("received userstate from irc: {:?}", msg.roles());
-                continue;
-            }
-            msg => {
-                log::warn!("untreated message kind: {:?}", msg.inner());
-                continue;
+        let span = cx.span.clone();
+        dispatch(cx, &cmds, &catchall).instrument(span).await;
+    }
+}
+
+/// Runs the handlers of a received message, within its span
+async fn dispatch(cx: HandlerContext, cmds: &[Command], catchall: &[Arc<Command>]) {
+    let msg = cx.msg;
+    match &msg {
+        AnySemantic::Notice(msg) => {
+            match msg.kind() {
+                Some(Ok(k)) => tracing::info!("received notice of kind: {k}"),
+                Some(Err(_)) => tracing::error!(
+                    "unknown notice kind: {}",
+                    msg.get_tag(OwnedTag::MsgId).unwrap()
+                ),
+                None => tracing::warn!("NOTICE message had no kind"),
+            };
+            return;
+        }
+        AnySemantic::Ping(msg) => {
+            cx.bot_tx
+                .send(BotCommand::SendRawIrc(
+                    msg.respond().to_owned(),
+                    cx.connection_idx,
+                ))
+                .await
+                .unwrap();
+            return;
+        }
+        AnySemantic::AuthSuccessful(_msg) => {
+            tracing::info!("auth successful");
+            return;
+        }
+        AnySemantic::Reconnect(_msg) => {
+            cx.bot_tx
+                .send(BotCommand::Reconnect(cx.connection_idx))
+                .await
+                .unwrap();
+            return;
+        }
+        AnySemantic::PrivMsg(_msg) => (),
+        AnySemantic::Numeric(_msg) => return,
+        AnySemantic::ClearChat(_) | AnySemantic::ClearMsg(_) => {
+            match ModerationEvent::from_any(&msg) {
+                Some(event) => tracing::info!("moderation event: {event:?}"),
+                None => tracing::warn!("malformed moderation message: {:?}", msg.inner()),
             }
+            return;
         }
-        let gcx = GuardContext {
-            data_store: &Default::default(),
-            message: &msg,
-        };
-        for c in &catchall {
-            let cx = HandlerContext {
-                msg: msg.clone(),
-                connection_idx: cx.connection_idx,
-                bot_tx: cx.bot_tx.clone(),
-                data_store: cx.data_store.clone(),
-            };
-            let c = c.clone();
-            tokio::task::spawn_local(async move {
-                c.handle(cx).await;
-            });
+        AnySemantic::UserState(msg) => {
+            tracing::debug!("received userstate from irc: {:?}", msg.roles());
+            return;
+        }
+        msg => {
+            tracing::warn!("untreated message kind: {:?}", msg.redacted());
+            return;
         }
-        let Some(cmd) = cmds.iter().find(|c| c.matches(&gcx)).cloned() else {
-            continue;
+    }
+    let gcx = GuardContext {
+        data_store: &Default::default(),
+        message: &msg,
+    };
+    for c in catchall {
+        let cx = HandlerContext {
+            msg: msg.clone(),
+            connection_idx: cx.connection_idx,
+            bot_tx: cx.bot_tx.clone(),
+            data_store: cx.data_store.clone(),
+            span: cx.span.clone(),
         };
-        tokio::task::spawn_local(async move {
+        let c = c.clone();
+        let span = info_span!("handler", command = c.name());
+        tokio::task::spawn_local(
+            async move {
+                let start = Instant::now();
+                c.handle(cx).await;
+                histogram!(HANDLER_DURATION, "command" => c.name().to_owned())
+                    .record(start.elapsed());
+            }
+            .instrument(span),
+        );
+    }
+    let Some(cmd) = cmds.iter().find(|c| c.matches(&gcx)).cloned() else {
+        return;
+    };
+    let span = info_span!("handler", command = cmd.name());
+    tokio::task::spawn_local(
+        async move {
+            let start = Instant::now();
             cmd.handle(HandlerContext {
                 msg,
                 connection_idx: cx.connection_idx,
                 bot_tx: cx.bot_tx,
                 data_store: cx.data_store,
+                span: cx.span,
             })
             .await;
-        });
+            histogram!(HANDLER_DURATION, "command" => cmd.name().to_owned())
+                .record(start.elapsed());
+        }
+        .instrument(span),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    };
+
+    use tracing::Instrument;
+
+    use twixel_core::{
+        IrcCommand, IrcMessage,
+        auth::OAuth,
+        connection::RateLimiter,
+        irc_message::{AnySemantic, Kick},
+        testing::MockServer,
+    };
+
+    use super::{
+        Bot, BotData, BotSender, MAX_RECONNECT_DELAY, MIN_RECONNECT_DELAY, Reconnects,
+        SharedChatFilter, SharedChatMode, dispatch, message_span,
+    };
+    use crate::handler::{Command, CommandHandler, HandlerContext};
+
+    fn msg(raw: &str) -> AnySemantic {
+        AnySemantic::from(IrcMessage::new(raw.to_owned()).unwrap())
+    }
+
+    /// The same message sent in #one, as received in #one and #two, plus an
+    /// unrelated message in #two
+    fn fixtures() -> [AnySemantic; 3] {
+        [
+            msg(
+                "@id=abc;room-id=1;source-id=abc;source-room-id=1 :a!a@a.tmi.twitch.tv PRIVMSG #one :hi",
+            ),
+            msg(
+                "@id=def;room-id=2;source-id=abc;source-room-id=1 :a!a@a.tmi.twitch.tv PRIVMSG #two :hi",
+            ),
+            msg("@id=ghi;room-id=2 :b!b@b.tmi.twitch.tv PRIVMSG #two :hey"),
+        ]
+    }
+
+    fn handled(mode: SharedChatMode, msgs: &[AnySemantic]) -> Vec<bool> {
+        let mut filter = SharedChatFilter::new(mode);
+        msgs.iter().map(|m| filter.check(m)).collect()
+    }
+
+    #[derive(Clone, Default)]
+    struct Logs(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Logs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn received_credentials_redacted() {
+        let logs = Logs::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::TRACE)
+            .with_ansi(false)
+            .with_writer({
+                let logs = logs.clone();
+                move || logs.clone()
+            })
+            .finish();
+        // tests run on a single thread
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let (bot_tx, _bot_rx) = tokio::sync::mpsc::channel(1);
+        for raw in ["AUTHENTICATE c2VjcmV0", "PASS oauth:hunter2"] {
+            let msg = msg(raw);
+            let span = message_span(&msg, 0);
+            let cx = HandlerContext {
+                msg,
+                connection_idx: 0,
+                bot_tx: BotSender(bot_tx.clone()),
+                data_store: Arc::new(BotData::default()),
+                span: span.clone(),
+            };
+            dispatch(cx, &[], &[]).instrument(span).await;
+        }
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        assert!(
+            logs.contains("AUTHENTICATE [credentials redacted]"),
+            "{logs}"
+        );
+        assert!(logs.contains("[user token redacted]"), "{logs}");
+        assert!(!logs.contains("c2VjcmV0"), "{logs}");
+        assert!(!logs.contains("hunter2"), "{logs}");
+    }
+
+    #[tokio::test]
+    async fn handler_extracts_kick() {
+        let kicked = Arc::new(Mutex::new(None));
+        let handler = {
+            let kicked = kicked.clone();
+            async move |kick: Kick| {
+                *kicked.lock().unwrap() = kick.target_login().map(|l| l.to_string());
+            }
+        };
+
+        let (bot_tx, _bot_rx) = tokio::sync::mpsc::channel(1);
+        let msg = msg(":op!op@host KICK #foo ferris :no crabs");
+        let span = message_span(&msg, 0);
+        let cx = HandlerContext {
+            msg,
+            connection_idx: 0,
+            bot_tx: BotSender(bot_tx),
+            data_store: Arc::new(BotData::default()),
+            span,
+        };
+        handler.handle(cx).await;
+
+        assert_eq!(kicked.lock().unwrap().as_deref(), Some("ferris"));
+    }
+
+    #[test]
+    fn reconnect_backoff() {
+        let mut reconnects = Reconnects::default();
+        let now = Instant::now();
+        reconnects.schedule(0);
+        assert_eq!(reconnects.due(Instant::now()), [0]);
+
+        // each failure waits twice as long, up to the maximum
+        assert_eq!(reconnects.failed(0, now), MIN_RECONNECT_DELAY);
+        assert!(reconnects.due(now).is_empty());
+        assert_eq!(reconnects.due(now + MIN_RECONNECT_DELAY), [0]);
+        assert_eq!(reconnects.failed(0, now), MIN_RECONNECT_DELAY * 2);
+        for _ in 0..10 {
+            reconnects.failed(0, now);
+        }
+        assert_eq!(reconnects.failed(0, now), MAX_RECONNECT_DELAY);
+
+        // scheduling again doesn't reset the backoff
+        reconnects.schedule(0);
+        assert!(reconnects.due(now).is_empty());
+    }
+
+    #[test]
+    fn shared_chat_all() {
+        assert_eq!(
+            handled(SharedChatMode::All, &fixtures()),
+            [true, true, true]
+        );
+    }
+
+    #[test]
+    fn shared_chat_once_per_source() {
+        assert_eq!(
+            handled(SharedChatMode::OncePerSource, &fixtures()),
+            [true, false, true]
+        );
+
+        // whichever copy comes first is handled
+        let [origin, copy, other] = fixtures();
+        assert_eq!(
+            handled(SharedChatMode::OncePerSource, &[copy, origin, other]),
+            [true, false, true]
+        );
+    }
+
+    #[test]
+    fn shared_chat_origin_only() {
+        assert_eq!(
+            handled(SharedChatMode::OriginOnly, &fixtures()),
+            [true, false, true]
+        );
+
+        // other messages are never filtered
+        let ping = msg("PING :tmi.twitch.tv");
+        assert_eq!(
+            handled(SharedChatMode::OriginOnly, &[ping.clone(), ping]),
+            [true, true]
+        );
+    }
+
+    #[tokio::test]
+    async fn pings_answered_while_rate_limited() {
+        let server = MockServer::start().await.unwrap();
+        let auth = OAuth {
+            nick: "twixel".into(),
+            oauth: "token".into(),
+        };
+        let bot = Bot::with_server(auth, server.profile())
+            .await
+            .rate_limiter(RateLimiter::new(1, Duration::from_secs(60)))
+            .add_channels(["foo"])
+            .await
+            .add_command(Command::new(async || "hi", vec!["hi".into()], "%"));
+        tokio::spawn(bot.run());
+        server.wait_for_command(IrcCommand::Join).await;
+
+        // the second reply has to wait a minute for the rate limit
+        server.send_privmsg("foo", "bar", "%hi");
+        server.send_privmsg("foo", "bar", "%hi");
+        server.wait_for_command(IrcCommand::PrivMsg).await;
+
+        server.send_ping();
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            server.wait_for_command(IrcCommand::Pong),
+        )
+        .await
+        .expect("PING wasn't answered while a message was queued");
     }
 }
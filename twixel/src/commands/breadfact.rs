@@ -1,7 +1,10 @@
 use futures::TryFutureExt;
 
+use crate::util::http::CLIENT;
+
 pub async fn bread_fact() -> String {
-    match reqwest::get("https://website-backend.w3champions.com/api/players/breadworms%232156/game-mode-stats?gateWay=20&season=21")
+    match CLIENT.get("https://website-backend.w3champions.com/api/players/breadworms%232156/game-mode-stats?gateWay=20&season=21")
+        .send()
         .and_then(|r| r.json::<serde_json::Value>())
         .await
     {
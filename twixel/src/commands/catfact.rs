@@ -1,12 +1,16 @@
 use futures::TryFutureExt;
 
+use crate::util::http::CLIENT;
+
 #[derive(serde::Deserialize)]
 struct CatFact {
     fact: String,
 }
 
 pub async fn cat_fact() -> String {
-    match reqwest::get("https://catfact.ninja/fact")
+    match CLIENT
+        .get("https://catfact.ninja/fact")
+        .send()
         .and_then(|r| r.json::<CatFact>())
         .await
     {
@@ -1,6 +1,6 @@
 use serde::Serialize;
 
-use crate::{config::CONFIG, handler::extract::MessageText};
+use crate::{config::CONFIG, handler::extract::MessageText, util::http::CLIENT};
 
 #[derive(Serialize)]
 struct ResponsesRequest {
@@ -20,8 +20,7 @@ pub async fn gpt(MessageText(text): MessageText) -> String {
         input: text,
     };
 
-    let client = reqwest::Client::new();
-    match client
+    match CLIENT
         .post("https://api.openai.com/v1/responses")
         .bearer_auth(api_key)
         .json(&req)
@@ -6,7 +6,7 @@ use crate::handler::{
 pub async fn join(MessageText(msg): MessageText) -> impl IntoResponse {
     let args = msg.split_ascii_whitespace().skip(1).collect::<Vec<_>>();
 
-    log::info!("Joining {}", args.join(", "));
+    tracing::info!("Joining {}", args.join(", "));
 
     let joins: Vec<BotResponse> = args
         .iter()
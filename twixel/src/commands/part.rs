@@ -10,7 +10,10 @@ pub async fn part(
     let args = msg.split_ascii_whitespace().skip(1).collect::<Vec<_>>();
 
     if args.is_empty() {
-        ("byeeee :333".into(), vec![BotResponse::Part(source_chan)])
+        (
+            "byeeee :333".into(),
+            vec![BotResponse::Part(source_chan.into())],
+        )
     } else {
         let channels = args.join(", ");
 
@@ -1,16 +1,19 @@
-use reqwest::Client;
 use serde::Deserialize;
 use twixel_core::irc_message::PrivMsg;
 
+use crate::util::http::CLIENT;
+
 #[derive(Debug, Deserialize)]
 struct HasteResp {
-    key: String
+    key: String,
 }
 
 pub async fn raw(msg: PrivMsg) -> String {
-
-    let resp = Client::new().post("https://haste.potat.app/documents")
-        .body(reqwest::Body::from(serde_json::to_string_pretty(&*msg).unwrap()))
+    let resp = CLIENT
+        .post("https://haste.potat.app/documents")
+        .body(reqwest::Body::from(
+            serde_json::to_string_pretty(&*msg).unwrap(),
+        ))
         .send()
         .await;
     let Ok(r) = resp else {
@@ -44,7 +44,7 @@ pub async fn handle_joefish(
         return None;
     };
     let (fisherman, catch) = (fisherman.as_str(), catch.as_str());
-    log::info!("new catch! {catch:?}");
+    tracing::info!("new catch! {catch:?}");
 
     let mut conn = pool.acquire().await.unwrap();
 
@@ -58,7 +58,7 @@ pub async fn handle_joefish(
         }
     };
     if user.fish_reminder() {
-        log::info!("fish reminder set for {fisherman}");
+        tracing::info!("fish reminder set for {fisherman}");
         let mut reminder = format!("@{fisherman}, ");
         write_random_spice_text(&mut reminder, catch).unwrap();
         Some(DelayedResponse(
@@ -66,7 +66,7 @@ pub async fn handle_joefish(
             Duration::from_secs(30 * 60),
         ))
     } else {
-        log::debug!("fish caught but no reminder set for {fisherman}");
+        tracing::debug!("fish caught but no reminder set for {fisherman}");
         None
     }
 }
@@ -95,7 +95,7 @@ pub async fn remindfish(user: TwixelUser, Data(pool): Data<SqlitePool>) -> &'sta
             }
         }
         Err(e) => {
-            log::error!("Failed to create fish reminder: {e}");
+            tracing::error!("Failed to create fish reminder: {e}");
             "Failed to create fish reminder"
         }
     }
@@ -25,7 +25,7 @@ pub async fn suggest(
     match conn.execute(query).await {
         Ok(_) => Some("suggestion saved successfully!".into()),
         Err(err) => {
-            log::error!("{err}");
+            tracing::error!("{err}");
             Some(err.to_string())
         }
     }
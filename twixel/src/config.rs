@@ -17,6 +17,7 @@ pub struct Config {
 pub struct Twitch {
     pub token: String,
     pub login: String,
+    #[allow(dead_code)]
     pub id: String,
 }
 
@@ -14,7 +14,7 @@ use twixel_core::irc_message::{AnySemantic, PrivMsg};
 use crate::{
     bot::BotCommand,
     handler::{CommandHandler, HandlerContext, response::BotResponse},
-    util::sanitize_output,
+    util::{http, sanitize_output},
 };
 
 #[derive(Clone)]
@@ -71,7 +71,7 @@ fn eval_thread() -> tokio::sync::mpsc::Sender<HandlerContext> {
                 local_set.spawn_local(async move {
                     loop {
                         let Some(cx) = rx.recv().await else { break };
-                        log::debug!("received js task");
+                        tracing::debug!("received js task");
 
                         tokio::task::spawn_local(async move {
                             let rt = AsyncRuntime::new().unwrap();
@@ -87,7 +87,7 @@ fn eval_thread() -> tokio::sync::mpsc::Sender<HandlerContext> {
                             let context = AsyncContext::full(&rt).await.unwrap();
 
                             tokio::task::spawn_local(async move {
-                                log::debug!("driving new quickjs runtime");
+                                tracing::debug!("driving new quickjs runtime");
                                 rt.drive().await;
                             });
 
@@ -149,12 +149,12 @@ fn repl_print_value(val: Value<'_>) -> LocalBoxFuture<'_, String> {
             | Type::BigInt
             | Type::Constructor
             | Type::Symbol
-            | Type::Uninitialized => Coerced::<String>::from_js(&ctx, val.to_owned()).map(|i| i.0).unwrap(),
+            | Type::Uninitialized => Coerced::<String>::from_js(&ctx, val.to_owned())
+                .map(|i| i.0)
+                .unwrap(),
             Type::String => val
                 .as_string()
-                .map(|i| {
-                    i.to_string().unwrap_or("invalid UTF-8 string".into())
-                })
+                .map(|i| i.to_string().unwrap_or("invalid UTF-8 string".into()))
                 .unwrap(),
             Type::Array | Type::Exception | Type::Object | Type::Module | Type::Unknown => ctx
                 .json_stringify(val)
@@ -164,17 +164,22 @@ fn repl_print_value(val: Value<'_>) -> LocalBoxFuture<'_, String> {
                 Ok(v) => repl_print_value(v).await,
                 Err(e) => rquickjs_err_to_pretty(e, &ctx),
             },
-            Type::Proxy => {
-                ctx.json_stringify(val.into_proxy().unwrap()).and_then(|i| i.map(|s| s.to_string()).unwrap()).expect("WAAAA")
-            }
+            Type::Proxy => ctx
+                .json_stringify(val.into_proxy().unwrap())
+                .and_then(|i| i.map(|s| s.to_string()).unwrap())
+                .expect("WAAAA"),
         }
     })
 }
 
 async fn eval(ctx: Ctx<'_>, cx: HandlerContext) {
-    let source_channel: String = cx.msg.get_param(0).unwrap().split_at(1).1.into();
-    let Some(code) = cx
-        .msg
+    let AnySemantic::PrivMsg(msg) = cx.msg else {
+        return;
+    };
+    let Some(source_channel) = msg.channel_login().map(|c| c.into_owned()) else {
+        return;
+    };
+    let Some(code) = msg
         .get_param(1)
         .and_then(|s| s.split_once(' ').map(|s| s.1))
         .map(|s| s.to_string())
@@ -186,10 +191,6 @@ async fn eval(ctx: Ctx<'_>, cx: HandlerContext) {
 
     globals.remove("eval").unwrap();
 
-    let AnySemantic::PrivMsg(msg) = cx.msg else {
-        return;
-    };
-
     let js_ctx = JsContext::new(msg);
 
     globals.set("context", js_ctx).unwrap();
@@ -264,13 +265,14 @@ impl JsContext {
     fn new(msg: PrivMsg) -> Self {
         Self {
             msg: msg.message_text().to_owned(),
-            user_login: msg.sender_login().map(|s| s.to_owned()),
-            user_id: msg.sender_id().map(|s| s.to_owned()),
+            user_login: msg.sender_login().map(|s| s.to_string()),
+            user_id: msg.sender_id().map(|s| s.to_string()),
         }
     }
 }
 #[derive(smart_default::SmartDefault)]
 struct JsRequestInit {
+    #[allow(dead_code)]
     body: Option<String>,
     #[default(reqwest::Method::GET)]
     method: reqwest::Method,
@@ -302,7 +304,7 @@ static JS_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
     );
     default_headers.insert(ACCEPT, "application/json, text/plain".parse().unwrap());
 
-    reqwest::ClientBuilder::new()
+    http::client_builder()
         .default_headers(default_headers)
         .https_only(true)
         .user_agent(format!("twixel-rs/{}", env!("CARGO_PKG_VERSION")))
@@ -220,7 +220,7 @@ impl Guard for UserGuard {
             return false;
         };
         msg.sender_id()
-            .map(|t| self.user_ids.contains(t))
+            .map(|t| self.user_ids.contains(t.as_str()))
             .unwrap_or(false)
     }
 
@@ -256,7 +256,7 @@ impl Guard for ChannelGuard {
             return false;
         };
         msg.channel_id()
-            .map(|t| self.channel_ids.contains(t))
+            .map(|t| self.channel_ids.contains(t.as_str()))
             .unwrap_or(false)
     }
 
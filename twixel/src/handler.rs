@@ -2,11 +2,11 @@ use std::{future::Future, marker::PhantomData, pin::Pin, sync::Arc};
 
 use extract::{Extract, ExtractFull};
 use guard::CommandGuard;
-use tokio::sync::mpsc::Sender;
+use tracing::{Span, debug_span};
 use twixel_core::irc_message::AnySemantic;
 
 use crate::{
-    bot::{BotCommand, BotData},
+    bot::{BotCommand, BotData, BotSender},
     guard::{AndGuard, Guard, GuardContext, NoOpGuard, OrGuard},
     handler::response::{BotResponse, IntoResponse},
 };
@@ -19,8 +19,10 @@ pub mod response;
 pub struct HandlerContext {
     pub msg: AnySemantic,
     pub connection_idx: usize,
-    pub bot_tx: tokio::sync::mpsc::Sender<BotCommand>,
+    pub bot_tx: BotSender,
     pub data_store: Arc<BotData>,
+    /// Span of the received message, handlers run within it
+    pub span: Span,
 }
 
 pub type DynHandler = Pin<
@@ -181,6 +183,7 @@ fn test_handler_trait() {
 /// Holds command logic and information, you should use [CommandBuilder](self::CommandBuilder) instead
 /// if you plan on adding multiple [Guard](crate::command::Guard)s
 pub struct Command {
+    name: String,
     guard: Box<dyn Guard>,
     pub handler: DynHandler,
 }
@@ -188,6 +191,7 @@ pub struct Command {
 impl Clone for Command {
     fn clone(&self) -> Self {
         Self {
+            name: self.name.clone(),
             guard: self.guard.clone_boxed(),
             handler: self.handler.clone_boxed(),
         }
@@ -201,6 +205,7 @@ impl Command {
         prefix: impl Into<String>,
     ) -> Self {
         Self {
+            name: names.first().cloned().unwrap_or_default(),
             handler: handler.clone_boxed(),
             guard: CommandGuard::new(names, prefix.into()).clone_boxed(),
         }
@@ -208,26 +213,31 @@ impl Command {
 
     pub fn new_catchall(handler: DynHandler) -> Self {
         Self {
+            name: "catchall".into(),
             handler,
             guard: NoOpGuard {}.clone_boxed(),
         }
     }
 
-    async fn handle_resp(resp: BotResponse, privmsg: &AnySemantic, sender: Sender<BotCommand>) {
+    /// Name of the command, its first name for commands matched by name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn handle_resp(resp: BotResponse, privmsg: &AnySemantic, sender: BotSender) {
         match resp {
             BotResponse::Message(msg) => {
                 if let AnySemantic::PrivMsg(privmsg) = privmsg {
-                    sender
-                        .send(BotCommand::respond(privmsg, msg, false))
-                        .await
-                        .unwrap();
+                    match BotCommand::respond(privmsg, msg, false) {
+                        Some(cmd) => sender.send(cmd).await.unwrap(),
+                        None => tracing::warn!(
+                            "can't respond to message with malformed channel: {:?}",
+                            privmsg.inner()
+                        ),
+                    }
                 }
             }
-            BotResponse::Raw(raw) => {
-                sender.send(BotCommand::SendRawIrc(raw, 0))
-                    .await
-                    .unwrap()
-            }
+            BotResponse::Raw(raw) => sender.send(BotCommand::SendRawIrc(raw, 0)).await.unwrap(),
             BotResponse::Join(chan) => {
                 sender.send(BotCommand::JoinChannel(chan)).await.unwrap();
             }
@@ -257,11 +267,12 @@ impl Command {
     }
 
     pub fn matches(&self, cx: &GuardContext) -> bool {
-        self.guard.check(cx)
+        debug_span!("guard", command = %self.name).in_scope(|| self.guard.check(cx))
     }
 }
 
 pub struct CommandBuilder<T, H: CommandHandler<T>, G: Guard + Clone> {
+    name: String,
     pub handler: H,
     guard: G,
     _marker: PhantomData<T>,
@@ -272,6 +283,7 @@ impl<T, H: CommandHandler<T> + Clone, G: Guard + Clone + Send + Sync> Clone
 {
     fn clone(&self) -> Self {
         Self {
+            name: self.name.clone(),
             handler: self.handler.clone(),
             guard: self.guard.clone(),
             _marker: PhantomData,
@@ -282,6 +294,7 @@ impl<T, H: CommandHandler<T> + Clone, G: Guard + Clone + Send + Sync> Clone
 impl<T, H: CommandHandler<T>> CommandBuilder<T, H, CommandGuard> {
     pub fn new(handler: H, names: Vec<String>, prefix: impl Into<String>) -> Self {
         Self {
+            name: names.first().cloned().unwrap_or_default(),
             handler,
             guard: CommandGuard::new(names, prefix),
             _marker: PhantomData,
@@ -292,6 +305,7 @@ impl<T, H: CommandHandler<T>> CommandBuilder<T, H, CommandGuard> {
 impl<T, H: CommandHandler<T>, G: Guard + Clone + Send + 'static> CommandBuilder<T, H, G> {
     pub fn build(self) -> Command {
         Command {
+            name: self.name,
             guard: Box::new(self.guard),
             handler: self.handler.clone_boxed(),
         }
@@ -302,17 +316,20 @@ impl<T, H: CommandHandler<T>, G: Guard + Clone + Send + 'static> CommandBuilder<
         guard: G2,
     ) -> CommandBuilder<T, H, AndGuard<G, G2>> {
         CommandBuilder {
+            name: self.name,
             handler: self.handler,
             guard: self.guard.and(guard),
             _marker: PhantomData,
         }
     }
 
+    #[allow(dead_code)]
     pub fn or<G2: Guard + Clone + Send + Sync>(
         self,
         guard: G2,
     ) -> CommandBuilder<T, H, OrGuard<G, G2>> {
         CommandBuilder {
+            name: self.name,
             handler: self.handler,
             guard: self.guard.or(guard),
             _marker: PhantomData,
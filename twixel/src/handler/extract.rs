@@ -7,10 +7,7 @@ use std::{
 };
 
 use futures::FutureExt;
-use twixel_core::{
-    IrcCommand,
-    irc_message::AnySemantic,
-};
+use twixel_core::{ChannelLogin, irc_message::AnySemantic};
 
 use crate::{bot::BotData, handler::response::IntoResponse};
 
@@ -94,10 +91,17 @@ impl_semantic!(
     UserNotice,
     Reconnect,
     Whisper,
+    User,
+    Mode,
+    Topic,
+    Kick,
+    Quit,
+    Authenticate,
     UnsupportedError,
     UserList,
     AuthSuccessful,
-    Useless
+    Numeric,
+    Other
 );
 
 impl<T: Extract> Extract for Option<T> {
@@ -111,6 +115,7 @@ impl<T: Extract> Extract for Option<T> {
     }
 }
 
+#[allow(dead_code)]
 pub enum Lazy<T: Extract + Send> {
     NotInitialized {
         init: Pin<Box<dyn Future<Output = Result<T, T::Error>> + Send + 'static>>,
@@ -118,6 +123,7 @@ pub enum Lazy<T: Extract + Send> {
     Initialized(Result<T, T::Error>),
 }
 
+#[allow(dead_code)]
 impl<T: Extract + Send> Lazy<T> {
     pub async fn value(mut self) -> Result<T, <T as Extract>::Error> {
         self.init().await;
@@ -150,6 +156,7 @@ where
 pub struct MessageText(pub String);
 
 impl MessageText {
+    #[allow(dead_code)]
     pub fn split_first_rest(&self) -> Option<(&str, &str)> {
         self.0.split_once(' ')
     }
@@ -180,6 +187,7 @@ impl Extract for MessageText {
 
 /// Extractor for sender's login
 #[derive(Clone, Debug)]
+#[allow(dead_code)]
 pub struct Username(pub String);
 
 impl Extract for Username {
@@ -219,7 +227,7 @@ impl Extract for SenderId {
 
 /// Extractor for source channel's login
 #[derive(Clone, Debug)]
-pub struct Channel(pub String);
+pub struct Channel(pub ChannelLogin);
 
 impl Extract for Channel {
     type Error = ();
@@ -228,19 +236,12 @@ impl Extract for Channel {
         msg: &AnySemantic,
         _data: Arc<BotData>,
     ) -> impl Future<Output = Result<Self, Self::Error>> + Send {
-        let chan = if msg.get_command() == IrcCommand::PrivMsg {
-            let chan_param = msg
-                .get_param(0)
-                .expect("no channel param in PrivMsg elisWot");
-            if !chan_param.starts_with('#') {
-                panic!("channel param malformed")
-            } else {
-                Some(Self(chan_param.split_at(1).1.to_string()))
+        ready(match msg {
+            AnySemantic::PrivMsg(msg) => {
+                msg.channel_login().map(|c| Self(c.into_owned())).ok_or(())
             }
-        } else {
-            None
-        };
-        core::future::ready(chan.ok_or(()))
+            _ => Err(()),
+        })
     }
 }
 
@@ -9,6 +9,7 @@ use twixel_core::MessageBuilder;
 
 pub enum BotResponse {
     Message(String),
+    #[allow(dead_code)]
     Raw(MessageBuilder<'static>),
     Join(String),
     Part(String),
@@ -3,7 +3,7 @@
 use std::str::FromStr;
 
 use bot::Bot;
-use cli::ARGS;
+use cli::{ARGS, LogFormat};
 use commands::{
     argtest, bread_fact, cat_fact, handle_joefish, join, part, sql, strdbg, suggest, test,
 };
@@ -11,6 +11,12 @@ use config::CONFIG;
 use guard::UserGuard;
 use handler::{Command, CommandBuilder, response::BotResponse};
 use sqlx::{Sqlite, sqlite::SqliteConnectOptions};
+use tracing_subscriber::EnvFilter;
+use twixel_core::{
+    connection::Dedup,
+    recording::{Pace, Recorder, Replayer},
+};
+use util::http::PROXY;
 
 use crate::commands::{gpt, raw};
 
@@ -26,15 +32,28 @@ mod util;
 
 const JULIA_ID: &str = "173685614";
 
+/// Logs to stderr in the format chosen with `--log-format`
+fn init_tracing() {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| {
+        EnvFilter::new(if cfg!(debug_assertions) {
+            "debug"
+        } else {
+            "info"
+        })
+    });
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+    match ARGS.log_format {
+        LogFormat::Text => subscriber.init(),
+        LogFormat::Json => subscriber.json().with_span_list(true).init(),
+    }
+    if !ARGS.dotenv_found {
+        tracing::warn!(".env file was not found")
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
-    env_logger::init_from_env(env_logger::Env::new().default_filter_or(
-        if cfg!(debug_assertions) {
-            "DEBUG"
-        } else {
-            "INFO"
-        },
-    ));
+    init_tracing();
 
     let db_url = format!(
         "sqlite://{}",
@@ -63,10 +82,40 @@ async fn main() -> Result<(), anyhow::Error> {
         .await
         .expect("failed to run migrations");
 
-    let bot = Bot::new(CONFIG.twitch.login.clone(), CONFIG.twitch.token.clone())
-        .await
-        .add_channels(ARGS.channels.iter().map(|s| s.as_str()))
+    if let Some(addr) = ARGS.metrics_addr {
+        util::metrics::serve(addr)?;
+        tracing::info!("serving metrics on http://{addr}");
+    }
+
+    let mut bot = Bot::new(CONFIG.twitch.login.clone(), CONFIG.twitch.token.clone())
         .await
+        .dedup(Dedup::new());
+    if let Some(proxy) = &*PROXY {
+        tracing::info!("connecting through {proxy}");
+        bot = bot.proxy(proxy.clone());
+    }
+    if let Some(path) = &ARGS.record {
+        bot = bot.recorder(Recorder::create(path)?);
+    }
+    let bot = match &ARGS.replay {
+        Some(path) => {
+            let pace = if ARGS.replay_realtime {
+                Pace::RealTime
+            } else {
+                Pace::AsFastAsPossible
+            };
+            tracing::info!("replaying {}", path.display());
+            bot.replay(Replayer::open_rotated(path)?.stream(pace))
+        }
+        None => {
+            bot.add_channels(ARGS.channels.iter().map(|s| s.as_str()))
+                .await
+        }
+    };
+
+    let bot = bot
+        .shared_chat_mode(ARGS.shared_chat)
+        .split_marker(" …")
         .data(db)
         .add_catchall(handle_joefish)
         .add_command(Command::new(async || "hi", vec!["hi".into()], "%"))
@@ -141,7 +190,7 @@ async fn main() -> Result<(), anyhow::Error> {
             .build(),
         );
 
-    log::info!("twixel bot started");
+    tracing::info!("twixel bot started");
 
     bot.run().await;
     Ok(())
@@ -1,8 +1,11 @@
 use unicode_segmentation::UnicodeSegmentation;
 
 pub mod db;
+pub mod http;
+pub mod metrics;
 
 /// returns a &str that is at most `limit` bytes long
+#[allow(dead_code)]
 pub fn limit_str(value: &str, limit: usize) -> &str {
     let boundary = value
         .char_indices()
@@ -49,6 +52,42 @@ pub fn limit_str_at_graphemes(value: &str, limit: usize) -> &str {
     }
 }
 
+/// splits a message into parts that are at most `limit` chars long
+///
+/// parts are split at whitespace so words, emotes and urls stay whole, only a single word
+/// longer than `limit` is split, at grapheme boundaries. `marker` is appended as is to every
+/// part except the last one, and counts towards the limit
+pub fn split_message(value: &str, limit: usize, marker: Option<&str>) -> Vec<String> {
+    let marker = marker.unwrap_or_default();
+    let part_limit = limit.saturating_sub(marker.chars().count()).max(1);
+    let mut parts = Vec::new();
+    let mut rest = value.trim();
+
+    while rest.chars().count() > limit {
+        let head = limit_str_chars(rest, part_limit);
+        let cut = if rest[head.len()..].starts_with(char::is_whitespace) {
+            head.len()
+        } else if let Some(space) = head.rfind(char::is_whitespace) {
+            space
+        } else {
+            match limit_str_at_graphemes(rest, part_limit) {
+                // a single grapheme longer than the limit, can't do better than sending it whole
+                "" => rest.graphemes(true).next().map_or(rest.len(), str::len),
+                word => word.len(),
+            }
+        };
+
+        let (part, tail) = rest.split_at(cut);
+        parts.push(format!("{}{marker}", part.trim_end()));
+        rest = tail.trim_start();
+    }
+
+    if !rest.is_empty() || parts.is_empty() {
+        parts.push(rest.to_owned());
+    }
+    parts
+}
+
 /// prevents message output from running commands over twitch IRC
 pub fn sanitize_output(out: &mut String) {
     if out.starts_with(['.', '/']) {
@@ -107,3 +146,38 @@ fn limiting() {
     assert_eq!(limit_str_chars(EMOJI, 3).chars().count(), 3);
     assert_eq!(limit_str_at_graphemes(EMOJI, 3), "");
 }
+
+#[test]
+fn splitting() {
+    // under limit
+    assert_eq!(split_message("hello there", 11, None), ["hello there"]);
+    assert_eq!(split_message("", 11, None), [""]);
+
+    // at whitespace
+    assert_eq!(
+        split_message("hello there  general kenobi", 13, None),
+        ["hello there", "general", "kenobi"]
+    );
+    assert_eq!(
+        split_message("Kappa Kappa PogChamp Kappa", 14, None),
+        ["Kappa Kappa", "PogChamp Kappa"]
+    );
+    assert_eq!(
+        split_message("look at https://example.com/some/path now", 30, None),
+        ["look at", "https://example.com/some/path", "now"]
+    );
+
+    // with continuation markers
+    assert_eq!(
+        split_message("one two three four", 10, Some(" …")),
+        ["one two …", "three four"]
+    );
+
+    // words longer than the limit
+    assert_eq!(
+        split_message("aaaaaaaaaa b", 4, None),
+        ["aaaa", "aaaa", "aa b"]
+    );
+    assert_eq!(split_message("🧞‍♀️🧞‍♀️ hi", 7, None), ["🧞‍♀️", "🧞‍♀️ hi"]);
+    assert_eq!(split_message("🧞‍♀️", 3, None), ["🧞‍♀️"]);
+}
@@ -17,6 +17,7 @@ use crate::{
 };
 
 #[derive(Debug, Clone)]
+#[allow(dead_code)]
 pub struct TwitchUser {
     twitch_id: String,
     twitch_login: String,
@@ -29,12 +30,14 @@ pub struct TwixelUser {
     id: i64,
     #[getset(get_copy = "pub")]
     creation_ts: DateTime<Utc>,
+    #[allow(dead_code)]
     #[getset(get = "pub")]
     role: Option<String>,
     #[getset(get_copy = "pub")]
     fish_reminder: bool,
 }
 
+#[allow(dead_code)]
 pub async fn get_twitch_user_by_twitch_id(
     executor: impl Executor<'_, Database = sqlx::Sqlite>,
     id: &str,
@@ -117,7 +120,7 @@ pub async fn get_user_by_twitch_login(
 
 impl IntoResponse for sqlx::Error {
     fn into_response(self) -> Ready<Option<BotResponse>> {
-        log::error!("SQLX error: {self}");
+        tracing::error!("SQLX error: {self}");
         ready(None)
     }
 }
@@ -133,15 +136,16 @@ impl Extract for TwixelUser {
         };
 
         let Some(user_login) = msg.sender_login() else {
-            log::error!("Failed to get sender login from message!");
+            tracing::error!("Failed to get sender login from message!");
             return Err(None);
         };
         let Some(user_id) = msg.sender_id() else {
-            log::error!("Failed to get sender id from message!");
+            tracing::error!("Failed to get sender id from message!");
             return Err(None);
         };
+        let (user_login, user_id) = (user_login.as_str(), user_id.as_str());
         let Some(user_display_name) = msg.get_tag(OwnedTag::DisplayName) else {
-            log::error!("Failed to get display name from message!");
+            tracing::error!("Failed to get display name from message!");
             return Err(None);
         };
 
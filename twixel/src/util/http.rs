@@ -0,0 +1,35 @@
+use std::sync::LazyLock;
+
+use twixel_core::connection::Proxy;
+
+use crate::cli::ARGS;
+
+/// Proxy outbound connections are tunneled through, from `--proxy` or the
+/// `HTTPS_PROXY` and `ALL_PROXY` environment variables, except to the hosts in
+/// `NO_PROXY`
+pub static PROXY: LazyLock<Option<Proxy>> = LazyLock::new(|| {
+    ARGS.proxy
+        .clone()
+        .map(Proxy::no_proxy_from_env)
+        .or_else(Proxy::from_env)
+});
+
+/// HTTP client shared by commands
+pub static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    client_builder()
+        .build()
+        .expect("failed to build HTTP client")
+});
+
+/// A [reqwest::ClientBuilder] that goes through [PROXY]
+pub fn client_builder() -> reqwest::ClientBuilder {
+    let builder = reqwest::ClientBuilder::new();
+    match &*PROXY {
+        Some(proxy) => builder.proxy(
+            reqwest::Proxy::all(proxy.url())
+                .expect("invalid proxy URL")
+                .no_proxy(reqwest::NoProxy::from_env()),
+        ),
+        None => builder,
+    }
+}
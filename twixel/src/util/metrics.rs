@@ -0,0 +1,26 @@
+use std::net::SocketAddr;
+
+use metrics::{Unit, describe_histogram};
+use metrics_exporter_prometheus::{BuildError, Matcher, PrometheusBuilder};
+
+/// Histogram of how long handlers take to run, in seconds, labeled by
+/// `command`
+pub const HANDLER_DURATION: &str = "twixel_handler_duration_seconds";
+
+/// Bucket bounds of the histograms in seconds, from a millisecond to half a
+/// minute for slow handlers calling out to APIs
+const SECONDS_BUCKETS: &[f64] = &[
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+/// Serves every metric in the Prometheus text format on `addr`, at any path.
+/// Must be called from within the tokio runtime
+pub fn serve(addr: SocketAddr) -> Result<(), BuildError> {
+    PrometheusBuilder::new()
+        .with_http_listener(addr)
+        .set_buckets_for_metric(Matcher::Suffix("_seconds".into()), SECONDS_BUCKETS)?
+        .install()?;
+    twixel_core::metrics::describe();
+    describe_histogram!(HANDLER_DURATION, Unit::Seconds, "time taken by handlers");
+    Ok(())
+}
@@ -2,6 +2,7 @@ use std::hint::black_box;
 
 use divan::{Bencher, counter::BytesCount};
 use mimalloc::MiMalloc;
+use twixel_core::ChannelLogin;
 use twixel_core::irc_message::{
     builder::MessageBuilder, message::IrcMessage, prefix::OwnedPrefix, tags::OwnedTag,
 };
@@ -29,10 +30,27 @@ fn deserialize_shit_ton(bencher: Bencher) {
         });
 }
 
+#[divan::bench(threads = [0, 1, 4], min_time = 1)]
+fn deserialize_and_read_tags(bencher: Bencher) {
+    bencher
+        .with_inputs(|| SHIT_TON.lines().take(COUNT).collect::<Vec<&str>>())
+        .input_counter(|i| i.len())
+        .input_counter(|i| BytesCount::new(i.iter().fold(0, |r, i| r + i.len())))
+        .bench_local_values(move |messages| {
+            for i in messages.into_iter() {
+                let msg = IrcMessage::<String>::try_from(i).unwrap();
+                black_box(msg.get_tag_raw(OwnedTag::Id));
+                black_box(msg.get_tag_raw(OwnedTag::UserId));
+                black_box(msg.get_tag_raw(OwnedTag::Badges));
+                black_box(msg.get_tag_raw_by_str("room-id"));
+            }
+        });
+}
+
 #[divan::bench]
 fn build_and_format_owned_messages() {
     let owned = black_box(
-        MessageBuilder::privmsg("juliapixel", "hi im julia!")
+        MessageBuilder::privmsg(&ChannelLogin::new("juliapixel").unwrap(), "hi im julia!")
             .add_tag(OwnedTag::Color, "#ffffff")
             .add_tag(OwnedTag::DisplayName, "Juliapixel")
             .add_tag(OwnedTag::Id, "12345678")
@@ -40,7 +58,7 @@ fn build_and_format_owned_messages() {
                 host: "juliapixel.com".into(),
             }),
     );
-    black_box(owned.build());
+    black_box(owned.build().unwrap());
 }
 
 fn main() {
@@ -13,6 +13,12 @@ pub trait AuthProvider {
     /// first param to a [`NICK`](crate::irc_message::Nick) message
     fn pass_nick(&mut self) -> (String, String);
 
+    /// Account name and password to authenticate with through SASL `PLAIN`,
+    /// only used when connecting to IRC servers other than Twitch
+    fn sasl_plain(&mut self) -> Option<(String, String)> {
+        None
+    }
+
     /// Provided method that returns a tuple of a [`PASS`](crate::irc_message::Pass)
     /// and a [`NICK`](crate::irc_message::Nick) message, to be sent to the IRC
     /// server
@@ -61,3 +67,110 @@ impl Debug for OAuth {
             .finish()
     }
 }
+
+/// SASL `PLAIN` auth, for IRC servers other than Twitch
+#[derive(Clone)]
+pub struct SaslPlain {
+    /// The nickname to use
+    pub nick: String,
+    /// The account to log into
+    pub account: String,
+    /// The account's password
+    pub password: String,
+}
+
+impl AuthProvider for SaslPlain {
+    fn pass_nick(&mut self) -> (String, String) {
+        (String::new(), self.nick.clone())
+    }
+
+    fn sasl_plain(&mut self) -> Option<(String, String)> {
+        Some((self.account.clone(), self.password.clone()))
+    }
+}
+
+impl Debug for SaslPlain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SaslPlain")
+            .field("nick", &self.nick)
+            .field("account", &self.account)
+            .field("password", &"[REDACTED]")
+            .finish()
+    }
+}
+
+/// Maximum length of the data in a single `AUTHENTICATE` message
+#[cfg(feature = "connection")]
+const AUTHENTICATE_CHUNK_LEN: usize = 400;
+
+/// Whether an `AUTHENTICATE` param only selects the mechanism, continues or
+/// aborts the exchange, rather than carrying credentials that mustn't be logged
+pub(crate) fn is_public_authenticate(param: &str) -> bool {
+    matches!(param.trim(), "PLAIN" | "+" | "*")
+}
+
+/// Params of the `AUTHENTICATE` messages that log into `account` through SASL
+/// `PLAIN`, split into chunks the server accepts
+#[cfg(feature = "connection")]
+pub(crate) fn sasl_plain_messages(account: &str, password: &str) -> Vec<String> {
+    let encoded = base64(format!("\0{account}\0{password}").as_bytes());
+    let mut chunks: Vec<String> = encoded
+        .as_bytes()
+        .chunks(AUTHENTICATE_CHUNK_LEN)
+        // base64 is always ASCII
+        .map(|c| String::from_utf8_lossy(c).into_owned())
+        .collect();
+    // a full last chunk means more data would follow, so it's terminated by a `+`
+    if encoded.len().is_multiple_of(AUTHENTICATE_CHUNK_LEN) {
+        chunks.push("+".into());
+    }
+    chunks
+}
+
+#[cfg(feature = "connection")]
+pub(crate) fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(all(test, feature = "connection"))]
+mod tests {
+    use super::{base64, sasl_plain_messages};
+
+    #[test]
+    fn sasl_plain() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+
+        assert_eq!(
+            sasl_plain_messages("jilles", "sesame"),
+            ["AGppbGxlcwBzZXNhbWU="]
+        );
+
+        // 300 bytes encode to exactly 400
+        let chunks = sasl_plain_messages(&"a".repeat(149), &"b".repeat(149));
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].len(), 400);
+        assert_eq!(chunks[1], "+");
+    }
+}
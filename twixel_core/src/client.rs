@@ -0,0 +1,768 @@
+use std::{
+    collections::VecDeque,
+    ops::Deref,
+    sync::{Arc, Mutex, RwLock},
+    task::{Poll, Waker},
+    time::{Duration, Instant},
+};
+
+use ::metrics::{counter, gauge};
+use futures_util::{Stream, StreamExt};
+use hashbrown::HashMap;
+use thiserror::Error;
+use tokio::sync::{broadcast, mpsc, oneshot};
+
+use crate::{
+    ChannelLogin, ConnectionPool, IrcMessage, MessageBuilder, UserId,
+    auth::AuthProvider,
+    connection::{LifecycleEvent, Proxy, RateLimiter, ServerProfile, error::PoolError},
+    irc_message::{AnySemantic, PrivMsg, UserState, tags::OwnedTag},
+    metrics,
+    recording::Recorder,
+    runtime::{self, Runtime},
+};
+
+/// Longest time waited between attempts to reconnect a connection
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+/// Time waited before retrying to reconnect a connection the first time
+const MIN_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+/// Amount of received messages a [Client] keeps until they are read by
+/// default, the oldest ones are dropped past it
+pub const DEFAULT_EVENT_CAPACITY: usize = 4096;
+
+/// Error returned by [Client] methods
+#[derive(Debug, Error)]
+pub enum ClientError {
+    /// The client's background task stopped, because it was shut down or
+    /// panicked
+    #[error("the client was shut down")]
+    Closed,
+    /// The message replied to had a missing or malformed channel param
+    #[error("the message's channel is missing or malformed")]
+    InvalidChannel,
+}
+
+/// Settings of a channel's chat room, from `ROOMSTATE` messages
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RoomSettings {
+    /// ID of the channel
+    pub room_id: Option<UserId>,
+    /// Only emotes can be sent
+    pub emote_only: bool,
+    /// Minutes users must have followed the channel for to chat, `None` if
+    /// followers-only mode is off
+    pub followers_only: Option<u32>,
+    /// Messages must be unique, also known as r9k mode
+    pub unique_chat: bool,
+    /// Seconds users must wait between messages, 0 if slow mode is off
+    pub slow: u32,
+    /// Only subscribers can chat
+    pub subs_only: bool,
+}
+
+impl RoomSettings {
+    /// Applies the settings of a `ROOMSTATE` message, which only contains the
+    /// settings that changed after the first one
+    fn update<C: Deref<Target = str>>(&mut self, msg: &IrcMessage<C>) {
+        let flag = |tag| msg.get_tag_raw(tag).map(|v| v == "1");
+        if let Some(id) = msg.get_tag_raw(OwnedTag::RoomId) {
+            self.room_id = UserId::new(id.to_owned()).ok();
+        }
+        if let Some(v) = flag(OwnedTag::EmoteOnly) {
+            self.emote_only = v;
+        }
+        if let Some(v) = msg.get_tag_raw(OwnedTag::FollowersOnly) {
+            self.followers_only = v.parse().ok();
+        }
+        if let Some(v) = flag(OwnedTag::R9K) {
+            self.unique_chat = v;
+        }
+        if let Some(v) = msg.get_tag_raw(OwnedTag::Slow) {
+            self.slow = v.parse().unwrap_or_default();
+        }
+        if let Some(v) = flag(OwnedTag::SubsOnly) {
+            self.subs_only = v;
+        }
+    }
+}
+
+/// What the [Client] knows about a joined channel
+#[derive(Debug, Clone, Default)]
+pub struct ChannelState {
+    /// Settings of the chat room
+    pub room: RoomSettings,
+    /// The client's state in the channel, from the last `USERSTATE` received,
+    /// `None` until one is received, which anonymous clients never do
+    pub user: Option<UserState>,
+}
+
+#[derive(Debug, Default)]
+struct SharedState {
+    channels: HashMap<ChannelLogin, ChannelState>,
+    capabilities: Vec<String>,
+}
+
+/// Received messages waiting to be read from a [Client], dropping the oldest
+/// ones once full
+#[derive(Debug)]
+struct EventQueue {
+    events: VecDeque<AnySemantic>,
+    capacity: usize,
+    /// the client task stopped, no more events will be pushed
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+impl EventQueue {
+    fn new(capacity: usize) -> Self {
+        Self {
+            events: VecDeque::new(),
+            capacity,
+            closed: false,
+            waker: None,
+        }
+    }
+
+    fn poll_next(&mut self, cx: &mut std::task::Context<'_>) -> Poll<Option<AnySemantic>> {
+        match self.events.pop_front() {
+            Some(event) => Poll::Ready(Some(event)),
+            None if self.closed => Poll::Ready(None),
+            None => {
+                self.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// The client task's end of the [EventQueue], closing it when dropped
+struct EventSender(Arc<Mutex<EventQueue>>);
+
+impl EventSender {
+    fn send(&self, event: AnySemantic) {
+        let mut queue = self.0.lock().unwrap();
+        if queue.events.len() >= queue.capacity {
+            queue.events.pop_front();
+            counter!(metrics::EVENTS_DROPPED).increment(1);
+        }
+        queue.events.push_back(event);
+        if let Some(waker) = queue.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Drop for EventSender {
+    fn drop(&mut self) {
+        let mut queue = self.0.lock().unwrap();
+        queue.closed = true;
+        if let Some(waker) = queue.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+enum ClientCommand {
+    Say {
+        channel: ChannelLogin,
+        text: String,
+        reply_to: Option<String>,
+    },
+    Join(ChannelLogin),
+    Part(ChannelLogin),
+    Shutdown,
+}
+
+/// Builder for a [Client]
+pub struct ClientBuilder<A: AuthProvider + Clone> {
+    auth: A,
+    profile: ServerProfile,
+    channels: Vec<ChannelLogin>,
+    rate_limiter: RateLimiter,
+    recorder: Option<Recorder>,
+    writers: usize,
+    proxy: Option<Proxy>,
+    event_capacity: usize,
+    runtime: Arc<dyn Runtime>,
+    #[cfg(feature = "backfill")]
+    backfill: Option<crate::connection::Backfill>,
+}
+
+impl<A: AuthProvider + Clone + Send + Sync + 'static> ClientBuilder<A> {
+    /// Creates a [ClientBuilder] that connects to Twitch with `auth`
+    pub fn new(auth: A) -> Self {
+        Self {
+            auth,
+            profile: ServerProfile::Twitch,
+            channels: vec![],
+            rate_limiter: RateLimiter::twitch_user(),
+            recorder: None,
+            writers: 0,
+            proxy: None,
+            event_capacity: DEFAULT_EVENT_CAPACITY,
+            runtime: runtime::default_runtime(),
+            #[cfg(feature = "backfill")]
+            backfill: None,
+        }
+    }
+
+    /// Sets the server to connect to, Twitch by default
+    pub fn profile(mut self, profile: impl Into<ServerProfile>) -> Self {
+        self.profile = profile.into();
+        self
+    }
+
+    /// Adds channels to join once connected
+    pub fn channels(mut self, channels: impl IntoIterator<Item = impl Into<ChannelLogin>>) -> Self {
+        self.channels.extend(channels.into_iter().map(Into::into));
+        self
+    }
+
+    /// Sets how fast messages are sent, [RateLimiter::twitch_user] by default
+    pub fn rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.rate_limiter = limiter;
+        self
+    }
+
+    /// Records every raw IRC line received and sent
+    pub fn recorder(mut self, recorder: Recorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Reads chat through anonymous connections and sends messages through
+    /// `writers` authenticated ones, see [ConnectionPool::split]
+    pub fn split(mut self, writers: usize) -> Self {
+        self.writers = writers;
+        self
+    }
+
+    /// Tunnels connections through `proxy`
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Sets how many received messages are kept until they are read,
+    /// [DEFAULT_EVENT_CAPACITY] by default. Once full, the oldest ones are
+    /// dropped
+    pub fn event_capacity(mut self, capacity: usize) -> Self {
+        self.event_capacity = capacity.max(1);
+        self
+    }
+
+    /// Sets the runtime the client and its connections run on,
+    /// [default_runtime](runtime::default_runtime) by default
+    pub fn runtime(mut self, runtime: Arc<dyn Runtime>) -> Self {
+        self.runtime = runtime;
+        self
+    }
+
+    /// Backfills the recent messages of channels when they are joined
+    #[cfg(feature = "backfill")]
+    pub fn backfill(mut self, backfill: crate::connection::Backfill) -> Self {
+        self.backfill = Some(backfill);
+        self
+    }
+
+    /// Connects, joins the channels and starts handling messages in the
+    /// background
+    pub async fn connect(self) -> Result<Client, PoolError> {
+        let mut pool = ConnectionPool::with_profile(
+            core::iter::empty::<ChannelLogin>(),
+            self.auth,
+            self.profile,
+        )
+        .await?;
+        let lifecycle = pool.subscribe();
+        pool.set_runtime(Arc::clone(&self.runtime));
+        pool.set_proxy(self.proxy);
+        pool.start_writers(self.writers).await?;
+        if let Some(recorder) = self.recorder {
+            pool.record_to(recorder);
+        }
+        #[cfg(feature = "backfill")]
+        if let Some(backfill) = self.backfill {
+            pool.enable_backfill(backfill).await;
+        }
+
+        let state = Arc::new(RwLock::new(SharedState::default()));
+        for channel in self.channels {
+            pool.join_channel(channel.clone()).await?;
+            state
+                .write()
+                .unwrap()
+                .channels
+                .insert(channel, ChannelState::default());
+        }
+
+        let (commands, commands_rx) = mpsc::unbounded_channel();
+        let events = Arc::new(Mutex::new(EventQueue::new(self.event_capacity)));
+        let (stopped_tx, stopped) = oneshot::channel();
+        let task = ClientTask {
+            pool,
+            state: Arc::clone(&state),
+            rate_limiter: self.rate_limiter,
+            commands: commands_rx,
+            events: EventSender(Arc::clone(&events)),
+            reconnects: HashMap::new(),
+            runtime: Arc::clone(&self.runtime),
+        };
+        self.runtime.spawn(Box::pin(async move {
+            task.run().await;
+            let _ = stopped_tx.send(());
+        }));
+
+        Ok(Client {
+            commands,
+            events,
+            lifecycle,
+            state,
+            stopped,
+        })
+    }
+}
+
+/// A client that handles the connections to chat in the background.
+///
+/// It answers `PING`s, reconnects connections that are lost or asked to
+/// reconnect, rate limits the messages it sends and keeps track of the state
+/// of joined channels. Every received message is returned by its [Stream]
+/// implementation, the oldest ones are dropped if they aren't read fast enough,
+/// see [ClientBuilder::event_capacity]
+pub struct Client {
+    commands: mpsc::UnboundedSender<ClientCommand>,
+    events: Arc<Mutex<EventQueue>>,
+    lifecycle: broadcast::Receiver<LifecycleEvent>,
+    state: Arc<RwLock<SharedState>>,
+    /// resolves once the client task stops
+    stopped: oneshot::Receiver<()>,
+}
+
+impl Client {
+    /// Creates a [ClientBuilder] that connects with `auth`
+    pub fn builder<A: AuthProvider + Clone + Send + Sync + 'static>(auth: A) -> ClientBuilder<A> {
+        ClientBuilder::new(auth)
+    }
+
+    fn command(&self, command: ClientCommand) -> Result<(), ClientError> {
+        self.commands.send(command).map_err(|_| ClientError::Closed)
+    }
+
+    /// Joins a channel
+    pub fn join(&self, channel: impl Into<ChannelLogin>) -> Result<(), ClientError> {
+        self.command(ClientCommand::Join(channel.into()))
+    }
+
+    /// Parts a channel
+    pub fn part(&self, channel: impl Into<ChannelLogin>) -> Result<(), ClientError> {
+        self.command(ClientCommand::Part(channel.into()))
+    }
+
+    /// Queues a message to be sent to a joined channel once the rate limit
+    /// allows it
+    pub fn say(
+        &self,
+        channel: impl Into<ChannelLogin>,
+        text: impl Into<String>,
+    ) -> Result<(), ClientError> {
+        self.command(ClientCommand::Say {
+            channel: channel.into(),
+            text: text.into(),
+            reply_to: None,
+        })
+    }
+
+    /// Queues a reply to a message, like [say](Self::say)
+    ///
+    /// # Errors
+    /// If the client was shut down, or if the message's channel param is
+    /// missing or malformed
+    pub fn reply<C: Deref<Target = str>>(
+        &self,
+        msg: &PrivMsg<C>,
+        text: impl Into<String>,
+    ) -> Result<(), ClientError> {
+        let channel = msg.channel_login().ok_or(ClientError::InvalidChannel)?;
+        self.command(ClientCommand::Say {
+            channel: channel.into_owned(),
+            text: text.into(),
+            reply_to: msg.reply_to_id().map(ToOwned::to_owned),
+        })
+    }
+
+    /// The channels that are joined
+    pub fn channels(&self) -> Vec<ChannelLogin> {
+        self.state
+            .read()
+            .unwrap()
+            .channels
+            .keys()
+            .cloned()
+            .collect()
+    }
+
+    /// The state of a joined channel
+    pub fn channel_state(&self, channel: &str) -> Option<ChannelState> {
+        self.state.read().unwrap().channels.get(channel).cloned()
+    }
+
+    /// The capabilities the server acknowledged
+    pub fn capabilities(&self) -> Vec<String> {
+        self.state.read().unwrap().capabilities.clone()
+    }
+
+    /// Subscribes to the [LifecycleEvent]s of the client's connections that
+    /// happen from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<LifecycleEvent> {
+        self.lifecycle.resubscribe()
+    }
+
+    /// Sends the queued messages and closes every connection
+    pub async fn shutdown(mut self) {
+        if self.command(ClientCommand::Shutdown).is_ok() {
+            let _ = (&mut self.stopped).await;
+        }
+    }
+}
+
+impl Stream for Client {
+    type Item = AnySemantic;
+
+    fn poll_next(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        self.events.lock().unwrap().poll_next(cx)
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        // shutting down without waiting, queued messages may not be sent
+        let _ = self.commands.send(ClientCommand::Shutdown);
+    }
+}
+
+struct ClientTask<A: AuthProvider + Clone> {
+    pool: ConnectionPool<A>,
+    state: Arc<RwLock<SharedState>>,
+    rate_limiter: RateLimiter,
+    commands: mpsc::UnboundedReceiver<ClientCommand>,
+    events: EventSender,
+    /// when connections that must be restarted are next tried, and how long
+    /// to wait if that fails
+    reconnects: HashMap<usize, (Instant, Duration)>,
+    runtime: Arc<dyn Runtime>,
+}
+
+impl<A: AuthProvider + Clone + Send + Sync + 'static> ClientTask<A> {
+    async fn run(mut self) {
+        let mut queue =
+            std::collections::VecDeque::<(ChannelLogin, MessageBuilder<'static>)>::new();
+        let mut shutting_down = false;
+
+        loop {
+            // send every queued message the rate limit allows
+            let mut wait = None;
+            while !queue.is_empty() {
+                if let Err(w) = self.rate_limiter.try_acquire() {
+                    wait = Some(w);
+                    break;
+                }
+                let (channel, msg) = queue.pop_front().unwrap();
+                match self.pool.get_write_conn_idx(&channel) {
+                    Ok(idx) => {
+                        if let Err(e) = self.pool.send_to_connection(msg, idx).await {
+                            tracing::warn!("failed to send message to {channel}: {e}");
+                        }
+                    }
+                    Err(_) => tracing::warn!("not joined to {channel}, dropping message"),
+                }
+            }
+            gauge!(metrics::SEND_QUEUE_DEPTH, "queue" => "client").set(queue.len() as f64);
+            if shutting_down && queue.is_empty() {
+                break;
+            }
+
+            self.retry_reconnects().await;
+            let next_reconnect = self
+                .reconnects
+                .values()
+                .map(|(at, _)| at.saturating_duration_since(Instant::now()))
+                .min();
+
+            tokio::select! {
+                received = self.pool.next(), if self.pool.connection_count() > 0 => match received {
+                    Some(Ok((msg, idx))) => self.handle_message(msg, idx).await,
+                    Some(Err(PoolError::ReceiveFailed(idx, e))) => {
+                        tracing::warn!("connection {idx} failed: {e}");
+                        self.schedule_reconnect(idx);
+                    }
+                    Some(Err(e)) => tracing::warn!("failed to receive: {e}"),
+                    None => unreachable!("connection pools never end"),
+                },
+                command = self.commands.recv(), if !shutting_down => match command {
+                    Some(ClientCommand::Say { channel, text, reply_to }) => {
+                        let mut msg = MessageBuilder::privmsg(&channel, &text);
+                        if let Some(reply_to) = reply_to {
+                            msg = msg.add_tag(OwnedTag::ReplyParentMsgId, reply_to);
+                        }
+                        queue.push_back((channel, msg.to_owned()));
+                    }
+                    Some(ClientCommand::Join(channel)) => {
+                        match self.pool.join_channel(channel.clone()).await {
+                            Ok(()) => {
+                                self.state
+                                    .write()
+                                    .unwrap()
+                                    .channels
+                                    .entry(channel)
+                                    .or_default();
+                            }
+                            Err(e) => tracing::warn!("failed to join {channel}: {e}"),
+                        }
+                    }
+                    Some(ClientCommand::Part(channel)) => {
+                        self.state.write().unwrap().channels.remove(&channel);
+                        if let Err(e) = self.pool.part_channel(channel.clone()).await {
+                            tracing::warn!("failed to part {channel}: {e}");
+                        }
+                    }
+                    Some(ClientCommand::Shutdown) | None => shutting_down = true,
+                },
+                _ = self.runtime.sleep(wait.unwrap_or_default()), if wait.is_some() => {}
+                _ = self.runtime.sleep(next_reconnect.unwrap_or_default()),
+                    if next_reconnect.is_some() => {}
+            }
+        }
+
+        tracing::debug!("client shut down");
+    }
+
+    async fn handle_message(&mut self, msg: IrcMessage, idx: usize) {
+        let msg = AnySemantic::from(msg);
+        match &msg {
+            AnySemantic::Ping(ping) => {
+                if let Err(e) = self.pool.send_to_connection(ping.respond(), idx).await {
+                    tracing::warn!("failed to answer PING on connection {idx}: {e}");
+                }
+            }
+            AnySemantic::Reconnect(_) => {
+                tracing::info!("connection {idx} was asked to reconnect");
+                self.schedule_reconnect(idx);
+            }
+            AnySemantic::RoomState(room) => {
+                if let Ok(channel) =
+                    ChannelLogin::from_irc_param(room.get_param(0).unwrap_or_default())
+                {
+                    let mut state = self.state.write().unwrap();
+                    if let Some(channel) = state.channels.get_mut(channel.as_str()) {
+                        channel.room.update(&**room);
+                    }
+                }
+            }
+            AnySemantic::UserState(user) => {
+                let mut state = self.state.write().unwrap();
+                if let Some(channel) = user
+                    .channel_login()
+                    .and_then(|c| state.channels.get_mut(c.as_str()))
+                {
+                    channel.user = Some(user.clone());
+                }
+            }
+            AnySemantic::Cap(cap) => {
+                let caps = cap.get_param(2).unwrap_or_default();
+                match cap.get_param(1) {
+                    Some("ACK") => {
+                        let mut state = self.state.write().unwrap();
+                        for cap in caps.split(' ').filter(|c| !c.is_empty()) {
+                            if !state.capabilities.iter().any(|c| c == cap) {
+                                state.capabilities.push(cap.to_owned());
+                            }
+                        }
+                    }
+                    Some("NAK") => tracing::warn!("server rejected capabilities: {caps}"),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+        self.events.send(msg);
+    }
+
+    /// Restarts a connection as soon as possible, unless it already will be
+    fn schedule_reconnect(&mut self, idx: usize) {
+        self.reconnects
+            .entry(idx)
+            .or_insert((Instant::now(), MIN_RECONNECT_DELAY));
+    }
+
+    /// Tries to restart the connections that are due, waiting longer before
+    /// the next attempt each time one fails. Other connections keep being
+    /// handled in the meantime
+    async fn retry_reconnects(&mut self) {
+        let now = Instant::now();
+        let due: Vec<usize> = self
+            .reconnects
+            .iter()
+            .filter(|(_, (at, _))| *at <= now)
+            .map(|(idx, _)| *idx)
+            .collect();
+        for idx in due {
+            match self.pool.restart_connection(idx).await {
+                Ok(()) => {
+                    self.reconnects.remove(&idx);
+                }
+                Err(e) => {
+                    let (at, delay) = self.reconnects.get_mut(&idx).unwrap();
+                    tracing::warn!(
+                        "failed to reconnect connection {idx}, retrying in {delay:?}: {e}"
+                    );
+                    *at = Instant::now() + *delay;
+                    *delay = (*delay * 2).min(MAX_RECONNECT_DELAY);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use std::sync::{Arc, Mutex};
+
+    use futures_util::{StreamExt, future::poll_fn};
+
+    use super::{Client, EventQueue, EventSender};
+    use crate::{
+        ChannelLogin, IrcCommand, IrcMessage, auth::OAuth, connection::RateLimiter,
+        irc_message::AnySemantic, testing::MockServer,
+    };
+
+    fn auth() -> OAuth {
+        OAuth {
+            nick: "julia".into(),
+            oauth: "token".into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn client_flow() {
+        let server = MockServer::start().await.unwrap();
+        let mut client = Client::builder(auth())
+            .profile(server.profile())
+            .channels([ChannelLogin::parse("foo").unwrap()])
+            .connect()
+            .await
+            .unwrap();
+
+        // the room and user state of joined channels is tracked
+        loop {
+            let msg = client.next().await.unwrap();
+            if msg.get_command() == IrcCommand::RoomState {
+                break;
+            }
+        }
+        let state = client.channel_state("foo").unwrap();
+        assert_eq!(state.room.room_id.unwrap(), "1");
+        assert_eq!(state.room.followers_only, None);
+        assert!(state.user.is_some());
+        assert_eq!(client.channels(), ["foo"]);
+        assert!(client.capabilities().contains(&"twitch.tv/tags".to_owned()));
+
+        server.send_privmsg("foo", "bar", "hi julia");
+        let msg = loop {
+            if let AnySemantic::PrivMsg(msg) = client.next().await.unwrap() {
+                break msg;
+            }
+        };
+        client.reply(&msg, "hi bar").unwrap();
+        let sent = server
+            .wait_for(|m| m.message.get_command() == IrcCommand::PrivMsg)
+            .await;
+        assert_eq!(sent.message.get_param(1), Some("hi bar"));
+        assert!(
+            sent.message
+                .get_tag_raw_by_str("reply-parent-msg-id")
+                .is_some()
+        );
+
+        // PINGs are answered and RECONNECTs followed
+        server.send_ping();
+        server.wait_for_command(IrcCommand::Pong).await;
+        server.send_reconnect();
+        server
+            .wait_for(|m| m.client == 1 && m.message.get_command() == IrcCommand::Join)
+            .await;
+
+        client.part(ChannelLogin::parse("foo").unwrap()).unwrap();
+        server.wait_for_command(IrcCommand::Part).await;
+        assert!(client.channel_state("foo").is_none());
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn rate_limited_sends() {
+        let server = MockServer::start().await.unwrap();
+        let client = Client::builder(auth())
+            .profile(server.profile())
+            .channels([ChannelLogin::parse("foo").unwrap()])
+            .rate_limiter(RateLimiter::new(1, Duration::from_millis(300)))
+            .connect()
+            .await
+            .unwrap();
+
+        client
+            .say("foo".parse::<ChannelLogin>().unwrap(), "one")
+            .unwrap();
+        client
+            .say("foo".parse::<ChannelLogin>().unwrap(), "two")
+            .unwrap();
+        let two = server
+            .wait_for(|m| m.message.get_param(1) == Some("two"))
+            .await;
+        let one = server
+            .wait_for(|m| m.message.get_param(1) == Some("one"))
+            .await;
+        assert!(two.received_at - one.received_at >= Duration::from_millis(250));
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn oldest_events_dropped() {
+        let queue = Arc::new(Mutex::new(EventQueue::new(2)));
+        let sender = EventSender(Arc::clone(&queue));
+        for i in 0..3 {
+            let msg = IrcMessage::new(format!("PING :{i}\r\n")).unwrap();
+            sender.send(AnySemantic::from(msg));
+        }
+        drop(sender);
+
+        let mut received = vec![];
+        while let Some(event) = poll_fn(|cx| queue.lock().unwrap().poll_next(cx)).await {
+            received.push(event.get_param(0).unwrap().to_owned());
+        }
+        assert_eq!(received, ["1", "2"]);
+    }
+
+    #[tokio::test]
+    async fn reconnects_lost_connections() {
+        let server = MockServer::start().await.unwrap();
+        let client = Client::builder(auth())
+            .profile(server.profile())
+            .channels([ChannelLogin::parse("foo").unwrap()])
+            .connect()
+            .await
+            .unwrap();
+        server.wait_for_command(IrcCommand::Join).await;
+
+        server.disconnect(0);
+        server
+            .wait_for(|m| m.client == 1 && m.message.get_command() == IrcCommand::Join)
+            .await;
+        client.shutdown().await;
+    }
+}
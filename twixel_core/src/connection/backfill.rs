@@ -0,0 +1,478 @@
+use std::{
+    ops::Deref,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use hashbrown::{HashMap, HashSet};
+use thiserror::Error;
+use tokio::sync::mpsc;
+
+use super::Dedup;
+use crate::{ChannelLogin, IrcMessage, runtime::Runtime};
+
+/// Base URL of the public recent-messages service
+pub const RECENT_MESSAGES_URL: &str = "https://recent-messages.robotty.de";
+
+/// How long fetching the recent messages of a channel may take by default
+pub const DEFAULT_BACKFILL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How many messages are remembered to drop the ones that are both
+/// backfilled and received live
+const SEEN_IDS_SIZE: usize = 4096;
+
+/// Error returned when fetching recent messages fails
+#[derive(Debug, Error)]
+pub enum BackfillError {
+    /// The request failed or the response wasn't valid
+    #[error("failed to fetch recent messages: {0}")]
+    Http(#[from] reqwest::Error),
+    /// The service returned an error instead of messages
+    #[error("recent-messages service returned an error: {0}")]
+    Service(String),
+}
+
+/// Fetches messages sent to channels before they were joined from a
+/// recent-messages style HTTP service, such as
+/// <https://recent-messages.robotty.de>.
+///
+/// Backfilled messages are tagged as [historical](IrcMessage::is_historical)
+/// and returned by a [Connection](super::Connection) before live messages.
+/// Messages received both live and from the service are only returned once.
+///
+/// Channels are backfilled in the background the first time they are joined,
+/// their live messages are held back until then
+#[derive(Debug, Clone)]
+pub struct Backfill {
+    base_url: String,
+    limit: Option<usize>,
+    timeout: Duration,
+    client: reqwest::Client,
+}
+
+impl Default for Backfill {
+    fn default() -> Self {
+        Self::new(RECENT_MESSAGES_URL)
+    }
+}
+
+impl Backfill {
+    /// Creates a [Backfill] that fetches messages from the service at
+    /// `base_url`, which serves `/api/v2/recent-messages/<channel>`
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_owned(),
+            limit: None,
+            timeout: DEFAULT_BACKFILL_TIMEOUT,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Sets the maximum amount of messages fetched per channel, the service
+    /// decides how many are returned by default
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Sets how long fetching the recent messages of a channel may take before
+    /// it is given up on, [DEFAULT_BACKFILL_TIMEOUT] by default
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the HTTP client used to make requests
+    pub fn client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// The base URL of the service
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Fetches the recent messages of `channel`, oldest first
+    pub async fn fetch(&self, channel: &ChannelLogin) -> Result<Vec<IrcMessage>, BackfillError> {
+        let mut url = format!("{}/api/v2/recent-messages/{channel}", self.base_url);
+        if let Some(limit) = self.limit {
+            url += &format!("?limit={limit}");
+        }
+        let body = self
+            .client
+            .get(url)
+            .timeout(self.timeout)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        let response: serde_json::Value = serde_json::from_slice(&body)
+            .map_err(|e| BackfillError::Service(format!("invalid response: {e}")))?;
+
+        let raw: Vec<&str> = response["messages"]
+            .as_array()
+            .map(|m| m.iter().filter_map(|m| m.as_str()).collect())
+            .unwrap_or_default();
+        if raw.is_empty()
+            && let Some(error) = response["error"].as_str()
+        {
+            return Err(BackfillError::Service(error.to_owned()));
+        }
+
+        let mut buffer = raw.join("\r\n");
+        buffer.push_str("\r\n");
+        Ok(IrcMessage::<String>::new_multiline(&buffer)
+            .filter_map(|msg| match msg {
+                Ok(msg) if msg.is_historical() => Some(msg),
+                Ok(msg) => IrcMessage::new(mark_historical(msg.inner())).ok(),
+                Err(e) => {
+                    tracing::warn!("skipping invalid recent message: {e}");
+                    None
+                }
+            })
+            .collect())
+    }
+}
+
+/// Adds the `historical` tag to a raw IRC message
+fn mark_historical(raw: &str) -> String {
+    match raw.strip_prefix('@') {
+        Some(rest) => format!("@historical=1;{rest}"),
+        None => format!("@historical=1 {raw}"),
+    }
+}
+
+/// The recent messages fetched for a channel
+pub(crate) type Fetched = (ChannelLogin, Result<Vec<IrcMessage>, BackfillError>);
+
+/// Backfills the channels of a [Connection](super::Connection) in the
+/// background, holding back their live messages until it's done
+#[derive(Debug)]
+pub(crate) struct Backfilling<C: Deref<Target = str>> {
+    backfill: Backfill,
+    /// the connection's runtime, which fetches run on
+    pub(crate) runtime: Arc<dyn Runtime>,
+    /// channels that were or are being backfilled, which aren't backfilled again
+    requested: HashSet<ChannelLogin>,
+    /// live messages of the channels still being backfilled
+    held: HashMap<ChannelLogin, Vec<IrcMessage<C>>>,
+    /// recently returned messages, so ones that are both backfilled and
+    /// received live are only returned once
+    seen: Dedup,
+    fetched_tx: mpsc::UnboundedSender<Fetched>,
+    fetched_rx: mpsc::UnboundedReceiver<Fetched>,
+}
+
+impl<C: Deref<Target = str>> Backfilling<C> {
+    pub(crate) fn new(backfill: Backfill, runtime: Arc<dyn Runtime>) -> Self {
+        let (fetched_tx, fetched_rx) = mpsc::unbounded_channel();
+        Self {
+            backfill,
+            runtime,
+            requested: HashSet::new(),
+            held: HashMap::new(),
+            seen: Dedup::new().capacity(SEEN_IDS_SIZE).ttl(Duration::MAX),
+            fetched_tx,
+            fetched_rx,
+        }
+    }
+
+    /// Starts fetching the recent messages of the channels that weren't
+    /// backfilled before
+    pub(crate) fn request(&mut self, channels: impl IntoIterator<Item = ChannelLogin>) {
+        for channel in channels {
+            if !self.requested.insert(channel.clone()) {
+                continue;
+            }
+            self.held.insert(channel.clone(), Vec::new());
+            let backfill = self.backfill.clone();
+            let fetched_tx = self.fetched_tx.clone();
+            self.runtime.spawn(Box::pin(async move {
+                let fetched = backfill.fetch(&channel).await;
+                // the connection may be gone by now
+                let _ = fetched_tx.send((channel, fetched));
+            }));
+        }
+    }
+
+    /// Whether any channel is still being backfilled
+    pub(crate) fn is_pending(&self) -> bool {
+        !self.held.is_empty()
+    }
+
+    /// Polls for the recent messages of a channel being backfilled
+    pub(crate) fn poll_fetched(&mut self, cx: &mut Context<'_>) -> Poll<Option<Fetched>> {
+        self.fetched_rx.poll_recv(cx)
+    }
+
+    /// Takes a live message, returning it unless it was already returned or
+    /// its channel is still being backfilled
+    pub(crate) fn live(&mut self, msg: IrcMessage<C>) -> Option<IrcMessage<C>> {
+        let held = msg
+            .get_param(0)
+            .and_then(|p| ChannelLogin::from_irc_param(p).ok())
+            .and_then(|channel| self.held.get_mut(&*channel));
+        match held {
+            Some(held) => {
+                held.push(msg);
+                None
+            }
+            None => self.seen.check(&msg).then_some(msg),
+        }
+    }
+
+    /// Finishes backfilling a channel, returning its recent messages and then
+    /// its held live messages, without duplicates
+    pub(crate) fn finish(
+        &mut self,
+        (channel, fetched): Fetched,
+    ) -> (Vec<IrcMessage>, Vec<IrcMessage<C>>) {
+        let held = self.held.remove(&channel).unwrap_or_default();
+        let history = match fetched {
+            Ok(messages) => {
+                tracing::debug!("backfilled {} messages in {channel}", messages.len());
+                messages
+            }
+            Err(e) => {
+                tracing::warn!("failed to backfill {channel}: {e}");
+                Vec::new()
+            }
+        };
+        let history = history
+            .into_iter()
+            .filter(|msg| self.seen.check(msg))
+            .collect();
+        let held = held
+            .into_iter()
+            .filter(|msg| self.seen.check(msg))
+            .collect();
+        (history, held)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+        time::Duration,
+    };
+
+    use async_tungstenite::tungstenite::Error as WsError;
+    use futures_util::{StreamExt, future::BoxFuture};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        sync::mpsc,
+    };
+
+    use super::Backfill;
+    use crate::{
+        ChannelLogin, Connection, IrcCommand,
+        auth::Anonymous,
+        runtime::{Connector, Spawner, Timer, Tokio, WebSocket},
+        testing::MockServer,
+    };
+
+    /// Serves `body` as JSON to every request, or never answers if it's
+    /// `None`. Returns the base URL and the requests it received
+    async fn http_server(body: Option<String>) -> (String, mpsc::UnboundedReceiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (requests_tx, requests) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let mut hanging = vec![];
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = vec![0; 4096];
+                let read = stream.read(&mut request).await.unwrap();
+                let _ = requests_tx.send(String::from_utf8_lossy(&request[..read]).into_owned());
+                let Some(body) = &body else {
+                    // keep the request hanging
+                    hanging.push(stream);
+                    continue;
+                };
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\n\
+                     content-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        (url, requests)
+    }
+
+    async fn receive_privmsgs(
+        conn: &mut Connection<Anonymous>,
+        count: usize,
+    ) -> Vec<(String, bool)> {
+        let mut privmsgs = vec![];
+        while privmsgs.len() < count {
+            let msg = conn.receive().await.unwrap();
+            if msg.get_command() == IrcCommand::PrivMsg {
+                privmsgs.push((msg.get_param(1).unwrap().to_owned(), msg.is_historical()));
+            }
+        }
+        privmsgs
+    }
+
+    #[tokio::test]
+    async fn backfill_before_live() {
+        let history = serde_json::json!({
+            "messages": [
+                "@historical=1;id=a;rm-received-ts=1 :bar!bar@bar.tmi.twitch.tv PRIVMSG #foo :old",
+                "@id=b :bar!bar@bar.tmi.twitch.tv PRIVMSG #foo :older",
+            ],
+            "error": null,
+        });
+        let (url, mut requests) = http_server(Some(history.to_string())).await;
+        let server = MockServer::start().await.unwrap();
+
+        let mut conn: Connection<_> = Connection::with_profile(
+            [ChannelLogin::parse("foo").unwrap()],
+            Anonymous,
+            server.profile(),
+        );
+        conn.enable_backfill(Backfill::new(url).limit(10)).await;
+        conn.start().await.unwrap();
+        server.wait_for_command(IrcCommand::Join).await;
+
+        // the message with id b was also received live
+        server.send_raw("@id=b :bar!bar@bar.tmi.twitch.tv PRIVMSG #foo :older");
+        server.send_raw("@id=c :bar!bar@bar.tmi.twitch.tv PRIVMSG #foo :new");
+
+        assert_eq!(
+            receive_privmsgs(&mut conn, 3).await,
+            [
+                ("old".to_owned(), true),
+                ("older".to_owned(), true),
+                ("new".to_owned(), false)
+            ]
+        );
+        let request = requests.recv().await.unwrap();
+        assert!(
+            request.starts_with("GET /api/v2/recent-messages/foo?limit=10 "),
+            "{request}"
+        );
+
+        // reconnecting doesn't backfill again
+        conn.restart().await.unwrap();
+        assert!(!conn.backfill.as_ref().unwrap().is_pending());
+        server.send_raw("@id=d :bar!bar@bar.tmi.twitch.tv PRIVMSG #foo :after");
+        assert_eq!(
+            receive_privmsgs(&mut conn, 1).await,
+            [("after".to_owned(), false)]
+        );
+        assert!(requests.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn backfill_as_stream() {
+        let history = serde_json::json!({
+            "messages": [":bar!bar@bar.tmi.twitch.tv PRIVMSG #foo :old"],
+            "error": null,
+        });
+        let (url, _requests) = http_server(Some(history.to_string())).await;
+        let server = MockServer::start().await.unwrap();
+
+        let mut conn: Connection<_> = Connection::with_profile(
+            [ChannelLogin::parse("foo").unwrap()],
+            Anonymous,
+            server.profile(),
+        );
+        conn.enable_backfill(Backfill::new(url)).await;
+        conn.start().await.unwrap();
+        server.wait_for_command(IrcCommand::Join).await;
+        server.send_raw("@id=c :bar!bar@bar.tmi.twitch.tv PRIVMSG #foo :new");
+
+        let privmsgs = conn
+            .filter_map(|msg| async move {
+                let msg = msg.unwrap();
+                (msg.get_command() == IrcCommand::PrivMsg)
+                    .then(|| (msg.get_param(1).unwrap().to_owned(), msg.is_historical()))
+            })
+            .take(2)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tokio::time::timeout(Duration::from_secs(5), privmsgs)
+                .await
+                .unwrap(),
+            [("old".to_owned(), true), ("new".to_owned(), false)]
+        );
+    }
+
+    /// Tokio, counting the tasks spawned on it
+    #[derive(Debug, Default)]
+    struct CountingRuntime(AtomicUsize);
+
+    impl Timer for CountingRuntime {
+        fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+            Tokio.sleep(duration)
+        }
+    }
+
+    impl Spawner for CountingRuntime {
+        fn spawn(&self, task: BoxFuture<'static, ()>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Tokio.spawn(task);
+        }
+    }
+
+    impl Connector for CountingRuntime {
+        fn connect<'a>(
+            &'a self,
+            url: &'a str,
+        ) -> BoxFuture<'a, Result<Box<dyn WebSocket>, WsError>> {
+            Tokio.connect(url)
+        }
+    }
+
+    #[tokio::test]
+    async fn backfill_on_runtime() {
+        let (url, mut requests) = http_server(None).await;
+        let server = MockServer::start().await.unwrap();
+        let runtime = Arc::new(CountingRuntime::default());
+
+        let mut conn: Connection<_> = Connection::with_profile(
+            [ChannelLogin::parse("foo").unwrap()],
+            Anonymous,
+            server.profile(),
+        );
+        conn.enable_backfill(Backfill::new(url)).await;
+        conn.set_runtime(runtime.clone());
+        conn.start().await.unwrap();
+        requests.recv().await.unwrap();
+
+        assert_eq!(runtime.0.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn backfill_timeout() {
+        let (url, mut requests) = http_server(None).await;
+        let server = MockServer::start().await.unwrap();
+
+        let mut conn: Connection<_> = Connection::with_profile(
+            [ChannelLogin::parse("foo").unwrap()],
+            Anonymous,
+            server.profile(),
+        );
+        conn.enable_backfill(Backfill::new(url).timeout(Duration::from_millis(100)))
+            .await;
+        conn.start().await.unwrap();
+        server.wait_for_command(IrcCommand::Join).await;
+        requests.recv().await.unwrap();
+
+        // live messages are held until the backfill gives up
+        server.send_raw("@id=a :bar!bar@bar.tmi.twitch.tv PRIVMSG #foo :live");
+        assert_eq!(
+            receive_privmsgs(&mut conn, 1).await,
+            [("live".to_owned(), false)]
+        );
+        assert!(!conn.backfill.as_ref().unwrap().is_pending());
+    }
+}
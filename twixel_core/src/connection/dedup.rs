@@ -0,0 +1,219 @@
+use std::{
+    collections::VecDeque,
+    hash::{BuildHasher, Hash, Hasher},
+    time::{Duration, Instant},
+};
+
+use hashbrown::{DefaultHashBuilder, HashSet};
+
+use crate::{IrcCommand, IrcMessage, irc_message::tags::OwnedTag};
+
+/// How many messages are remembered by default
+pub const DEFAULT_CAPACITY: usize = 4096;
+
+/// How long messages are remembered by default
+pub const DEFAULT_TTL: Duration = Duration::from_secs(10);
+
+/// Drops messages a [ConnectionPool](super::ConnectionPool) already returned
+/// recently, which happens when a channel is visible on several of its
+/// connections at once, like while one is reconnecting.
+///
+/// Messages are recognized by their `id` tag. Every channel in a Shared Chat
+/// session receives its own copy of a message, with a distinct `id` and the
+/// original one in `source-id`, so copies are only dropped with
+/// [once_per_source](Self::once_per_source). `PRIVMSG`, `USERNOTICE`,
+/// `CLEARCHAT` and `CLEARMSG`s without one are recognized by a hash of the
+/// whole message if they are timestamped, with `tmi-sent-ts` or the IRCv3
+/// `time` tag, so a line that is legitimately repeated isn't dropped. Other
+/// messages without an ID, like `PING`s, are never dropped since every
+/// connection needs to handle its own.
+///
+/// At most [capacity](Self::capacity) messages are remembered, each for
+/// [ttl](Self::ttl) after it was first returned
+#[derive(Debug, Clone)]
+pub struct Dedup {
+    capacity: usize,
+    ttl: Duration,
+    hasher: DefaultHashBuilder,
+    seen: HashSet<u64>,
+    // keys in the order they were first seen
+    order: VecDeque<(u64, Instant)>,
+    dropped: u64,
+    once_per_source: bool,
+}
+
+impl Default for Dedup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Dedup {
+    /// Creates a [Dedup] that remembers [DEFAULT_CAPACITY] messages for
+    /// [DEFAULT_TTL]
+    pub fn new() -> Self {
+        Self {
+            capacity: DEFAULT_CAPACITY,
+            ttl: DEFAULT_TTL,
+            hasher: DefaultHashBuilder::default(),
+            seen: HashSet::new(),
+            order: VecDeque::new(),
+            dropped: 0,
+            once_per_source: false,
+        }
+    }
+
+    /// Sets the maximum amount of messages remembered, the oldest ones are
+    /// forgotten first
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Sets how long messages are remembered after they were first returned
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Recognizes messages by their `source-id` tag when they have one, so
+    /// only the first copy of a Shared Chat message is returned, in whichever
+    /// channel it is received first
+    pub fn once_per_source(mut self, enabled: bool) -> Self {
+        self.once_per_source = enabled;
+        self
+    }
+
+    /// How many messages were dropped as duplicates
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Remembers the message, returns whether it wasn't seen recently.
+    /// Duplicates are counted as dropped
+    pub fn check<C: std::ops::Deref<Target = str>>(&mut self, msg: &IrcMessage<C>) -> bool {
+        self.check_at(msg, Instant::now())
+    }
+
+    fn check_at<C: std::ops::Deref<Target = str>>(
+        &mut self,
+        msg: &IrcMessage<C>,
+        now: Instant,
+    ) -> bool {
+        let Some(key) = self.key(msg) else {
+            return true;
+        };
+
+        while let Some(&(oldest, seen_at)) = self.order.front()
+            && now.duration_since(seen_at) >= self.ttl
+        {
+            self.seen.remove(&oldest);
+            self.order.pop_front();
+        }
+
+        if self.seen.contains(&key) {
+            self.dropped += 1;
+            return false;
+        }
+        if self.capacity == 0 {
+            return true;
+        }
+        if self.order.len() >= self.capacity
+            && let Some((oldest, _)) = self.order.pop_front()
+        {
+            self.seen.remove(&oldest);
+        }
+        self.seen.insert(key);
+        self.order.push_back((key, now));
+        true
+    }
+
+    fn key<C: std::ops::Deref<Target = str>>(&self, msg: &IrcMessage<C>) -> Option<u64> {
+        let mut hasher = self.hasher.build_hasher();
+        let source_id = msg
+            .get_tag_raw(OwnedTag::SourceId)
+            .filter(|id| self.once_per_source && !id.is_empty());
+        // the original message's `id` is its copies' `source-id`
+        if let Some(id) =
+            source_id.or_else(|| msg.get_tag_raw(OwnedTag::Id).filter(|id| !id.is_empty()))
+        {
+            ("id", id).hash(&mut hasher);
+        } else if matches!(
+            msg.get_command(),
+            IrcCommand::PrivMsg
+                | IrcCommand::UserNotice
+                | IrcCommand::ClearChat
+                | IrcCommand::ClearMsg
+        ) && msg.get_timestamp_millis().is_some()
+        {
+            ("content", msg.inner()).hash(&mut hasher);
+        } else {
+            return None;
+        }
+        Some(hasher.finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::Dedup;
+    use crate::IrcMessage;
+
+    #[test]
+    fn drops_recent_duplicates() {
+        let msg = |raw: &str| IrcMessage::<String>::new(raw.to_owned()).unwrap();
+        let with_id = |id: &str, text: &str| {
+            msg(&format!(
+                "@id={id} :foo!foo@foo.tmi.twitch.tv PRIVMSG #bar :{text}"
+            ))
+        };
+        let mut dedup = Dedup::new().capacity(2).ttl(Duration::from_secs(5));
+        let start = Instant::now();
+
+        assert!(dedup.check_at(&with_id("a", "hi"), start));
+        // the same ID with different content, like in another channel
+        assert!(!dedup.check_at(&with_id("a", "hello"), start));
+        // no ID, so the content is compared, including the timestamp
+        let anonymous = msg("@tmi-sent-ts=1 :foo!foo@foo.tmi.twitch.tv PRIVMSG #bar :hi");
+        assert!(dedup.check_at(&anonymous, start));
+        assert!(!dedup.check_at(&anonymous, start));
+        // without a timestamp a repeated line can't be told apart from a copy
+        let untimed = msg(":foo!foo@foo.tmi.twitch.tv PRIVMSG #bar :hi");
+        assert!(dedup.check_at(&untimed, start));
+        assert!(dedup.check_at(&untimed, start));
+        // pings are never dropped
+        let ping = msg("PING :tmi.twitch.tv");
+        assert!(dedup.check_at(&ping, start));
+        assert!(dedup.check_at(&ping, start));
+        assert_eq!(dedup.dropped(), 2);
+
+        // the oldest message is forgotten when full
+        assert!(dedup.check_at(&with_id("b", "hey"), start));
+        assert!(dedup.check_at(&with_id("a", "hi"), start));
+
+        // and every message once its TTL is over
+        let later = start + Duration::from_secs(5);
+        assert!(dedup.check_at(&with_id("b", "hey"), later));
+        assert!(!dedup.check_at(&with_id("b", "hey"), later));
+        assert_eq!(dedup.dropped(), 3);
+    }
+
+    #[test]
+    fn shared_chat_copies() {
+        let msg = |raw: &str| IrcMessage::<String>::new(raw.to_owned()).unwrap();
+        let origin = msg("@id=abc;source-id=abc :a!a@a.tmi.twitch.tv PRIVMSG #one :hi");
+        let copy = msg("@id=def;source-id=abc :a!a@a.tmi.twitch.tv PRIVMSG #two :hi");
+
+        // copies have their own ID
+        let mut dedup = Dedup::new();
+        assert!(dedup.check(&origin));
+        assert!(dedup.check(&copy));
+
+        let mut dedup = Dedup::new().once_per_source(true);
+        assert!(dedup.check(&copy));
+        assert!(!dedup.check(&origin));
+        assert!(!dedup.check(&copy));
+    }
+}
@@ -0,0 +1,93 @@
+use std::time::Duration;
+
+use crate::ChannelLogin;
+
+/// How many events are kept for subscribers that fall behind
+pub(crate) const EVENT_CHANNEL_SIZE: usize = 256;
+
+/// An event in the lifecycle of a [Connection](super::Connection)
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConnectionEvent {
+    /// The connection is opening its websocket
+    Connecting,
+    /// The server accepted the connection's registration
+    Authenticated,
+    /// The server confirmed a channel was joined
+    Joined(ChannelLogin),
+    /// The server confirmed a channel was parted
+    Parted(ChannelLogin),
+    /// The websocket was closed, for the given reason
+    Disconnected(String),
+    /// The connection is restarting, with the number of attempts since it
+    /// last authenticated, starting at 1
+    Reconnecting(u32),
+    /// Round trip time between a `PING`, sent periodically or with
+    /// [Connection::ping](super::Connection::ping), and its `PONG`
+    LatencyMeasured(Duration),
+}
+
+/// A [ConnectionEvent] along with the connection it happened to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LifecycleEvent {
+    /// Index of the connection in its [ConnectionPool](super::ConnectionPool),
+    /// always 0 for connections outside of pools
+    pub connection: usize,
+    /// What happened
+    pub event: ConnectionEvent,
+}
+
+#[cfg(all(test, feature = "tokio-runtime"))]
+mod tests {
+    use super::{ConnectionEvent, LifecycleEvent};
+    use crate::{ChannelLogin, Connection, IrcCommand, auth::Anonymous, testing::MockServer};
+
+    #[tokio::test]
+    async fn lifecycle_events() {
+        let server = MockServer::start().await.unwrap();
+        let foo = ChannelLogin::parse("foo").unwrap();
+        let mut conn: Connection<_> =
+            Connection::with_profile([foo.clone()], Anonymous, server.profile());
+        let mut events = conn.subscribe();
+
+        // events are emitted while receiving messages, or when receiving fails
+        let mut next = async |conn: &mut Connection<_>| loop {
+            match events.try_recv() {
+                Ok(LifecycleEvent { event, .. }) => return event,
+                Err(_) => {
+                    let _ = conn.receive().await;
+                }
+            }
+        };
+
+        conn.start().await.unwrap();
+        assert_eq!(next(&mut conn).await, ConnectionEvent::Connecting);
+        assert_eq!(next(&mut conn).await, ConnectionEvent::Authenticated);
+        assert_eq!(next(&mut conn).await, ConnectionEvent::Joined(foo.clone()));
+
+        conn.ping().await.unwrap();
+        assert!(matches!(
+            next(&mut conn).await,
+            ConnectionEvent::LatencyMeasured(_)
+        ));
+
+        conn.part(foo.clone()).await.unwrap();
+        assert_eq!(next(&mut conn).await, ConnectionEvent::Parted(foo.clone()));
+
+        server.wait_for_command(IrcCommand::Part).await;
+        conn.restart().await.unwrap();
+        assert_eq!(
+            next(&mut conn).await,
+            ConnectionEvent::Disconnected("restarting".into())
+        );
+        assert_eq!(next(&mut conn).await, ConnectionEvent::Reconnecting(1));
+        assert_eq!(next(&mut conn).await, ConnectionEvent::Connecting);
+        assert_eq!(next(&mut conn).await, ConnectionEvent::Authenticated);
+
+        server.disconnect(1);
+        assert!(matches!(
+            next(&mut conn).await,
+            ConnectionEvent::Disconnected(_)
+        ));
+    }
+}
@@ -1,29 +1,72 @@
-use std::{collections::VecDeque, task::Poll};
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    pin::pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{Duration, Instant, SystemTime},
+};
 
+use ::metrics::{counter, histogram};
+use async_tungstenite::tungstenite::Message as WsMessage;
 use error::ConnectionError;
-use futures_util::{Sink, SinkExt, Stream, StreamExt, stream::FusedStream};
+use futures_util::{
+    Sink, SinkExt, Stream, StreamExt,
+    future::{BoxFuture, Either, select},
+    stream::FusedStream,
+};
 use hashbrown::HashSet;
-use log::{debug, warn};
-use tokio::net::TcpStream;
-use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, tungstenite::Message as WsMessage};
+use tokio::sync::broadcast;
+use tracing::{Span, debug, field, info_span, instrument, trace, warn};
 
+/// Backfilling recent messages from a recent-messages service
+#[cfg(feature = "backfill")]
+pub mod backfill;
+/// Dropping messages a pool receives on several connections
+pub mod dedup;
+/// Events about the lifecycle of connections
+pub mod lifecycle;
+/// Pooling of many [Connection]s
 pub mod pool;
+/// Profiles for Twitch and other IRC servers
+pub mod profile;
+/// Tunneling connections through HTTP and SOCKS5 proxies
+pub mod proxy;
+/// Client side limiting of how fast messages are sent
+pub mod rate_limit;
 
+#[cfg(feature = "backfill")]
+pub use backfill::Backfill;
+pub use dedup::Dedup;
+pub use lifecycle::{ConnectionEvent, LifecycleEvent};
 pub use pool::ConnectionPool;
+pub use profile::{GenericServer, ServerProfile};
+pub use proxy::Proxy;
+pub use rate_limit::RateLimiter;
 
 use crate::{
-    auth::AuthProvider,
+    auth::{AuthProvider, sasl_plain_messages},
     irc_message::{
-        ToIrcMessage, builder::MessageBuilder, command::IrcCommand, message::IrcMessage,
+        ToIrcMessage,
+        builder::MessageBuilder,
+        command::{IrcCommand, numeric},
+        message::IrcMessage,
+        storage::{BytesStr, MessageStorage},
+        tags::OwnedTag,
     },
+    login::ChannelLogin,
+    metrics,
+    recording::{Direction, Recorder},
+    runtime::{self, Runtime, WebSocket},
+    time::{ClockOffset, system_time_to_millis},
 };
 
 /// Error types associated with [Connection] and related operations
 pub mod error {
+    use async_tungstenite::tungstenite::{Error as TungsteniteError, error::ProtocolError};
     use thiserror::Error;
-    use tokio_tungstenite::tungstenite::{Error as TungsteniteError, error::ProtocolError};
 
-    use crate::irc_message::error::IrcMessageParseError;
+    use crate::irc_message::{builder::MessageBuilderError, error::IrcMessageParseError};
 
     /// [Connection](super::Connection) errors
     #[derive(Debug, Error)]
@@ -37,15 +80,30 @@ pub mod error {
         /// A closed [Connection] was read/written to
         #[error("this Connection has been closed")]
         Closed,
-        /// An Error in the `tokio_tungstenite` websocket library
+        /// An Error in the `tungstenite` websocket library
         #[error(transparent)]
         TungsteniteError(TungsteniteError),
         /// An invalid IRCv3 message was received from the websocket
         #[error("the received message from the websocket was not a valid IRC message:\n {0}")]
         InvalidMessage(#[from] IrcMessageParseError),
+        /// An invalid IRC message was attempted to be sent
+        #[error("the message to be sent was not a valid IRC message: {0}")]
+        InvalidOutgoing(#[from] MessageBuilderError),
         /// No content was received from the underlying websocket connection
         #[error("the Connection received a websocket message, but no valid content was found")]
         NoMessage,
+        /// The server rejected the connection's registration
+        #[error("the server rejected the registration: {0}")]
+        RegistrationFailed(String),
+        /// SASL authentication failed or isn't supported by the server
+        #[error("SASL authentication failed: {0}")]
+        SaslFailed(String),
+        /// The server neither accepted nor rejected the registration in time
+        #[error("the server didn't accept the registration within {0:?}")]
+        RegistrationTimedOut(std::time::Duration),
+        /// The server didn't answer a keepalive `PING` in time
+        #[error("the server didn't answer a PING within {0:?}")]
+        PingTimeout(std::time::Duration),
     }
 
     /// [ConnectionPool](super::pool::ConnectionPool) errors
@@ -54,6 +112,10 @@ pub mod error {
         /// An error related to an internal [Connection](super::Connection)
         #[error(transparent)]
         ConnectionError(#[from] ConnectionError),
+        /// The [Connection](super::Connection) at the given index failed to
+        /// receive a message
+        #[error("connection {0} failed to receive: {1}")]
+        ReceiveFailed(usize, #[source] ConnectionError),
         /// A channel interaction was requested for a channel that was not joined
         #[error("The requested channel was not found {0}")]
         ChannelNotFound(String),
@@ -67,6 +129,10 @@ pub mod error {
         /// There are no connections to receive from
         #[error("there are no connections to receive from")]
         NoConnections,
+        /// Write connections were started in a pool that already had
+        /// connections
+        #[error("write connections can only be started in an empty pool")]
+        NotEmpty,
     }
 
     impl From<TungsteniteError> for ConnectionError {
@@ -81,17 +147,73 @@ pub mod error {
     }
 }
 
-const TWITCH_IRC_URL: &str = "wss://irc-ws.chat.twitch.tv:443";
+/// Storage types that messages received by a [Connection] can be backed by
+pub trait ConnectionStorage: MessageStorage + From<BytesStr> + Unpin {}
 
-type Websocket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+impl<T: MessageStorage + From<BytesStr> + Unpin> ConnectionStorage for T {}
+
+/// How often a [Connection] sends a keepalive `PING` by default
+pub const DEFAULT_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(60);
 
 /// handles the interface between the raw `Socket` and the `TwitchIrcClient`
-pub struct Connection<A: AuthProvider> {
-    socket: Option<Websocket>,
+///
+/// Received messages are backed by `C`, where [BytesStr] avoids copying each
+/// message out of the websocket frame it came in
+pub struct Connection<A: AuthProvider, C: ConnectionStorage = String> {
+    socket: Option<Box<dyn WebSocket>>,
+    runtime: Arc<dyn Runtime>,
+    proxy: Option<Proxy>,
     state: ConnectionState,
-    channel_list: HashSet<String>,
-    buffer: VecDeque<Result<IrcMessage, ConnectionError>>,
+    channel_list: HashSet<ChannelLogin>,
+    buffer: VecDeque<Result<IrcMessage<C>, ConnectionError>>,
     auth_info: Box<A>,
+    clock: ClockOffset,
+    profile: ServerProfile,
+    recorder: Option<ConnectionRecorder>,
+    events: broadcast::Sender<LifecycleEvent>,
+    index: usize,
+    /// parent of what happens on the connection, besides sending
+    span: Span,
+    /// the nickname the server registered the connection with
+    nick: Option<String>,
+    reconnect_attempt: u32,
+    /// data and send time of the last `PING` sent with [Connection::ping]
+    ping_sent: Option<(String, Instant)>,
+    /// how often a keepalive `PING` is sent, `None` if never
+    keepalive: Option<Duration>,
+    /// completes when the next keepalive `PING` is due, `None` while not
+    /// started or without a keepalive
+    ping_timer: Option<BoxFuture<'static, ()>>,
+    /// a keepalive `PING` is due but the socket wasn't ready to send it
+    ping_due: bool,
+    /// a keepalive `PING` was sent but not flushed yet
+    ping_unflushed: bool,
+    #[cfg(feature = "backfill")]
+    backfill: Option<backfill::Backfilling<C>>,
+}
+
+/// A [Recorder] shared by the connections of a pool, along with the index of
+/// the connection it records for
+struct ConnectionRecorder {
+    recorder: Arc<Mutex<Recorder>>,
+    index: usize,
+}
+
+impl ConnectionRecorder {
+    fn record(&self, direction: Direction, lines: &str) {
+        let mut recorder = self.recorder.lock().unwrap();
+        for line in lines.split("\r\n").filter(|l| !l.is_empty()) {
+            if let Err(e) = recorder.record(self.index, direction, line) {
+                warn!("failed to record message: {e}");
+            }
+        }
+    }
+}
+
+/// An outgoing message as it is logged, see [IrcMessage::redacted]
+fn redacted<'a>(command: &IrcCommand, out: &'a str) -> &'a str {
+    let first_param = out.split_once(' ').map(|(_, param)| param);
+    crate::irc_message::message::redacted(command, first_param, out)
 }
 
 /// State of the [Connection]
@@ -105,16 +227,210 @@ pub enum ConnectionState {
     Working,
 }
 
-// TODO: add logging
-impl<A: AuthProvider> Connection<A> {
+impl<A: AuthProvider, C: ConnectionStorage> Connection<A, C> {
     /// Create a new [Connection] that joins `channels` upon being started
-    pub fn new(channels: impl IntoIterator<Item = impl Into<String>>, auth: A) -> Self {
+    pub fn new(channels: impl IntoIterator<Item = impl Into<ChannelLogin>>, auth: A) -> Self {
         Self {
             socket: None,
+            runtime: runtime::default_runtime(),
+            proxy: None,
             state: ConnectionState::Closed,
             channel_list: channels.into_iter().map(|i| i.into()).collect(),
             buffer: VecDeque::new(),
             auth_info: Box::new(auth),
+            clock: ClockOffset::new(),
+            profile: ServerProfile::Twitch,
+            recorder: None,
+            events: broadcast::channel(lifecycle::EVENT_CHANNEL_SIZE).0,
+            index: 0,
+            span: info_span!("connection", index = 0, nick = field::Empty),
+            nick: None,
+            reconnect_attempt: 0,
+            ping_sent: None,
+            keepalive: Some(DEFAULT_KEEPALIVE_INTERVAL),
+            ping_timer: None,
+            ping_due: false,
+            ping_unflushed: false,
+            #[cfg(feature = "backfill")]
+            backfill: None,
+        }
+    }
+
+    /// Create a new [Connection] to the server described by `profile` that joins
+    /// `channels` upon being started
+    pub fn with_profile(
+        channels: impl IntoIterator<Item = impl Into<ChannelLogin>>,
+        auth: A,
+        profile: impl Into<ServerProfile>,
+    ) -> Self {
+        Self {
+            profile: profile.into(),
+            ..Self::new(channels, auth)
+        }
+    }
+
+    /// The kind of server this connection talks to
+    pub fn profile(&self) -> &ServerProfile {
+        &self.profile
+    }
+
+    /// Offset between the local clock and Twitch's, estimated from the
+    /// timestamps of received messages
+    pub fn clock_offset(&self) -> &ClockOffset {
+        &self.clock
+    }
+
+    /// Records every raw IRC line received and sent by this connection with
+    /// `recorder`, as the connection at `index` of its pool
+    pub fn record_to(&mut self, recorder: Arc<Mutex<Recorder>>, index: usize) {
+        self.recorder = Some(ConnectionRecorder { recorder, index });
+    }
+
+    /// Sets the runtime the connection opens its websocket with, the
+    /// [default runtime](runtime::default_runtime) otherwise. Takes effect
+    /// the next time the connection is started
+    pub fn set_runtime(&mut self, runtime: Arc<dyn Runtime>) {
+        #[cfg(feature = "backfill")]
+        if let Some(backfill) = &mut self.backfill {
+            backfill.runtime = runtime.clone();
+        }
+        self.runtime = runtime;
+    }
+
+    /// Sets the proxy the connection is tunneled through, such as one from
+    /// [Proxy::from_env]. Takes effect the next time the connection is started
+    pub fn set_proxy(&mut self, proxy: Option<Proxy>) {
+        self.proxy = proxy;
+    }
+
+    /// Subscribes to the [LifecycleEvent]s of this connection, events that
+    /// happen while there are no subscribers are lost
+    pub fn subscribe(&self) -> broadcast::Receiver<LifecycleEvent> {
+        self.events.subscribe()
+    }
+
+    /// Sends the lifecycle events of this connection to `events`, as the
+    /// connection at `index` of its pool
+    pub(crate) fn set_events(&mut self, events: broadcast::Sender<LifecycleEvent>, index: usize) {
+        self.events = events;
+        self.index = index;
+        self.span.record("index", index);
+    }
+
+    fn emit(&self, event: ConnectionEvent) {
+        // no subscribers is fine
+        let _ = self.events.send(LifecycleEvent {
+            connection: self.index,
+            event,
+        });
+    }
+
+    /// Emits the lifecycle events a received message signals
+    fn observe_lifecycle<D: std::ops::Deref<Target = str>>(&mut self, msg: &IrcMessage<D>) {
+        let own = || self.nick.is_some() && msg.get_nickname() == self.nick.as_deref();
+        let channel = || {
+            ChannelLogin::from_irc_param(msg.get_param(0).unwrap_or_default())
+                .ok()
+                .map(ChannelLogin::into_owned)
+        };
+        match msg.get_command() {
+            IrcCommand::AuthSuccessful => {
+                self.nick = msg.get_param(0).map(ToOwned::to_owned);
+                self.span.record("nick", self.nick.as_deref());
+                self.reconnect_attempt = 0;
+                self.emit(ConnectionEvent::Authenticated);
+            }
+            IrcCommand::Join if own() => {
+                if let Some(channel) = channel() {
+                    self.emit(ConnectionEvent::Joined(channel));
+                }
+            }
+            IrcCommand::Part if own() => {
+                if let Some(channel) = channel() {
+                    self.emit(ConnectionEvent::Parted(channel));
+                }
+            }
+            IrcCommand::Pong => {
+                let data = msg.params().last();
+                if let Some((sent, at)) = &self.ping_sent
+                    && data == Some(sent.as_str())
+                {
+                    let latency = at.elapsed();
+                    self.ping_sent = None;
+                    histogram!(metrics::PING_LATENCY).record(latency);
+                    self.emit(ConnectionEvent::LatencyMeasured(latency));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Closes the connection after the websocket errored or was closed
+    fn disconnected(&mut self, error: ConnectionError) -> ConnectionError {
+        self.socket = None;
+        self.state = ConnectionState::Closed;
+        self.ping_sent = None;
+        self.ping_timer = None;
+        self.ping_due = false;
+        self.ping_unflushed = false;
+        self.emit(ConnectionEvent::Disconnected(error.to_string()));
+        error
+    }
+
+    /// Sets how often a `PING` is sent while [receiving](Self::poll_receive), every
+    /// [DEFAULT_KEEPALIVE_INTERVAL] by default. The connection is closed with
+    /// [ConnectionError::PingTimeout] if a `PING` isn't answered before the
+    /// next one is due. `None` disables the keepalive
+    pub fn set_keepalive(&mut self, interval: Option<Duration>) {
+        self.keepalive = interval;
+        if self.state == ConnectionState::Working {
+            self.ping_timer = interval.map(|i| self.runtime.sleep(i));
+        }
+    }
+
+    /// Sends a `PING`, its `PONG` is reported as a
+    /// [ConnectionEvent::LatencyMeasured] event
+    pub async fn ping(&mut self) -> Result<(), ConnectionError> {
+        let data = system_time_to_millis(SystemTime::now()).to_string();
+        self.send(MessageBuilder::new(IrcCommand::Ping).add_param(data.as_str()))
+            .await?;
+        self.ping_sent = Some((data, Instant::now()));
+        Ok(())
+    }
+
+    /// Parses every IRC message in a websocket message into the buffer,
+    /// observing their timestamps
+    fn buffer_ws_message(&mut self, ws_message: WsMessage) {
+        let received_at = SystemTime::now();
+        let _span = self.span.clone().entered();
+        if let (Some(recorder), Ok(text)) = (&self.recorder, ws_message.to_text()) {
+            recorder.record(Direction::Received, text);
+        }
+        for msg in IrcMessage::from_ws_message(ws_message) {
+            let msg = msg.map(IrcMessage::into_storage).map_err(Into::into);
+            if msg.is_err() {
+                counter!(metrics::PARSE_ERRORS).increment(1);
+            }
+            if let Ok(msg) = &msg {
+                let command = msg.get_command();
+                counter!(metrics::MESSAGES_RECEIVED, "command" => metrics::command_label(&command))
+                    .increment(1);
+                if command == IrcCommand::Notice {
+                    let kind = msg.get_tag_raw(OwnedTag::MsgId).unwrap_or("unknown");
+                    counter!(metrics::NOTICES, "kind" => kind.to_owned()).increment(1);
+                }
+                self.clock.observe(msg, received_at);
+                self.observe_lifecycle(msg);
+            }
+            #[cfg(feature = "backfill")]
+            let msg = match (msg, &mut self.backfill) {
+                (Ok(msg), Some(backfill)) => match backfill.live(msg) {
+                    Some(msg) => Ok(msg),
+                    None => continue,
+                },
+                (msg, _) => msg,
+            };
+            self.buffer.push_back(msg);
         }
     }
 
@@ -125,25 +441,87 @@ impl<A: AuthProvider> Connection<A> {
 
     /// Connects to the IRC websocket and sends `JOIN` messages for added channels.
     ///
+    /// For servers other than Twitch, this waits until the server accepts the
+    /// registration, and messages received until then are still returned by
+    /// [receive](Self::receive).
+    ///
     /// Errors if the connection is already started.
+    #[instrument(parent = &self.span, skip_all)]
     pub async fn start(&mut self) -> Result<(), ConnectionError> {
         if self.socket.is_some() {
             warn!("tried starting connection when it was already started");
             return Err(ConnectionError::AlreadyStarted);
         }
 
-        let (new_socket, _resp) = tokio_tungstenite::connect_async(TWITCH_IRC_URL)
-            .await
-            .map_err(ConnectionError::TungsteniteError)?;
+        self.emit(ConnectionEvent::Connecting);
+        let url = self.profile.url();
+        let proxy = self
+            .proxy
+            .as_ref()
+            .filter(|proxy| proxy::target(url).map_or(true, |(host, _)| !proxy.bypasses(&host)));
+        let new_socket = match proxy {
+            Some(proxy) => {
+                debug!("connecting to {url} through {proxy}");
+                self.runtime.connect_proxied(url, proxy).await
+            }
+            None => self.runtime.connect(url).await,
+        }
+        .map_err(ConnectionError::TungsteniteError)?;
 
         self.socket = Some(new_socket);
         self.state = ConnectionState::StartedUnauthed;
 
+        match self.profile.clone() {
+            ServerProfile::Twitch | ServerProfile::TwitchAt(_) => self.register_twitch().await?,
+            ServerProfile::Generic(server) => self.register_generic(&server).await?,
+        }
+
+        #[cfg(feature = "backfill")]
+        if let Some(backfill) = &mut self.backfill {
+            backfill.request(self.channel_list.iter().cloned());
+        }
+
+        self.ping_sent = None;
+        self.ping_timer = self.keepalive.map(|i| self.runtime.sleep(i));
+        Ok(())
+    }
+
+    /// Fetches the recent messages of channels in the background the first
+    /// time they are joined, returning them before live messages. Channels that
+    /// are already joined are backfilled right away
+    #[cfg(feature = "backfill")]
+    pub async fn enable_backfill(&mut self, backfill: Backfill) {
+        let mut backfill = backfill::Backfilling::new(backfill, self.runtime.clone());
+        if self.state == ConnectionState::Working {
+            backfill.request(self.channel_list.iter().cloned());
+        }
+        self.backfill = Some(backfill);
+    }
+
+    /// Buffers the recent messages of a backfilled channel, followed by the
+    /// live messages held back until they were fetched
+    #[cfg(feature = "backfill")]
+    fn finish_backfill(&mut self, fetched: backfill::Fetched) {
+        let Some(backfill) = &mut self.backfill else {
+            return;
+        };
+        let (history, held) = backfill.finish(fetched);
+        self.buffer.extend(
+            history
+                .into_iter()
+                .map(|msg| Ok(msg.into_storage::<BytesStr>().into_storage())),
+        );
+        self.buffer.extend(held.into_iter().map(Ok));
+    }
+
+    async fn register_twitch(&mut self) -> Result<(), ConnectionError> {
         let (pass, nick) = self.auth_info.get_commands();
 
         let join_msg = {
             if !self.channel_list.is_empty() {
-                Some(MessageBuilder::join(self.channel_list.iter()))
+                Some(MessageBuilder::join(
+                    self.channel_list.iter().map(ChannelLogin::borrowed),
+                ))
             } else {
                 None
             }
@@ -166,34 +544,168 @@ impl<A: AuthProvider> Connection<A> {
         Ok(())
     }
 
+    async fn register_generic(&mut self, server: &GenericServer) -> Result<(), ConnectionError> {
+        let (pass, nick) = self.auth_info.pass_nick();
+        let sasl = self.auth_info.sasl_plain();
+
+        let mut registration = vec![];
+        if !server.capabilities().is_empty() {
+            registration.push(MessageBuilder::cap_req_with(server.capabilities()));
+        }
+        if sasl.is_some() {
+            registration.push(MessageBuilder::cap_req_with(["sasl"]));
+        }
+        if !pass.is_empty() {
+            registration.push(MessageBuilder::new(IrcCommand::Pass).add_param(pass));
+        }
+        registration.push(MessageBuilder::new(IrcCommand::Nick).add_param(nick));
+        registration.push(MessageBuilder::user(
+            server.get_username(),
+            server.get_realname(),
+        ));
+        if sasl.is_none() {
+            registration.push(MessageBuilder::cap_end());
+        }
+        self.send_batched(registration).await?;
+
+        let timeout = server.get_registration_timeout();
+        let deadline = self.runtime.sleep(timeout);
+        let registered = self.await_registration(sasl.as_ref());
+        let received = match select(pin!(registered), deadline).await {
+            Either::Left((received, _)) => received?,
+            Either::Right(_) => return Err(ConnectionError::RegistrationTimedOut(timeout)),
+        };
+
+        // messages received during registration come before any buffered after it
+        let mut received: VecDeque<_> = received.into_iter().map(Ok).collect();
+        received.extend(self.buffer.drain(..));
+        self.buffer = received;
+        self.state = ConnectionState::Working;
+
+        if !self.channel_list.is_empty() {
+            let join_msg =
+                MessageBuilder::join(self.channel_list.iter().map(ChannelLogin::borrowed));
+            self.send(join_msg.to_owned()).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Answers the server until it accepts the registration, authenticating
+    /// through SASL `PLAIN` if `sasl` is set. Returns the messages received
+    /// until then
+    async fn await_registration(
+        &mut self,
+        sasl: Option<&(String, String)>,
+    ) -> Result<Vec<IrcMessage<C>>, ConnectionError> {
+        let mut received = Vec::new();
+        loop {
+            let msg = self.receive().await?;
+            let last_param = || {
+                msg.params()
+                    .last()
+                    .map(ToOwned::to_owned)
+                    .unwrap_or_default()
+            };
+            match msg.get_command() {
+                IrcCommand::Ping => {
+                    let data = msg.get_param(0).unwrap_or_default();
+                    self.send(MessageBuilder::pong(data)).await?;
+                }
+                IrcCommand::Cap
+                    if sasl.is_some()
+                        && msg
+                            .get_param(2)
+                            .is_some_and(|caps| caps.split(' ').any(|c| c == "sasl")) =>
+                {
+                    match msg.get_param(1) {
+                        Some("ACK") => self.send(MessageBuilder::authenticate("PLAIN")).await?,
+                        _ => {
+                            return Err(ConnectionError::SaslFailed(
+                                "the server doesn't support SASL".into(),
+                            ));
+                        }
+                    }
+                }
+                IrcCommand::Authenticate if msg.get_param(0) == Some("+") => {
+                    if let Some((account, password)) = sasl {
+                        let chunks: Vec<_> = sasl_plain_messages(account, password)
+                            .into_iter()
+                            .map(|c| MessageBuilder::new(IrcCommand::Authenticate).add_param(c))
+                            .collect();
+                        self.send_batched(chunks).await?;
+                    }
+                }
+                numeric::RPL_SASLSUCCESS => self.send(MessageBuilder::cap_end()).await?,
+                numeric::ERR_NICKLOCKED
+                | numeric::ERR_SASLFAIL
+                | numeric::ERR_SASLTOOLONG
+                | numeric::ERR_SASLABORTED => {
+                    return Err(ConnectionError::SaslFailed(last_param()));
+                }
+                numeric::ERR_ERRONEUSNICKNAME
+                | numeric::ERR_NICKNAMEINUSE
+                | numeric::ERR_PASSWDMISMATCH
+                | numeric::ERR_YOUREBANNEDCREEP => {
+                    return Err(ConnectionError::RegistrationFailed(last_param()));
+                }
+                IrcCommand::Other(cmd) if cmd.as_str() == "ERROR" => {
+                    return Err(ConnectionError::RegistrationFailed(last_param()));
+                }
+                numeric::RPL_WELCOME => {
+                    received.push(msg);
+                    return Ok(received);
+                }
+                _ => {}
+            }
+            received.push(msg);
+        }
+    }
+
     /// Closes the websocket and restarts the connection.
+    #[instrument(parent = &self.span, skip_all)]
     pub async fn restart(&mut self) -> Result<(), ConnectionError> {
         if let Some(mut socket) = self.socket.take() {
-            socket.close(None).await?;
+            // the socket may already be closed if the connection was lost
+            if let Err(e) = socket.close().await {
+                debug!("failed to close websocket before restarting: {e}");
+            }
+            self.emit(ConnectionEvent::Disconnected("restarting".into()));
         }
+        self.state = ConnectionState::Closed;
+        self.reconnect_attempt += 1;
+        counter!(metrics::RECONNECTS).increment(1);
+        self.emit(ConnectionEvent::Reconnecting(self.reconnect_attempt));
         self.start().await
     }
 
     /// Immediately sends `JOIN` message if the connection has been started, otherwise
     /// sends it when [Connection::start] is called
-    pub async fn join(&mut self, channel: &str) -> Result<(), ConnectionError> {
+    pub async fn join(&mut self, channel: impl Into<ChannelLogin>) -> Result<(), ConnectionError> {
+        let channel = channel.into();
         if self.state != ConnectionState::Working {
-            self.channel_list.insert(channel.into());
+            self.channel_list.insert(channel);
             return Ok(());
-        } else if self.channel_list.insert(channel.into()) {
-            self.send(MessageBuilder::join(std::iter::once(&channel)))
+        } else if !self.channel_list.contains(&channel) {
+            self.send(MessageBuilder::join(std::iter::once(channel.borrowed())))
                 .await?;
+            #[cfg(feature = "backfill")]
+            if let Some(backfill) = &mut self.backfill {
+                backfill.request([channel.clone()]);
+            }
+            self.channel_list.insert(channel);
         }
         Ok(())
     }
 
     /// Sends `PART` message if the connection has been started, otherwise
     /// removes it from channels joined when [Connection::start] is called
-    pub async fn part(&mut self, channel: &str) -> Result<(), ConnectionError> {
+    pub async fn part(&mut self, channel: impl Into<ChannelLogin>) -> Result<(), ConnectionError> {
+        let channel = channel.into();
         if self.state != ConnectionState::Working {
-            self.channel_list.remove(channel);
+            self.channel_list.remove(&channel);
             return Ok(());
-        } else if self.channel_list.remove(channel) {
+        } else if self.channel_list.remove(&channel) {
             self.send(MessageBuilder::part(std::iter::once(channel)))
                 .await?;
         }
@@ -203,48 +715,114 @@ impl<A: AuthProvider> Connection<A> {
     /// Receives a single new message from Twitch. Multi-message websocket messages
     /// have their IRC messages buffered and are returned immediately upon subsequent calls
     /// to this function.
-    pub async fn receive(&mut self) -> Result<IrcMessage, ConnectionError> {
-        if let Some(next) = self.buffer.pop_front() {
-            log::trace!(
-                "Received new message: {:?}",
-                next.as_ref().map(|i| i.inner())
-            );
-            return next;
-        }
+    pub async fn receive(&mut self) -> Result<IrcMessage<C>, ConnectionError> {
+        futures_util::future::poll_fn(|cx| self.poll_receive(cx)).await
+    }
 
-        if let Some(socket) = &mut self.socket {
-            let received_msg = socket.next().await.ok_or(ConnectionError::Closed)??;
+    /// Polls for the next received message, like [receive](Self::receive).
+    ///
+    /// Keepalive `PING`s are sent and backfilled channels are released while
+    /// polling, so the connection must keep being polled while it is idle
+    pub fn poll_receive(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<IrcMessage<C>, ConnectionError>> {
+        loop {
+            if let Some(next) = self.buffer.pop_front() {
+                trace!(
+                    parent: &self.span,
+                    "Received new message: {:?}",
+                    next.as_ref().map(|i| i.inner())
+                );
+                return Poll::Ready(next);
+            }
+            if self.socket.is_none() {
+                return Poll::Ready(Err(ConnectionError::NotStarted));
+            }
 
-            let mut msgs =
-                IrcMessage::from_ws_message(&received_msg).map(|n| n.map_err(Into::into));
+            self.poll_keepalive(cx)?;
 
-            let next = msgs.next().ok_or(ConnectionError::NoMessage)?;
+            #[cfg(feature = "backfill")]
+            if let Some(backfill) = &mut self.backfill
+                && backfill.is_pending()
+                && let Poll::Ready(Some(fetched)) = backfill.poll_fetched(cx)
+            {
+                self.finish_backfill(fetched);
+                continue;
+            }
 
-            self.buffer.extend(msgs);
+            // websocket messages with no IRC messages left to return, such as
+            // pings or duplicates of backfilled messages, are skipped
+            let socket = self.socket.as_mut().ok_or(ConnectionError::NotStarted)?;
+            match futures_util::ready!(socket.poll_next_unpin(cx)) {
+                Some(Ok(received_msg)) => self.buffer_ws_message(received_msg),
+                Some(Err(e)) => return Poll::Ready(Err(self.disconnected(e.into()))),
+                None => return Poll::Ready(Err(self.disconnected(ConnectionError::Closed))),
+            }
+        }
+    }
 
-            log::trace!(
-                "Received new message: {:?}",
-                next.as_ref().map(|i| i.inner())
-            );
-            next
-        } else {
-            Err(ConnectionError::NotStarted)
+    /// Sends a keepalive `PING` once it is due, or closes the connection if
+    /// the server didn't answer the last one. Wakes the task when the next one
+    /// is due
+    fn poll_keepalive(&mut self, cx: &mut Context<'_>) -> Result<(), ConnectionError> {
+        if let Some(timer) = &mut self.ping_timer
+            && timer.as_mut().poll(cx).is_ready()
+        {
+            let interval = self.keepalive.unwrap_or(DEFAULT_KEEPALIVE_INTERVAL);
+            let mut timer = self.runtime.sleep(interval);
+            // registers the waker for the next one
+            let _ = timer.as_mut().poll(cx);
+            self.ping_timer = Some(timer);
+            if self.ping_sent.is_some() {
+                return Err(self.disconnected(ConnectionError::PingTimeout(interval)));
+            }
+            self.ping_due = true;
         }
+
+        if self.ping_due {
+            match Sink::<MessageBuilder>::poll_ready(Pin::new(&mut *self), cx) {
+                Poll::Ready(Ok(())) => {
+                    let data = system_time_to_millis(SystemTime::now()).to_string();
+                    let ping = MessageBuilder::new(IrcCommand::Ping).add_param(data.as_str());
+                    if let Err(e) = Pin::new(&mut *self).start_send(ping) {
+                        return Err(self.disconnected(e));
+                    }
+                    self.ping_sent = Some((data, Instant::now()));
+                    self.ping_due = false;
+                    self.ping_unflushed = true;
+                }
+                Poll::Ready(Err(e)) => return Err(self.disconnected(e)),
+                Poll::Pending => {}
+            }
+        }
+
+        if self.ping_unflushed {
+            match Sink::<MessageBuilder>::poll_flush(Pin::new(&mut *self), cx) {
+                Poll::Ready(Ok(())) => self.ping_unflushed = false,
+                Poll::Ready(Err(e)) => return Err(self.disconnected(e)),
+                Poll::Pending => {}
+            }
+        }
+        Ok(())
     }
 
     /// Immediately sends an IRC message to Twitch
+    #[instrument(
+        level = "debug",
+        skip_all,
+        fields(connection = self.index, command = %message.get_command())
+    )]
     pub async fn send(&mut self, message: impl ToIrcMessage) -> Result<(), ConnectionError> {
         if let Some(socket) = &mut self.socket {
             let command = message.get_command();
-            let out = message.to_message();
-            debug!(
-                "sent: {:?}",
-                if command == IrcCommand::Pass {
-                    "[user token redacted]"
-                } else {
-                    out.trim()
-                }
-            );
+            let out = message.to_message()?;
+            debug!("sent: {:?}", redacted(&command, &out));
+            if let Some(recorder) = &self.recorder {
+                recorder.record(Direction::Sent, &out);
+            }
+            counter!(metrics::MESSAGES_SENT, "command" => metrics::command_label(&command))
+                .increment(1);
             socket.send(WsMessage::Text(out.into())).await?;
             Ok(())
         } else {
@@ -254,6 +832,7 @@ impl<A: AuthProvider> Connection<A> {
 
     /// Immediately sends many IRC messages to Twitch. This method should be
     /// preferred to using [send](Connection::send) when many messages must be sent
+    #[instrument(level = "debug", skip_all, fields(connection = self.index))]
     pub async fn send_batched(
         &mut self,
         messages: impl IntoIterator<Item = impl ToIrcMessage>,
@@ -261,15 +840,13 @@ impl<A: AuthProvider> Connection<A> {
         if let Some(socket) = &mut self.socket {
             for i in messages {
                 let cmd = i.get_command();
-                let out = i.to_message();
-                debug!(
-                    "sent: {:?}",
-                    if cmd == IrcCommand::Pass {
-                        "[user token redacted]"
-                    } else {
-                        out.trim()
-                    }
-                );
+                let out = i.to_message()?;
+                debug!("sent: {:?}", redacted(&cmd, &out));
+                if let Some(recorder) = &self.recorder {
+                    recorder.record(Direction::Sent, &out);
+                }
+                counter!(metrics::MESSAGES_SENT, "command" => metrics::command_label(&cmd))
+                    .increment(1);
                 socket.feed(WsMessage::Text(out.into())).await?;
             }
             socket.flush().await?;
@@ -285,51 +862,24 @@ impl<A: AuthProvider> Connection<A> {
     }
 }
 
-impl<A: AuthProvider> FusedStream for Connection<A> {
+impl<A: AuthProvider, C: ConnectionStorage> FusedStream for Connection<A, C> {
     fn is_terminated(&self) -> bool {
         self.socket.as_ref().is_some_and(|s| s.is_terminated())
     }
 }
 
-impl<A: AuthProvider> Stream for Connection<A> {
-    type Item = Result<IrcMessage, ConnectionError>;
+impl<A: AuthProvider, C: ConnectionStorage> Stream for Connection<A, C> {
+    type Item = Result<IrcMessage<C>, ConnectionError>;
 
     fn poll_next(
         mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Option<Self::Item>> {
-        if let Some(next) = self.buffer.pop_front() {
-            log::trace!(
-                "Received new message: {:?}",
-                next.as_ref().map(|i| i.inner())
-            );
-            return Poll::Ready(Some(next));
-        }
-        let Some(socket) = self.socket.as_mut() else {
-            return Poll::Ready(Some(Err(ConnectionError::NotStarted)));
-        };
-        let ready = futures_util::ready!(socket.poll_next_unpin(cx));
-        match ready {
-            Some(Ok(recv)) => {
-                let mut msgs = IrcMessage::from_ws_message(&recv).map(|n| n.map_err(Into::into));
-
-                let next = msgs.next().ok_or(ConnectionError::NoMessage)?;
-
-                self.buffer.extend(msgs);
-
-                log::trace!(
-                    "Received new message: {:?}",
-                    next.as_ref().map(|i| i.inner())
-                );
-                Poll::Ready(Some(next))
-            }
-            Some(Err(e)) => Poll::Ready(Some(Err(e.into()))),
-            None => Poll::Ready(Some(Err(ConnectionError::Closed))),
-        }
+        self.poll_receive(cx).map(Some)
     }
 }
 
-impl<T: ToIrcMessage, A: AuthProvider> Sink<T> for Connection<A> {
+impl<T: ToIrcMessage, A: AuthProvider, C: ConnectionStorage> Sink<T> for Connection<A, C> {
     type Error = ConnectionError;
 
     fn poll_ready(
@@ -344,10 +894,18 @@ impl<T: ToIrcMessage, A: AuthProvider> Sink<T> for Connection<A> {
     }
 
     fn start_send(mut self: std::pin::Pin<&mut Self>, item: T) -> Result<(), Self::Error> {
+        let command = item.get_command();
+        let out = item.to_message()?;
+        debug!(parent: &self.span, "sent: {:?}", redacted(&command, &out));
+        if let Some(recorder) = &self.recorder {
+            recorder.record(Direction::Sent, &out);
+        }
+        counter!(metrics::MESSAGES_SENT, "command" => metrics::command_label(&command))
+            .increment(1);
         self.socket
             .as_mut()
             .ok_or(ConnectionError::NotStarted)?
-            .start_send_unpin(WsMessage::Text(item.to_message().into()))
+            .start_send_unpin(WsMessage::Text(out.into()))
             .map_err(Into::into)
     }
 
@@ -373,3 +931,333 @@ impl<T: ToIrcMessage, A: AuthProvider> Sink<T> for Connection<A> {
             .map_err(Into::into)
     }
 }
+
+#[cfg(all(test, feature = "tokio-runtime"))]
+mod tests {
+    use std::time::Duration;
+
+    use async_tungstenite::{
+        WebSocketStream,
+        tokio::{TokioAdapter, accept_async},
+        tungstenite::Message as WsMessage,
+    };
+    use std::sync::{Arc, Mutex};
+
+    use futures_util::StreamExt;
+    use tokio::net::TcpListener;
+
+    use super::{
+        Connection, ConnectionEvent, ConnectionState, GenericServer, LifecycleEvent,
+        error::ConnectionError,
+    };
+    use crate::{
+        IrcCommand,
+        auth::{Anonymous, OAuth, SaslPlain},
+        login::ChannelLogin,
+        testing::MockServer,
+    };
+
+    type ServerSocket = WebSocketStream<TokioAdapter<tokio::net::TcpStream>>;
+
+    async fn expect(ws: &mut ServerSocket, line: &str) {
+        let msg = ws.next().await.unwrap().unwrap();
+        assert_eq!(msg.to_text().unwrap(), format!("{line}\r\n"));
+    }
+
+    async fn reply(ws: &mut ServerSocket, line: &str) {
+        ws.send(WsMessage::Text(format!("{line}\r\n").into()))
+            .await
+            .unwrap();
+    }
+
+    /// Runs a fake IRC server that handles a single client with `handler`
+    async fn mock_server<F: Future<Output = ()> + Send>(
+        handler: impl FnOnce(ServerSocket) -> F + Send + 'static,
+    ) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            handler(accept_async(stream).await.unwrap()).await;
+        });
+        url
+    }
+
+    fn sasl_auth() -> SaslPlain {
+        SaslPlain {
+            nick: "ferris".into(),
+            account: "jilles".into(),
+            password: "sesame".into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn generic_registration() {
+        let url = mock_server(|mut ws| async move {
+            expect(&mut ws, "CAP REQ :message-tags server-time").await;
+            expect(&mut ws, "CAP REQ sasl").await;
+            expect(&mut ws, "NICK ferris").await;
+            expect(&mut ws, "USER twixel 0 * twixel").await;
+            reply(
+                &mut ws,
+                ":irc.example.com CAP * ACK :message-tags server-time",
+            )
+            .await;
+            reply(&mut ws, ":irc.example.com CAP * ACK :sasl").await;
+            expect(&mut ws, "AUTHENTICATE PLAIN").await;
+            reply(&mut ws, "AUTHENTICATE +").await;
+            expect(&mut ws, "AUTHENTICATE AGppbGxlcwBzZXNhbWU=").await;
+            reply(&mut ws, "PING :cookie").await;
+            expect(&mut ws, "PONG cookie").await;
+            reply(
+                &mut ws,
+                ":irc.example.com 903 ferris :SASL authentication successful",
+            )
+            .await;
+            expect(&mut ws, "CAP END").await;
+            reply(&mut ws, ":irc.example.com 001 ferris :Welcome ferris").await;
+            expect(&mut ws, "JOIN #rust-lang").await;
+            reply(&mut ws, ":corro!c@host PRIVMSG #rust-lang :hi ferris").await;
+            // keep the socket open until the client is done
+            let _ = ws.next().await;
+        })
+        .await;
+
+        let mut conn: Connection<_> = Connection::with_profile(
+            [ChannelLogin::parse_irc("#Rust-Lang").unwrap()],
+            sasl_auth(),
+            GenericServer::new(url),
+        );
+        conn.start().await.unwrap();
+
+        // messages received while registering are kept
+        let mut commands = vec![];
+        loop {
+            let msg = conn.receive().await.unwrap();
+            commands.push(msg.get_command());
+            if msg.get_command() == IrcCommand::PrivMsg {
+                assert_eq!(msg.get_param(1), Some("hi ferris"));
+                break;
+            }
+        }
+        assert_eq!(
+            commands,
+            [
+                IrcCommand::Cap,
+                IrcCommand::Cap,
+                IrcCommand::Authenticate,
+                IrcCommand::Ping,
+                IrcCommand::Numeric(903),
+                IrcCommand::AuthSuccessful,
+                IrcCommand::PrivMsg
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn sasl_failure() {
+        let url = mock_server(|mut ws| async move {
+            expect(&mut ws, "CAP REQ sasl").await;
+            expect(&mut ws, "NICK ferris").await;
+            expect(&mut ws, "USER twixel 0 * twixel").await;
+            reply(&mut ws, ":irc.example.com CAP * ACK :sasl").await;
+            expect(&mut ws, "AUTHENTICATE PLAIN").await;
+            reply(&mut ws, "AUTHENTICATE +").await;
+            expect(&mut ws, "AUTHENTICATE AGppbGxlcwBzZXNhbWU=").await;
+            reply(
+                &mut ws,
+                ":irc.example.com 904 ferris :SASL authentication failed",
+            )
+            .await;
+            let _ = ws.next().await;
+        })
+        .await;
+
+        let mut conn: Connection<_> = Connection::with_profile(
+            core::iter::empty::<ChannelLogin>(),
+            sasl_auth(),
+            GenericServer::new(url).without_capabilities(),
+        );
+        assert!(matches!(
+            conn.start().await,
+            Err(ConnectionError::SaslFailed(reason)) if reason == "SASL authentication failed"
+        ));
+    }
+
+    #[tokio::test]
+    async fn registration_timeout() {
+        let url = mock_server(|mut ws| async move {
+            // read everything but never answer
+            while ws.next().await.is_some() {}
+        })
+        .await;
+
+        let timeout = std::time::Duration::from_millis(100);
+        let mut conn: Connection<_> = Connection::with_profile(
+            core::iter::empty::<ChannelLogin>(),
+            sasl_auth(),
+            GenericServer::new(url).registration_timeout(timeout),
+        );
+        assert!(matches!(
+            conn.start().await,
+            Err(ConnectionError::RegistrationTimedOut(t)) if t == timeout
+        ));
+    }
+
+    #[tokio::test]
+    async fn keepalive_latency() {
+        let server = MockServer::start().await.unwrap();
+        let mut conn: Connection<_> = Connection::with_profile(
+            [ChannelLogin::parse("foo").unwrap()],
+            Anonymous,
+            server.profile(),
+        );
+        conn.set_keepalive(Some(Duration::from_millis(50)));
+        let mut events = conn.subscribe();
+        conn.start().await.unwrap();
+
+        let latency = async {
+            loop {
+                match events.try_recv() {
+                    Ok(LifecycleEvent {
+                        event: ConnectionEvent::LatencyMeasured(latency),
+                        ..
+                    }) => return latency,
+                    Ok(_) => {}
+                    Err(_) => {
+                        conn.receive().await.unwrap();
+                    }
+                }
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), latency)
+            .await
+            .unwrap();
+        server.wait_for_command(IrcCommand::Ping).await;
+    }
+
+    #[tokio::test]
+    async fn keepalive_timeout() {
+        let url = mock_server(|mut ws| async move {
+            let _ = ws.next().await;
+            reply(&mut ws, ":irc.example.com 001 ferris :Welcome ferris").await;
+            // never answer PINGs
+            while ws.next().await.is_some() {}
+        })
+        .await;
+
+        let interval = Duration::from_millis(50);
+        let mut conn: Connection<_> = Connection::with_profile(
+            core::iter::empty::<ChannelLogin>(),
+            Anonymous,
+            GenericServer::new(url),
+        );
+        conn.set_keepalive(Some(interval));
+        conn.start().await.unwrap();
+        let error = loop {
+            if let Err(e) = conn.receive().await {
+                break e;
+            }
+        };
+        assert!(matches!(error, ConnectionError::PingTimeout(i) if i == interval));
+        assert_eq!(conn.state(), ConnectionState::Closed);
+    }
+
+    #[derive(Clone, Default)]
+    struct Logs(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Logs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Logs {
+        /// Captures every event on the current thread until the guard is dropped
+        fn capture(&self) -> tracing::subscriber::DefaultGuard {
+            let subscriber = tracing_subscriber::fmt()
+                .with_max_level(tracing::Level::TRACE)
+                .with_ansi(false)
+                .with_writer({
+                    let logs = self.clone();
+                    move || logs.clone()
+                })
+                .finish();
+            // tests run on a single thread
+            tracing::subscriber::set_default(subscriber)
+        }
+
+        fn contents(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    #[tokio::test]
+    async fn pass_redacted() {
+        let logs = Logs::default();
+        let _guard = logs.capture();
+
+        let server = MockServer::start().await.unwrap();
+        let auth = OAuth {
+            oauth: "hunter2".into(),
+            nick: "bot".into(),
+        };
+        let mut conn: Connection<_> = Connection::with_profile(
+            [ChannelLogin::parse("foo").unwrap()],
+            auth,
+            server.profile(),
+        );
+        conn.start().await.unwrap();
+        server.wait_for_command(IrcCommand::Join).await;
+
+        let logs = logs.contents();
+        assert!(logs.contains("[user token redacted]"), "{logs}");
+        assert!(logs.contains("connection{index=0}"), "{logs}");
+        assert!(!logs.contains("hunter2"), "{logs}");
+    }
+
+    #[tokio::test]
+    async fn sasl_redacted() {
+        let logs = Logs::default();
+        let _guard = logs.capture();
+
+        let url = mock_server(|mut ws| async move {
+            expect(&mut ws, "CAP REQ sasl").await;
+            expect(&mut ws, "NICK ferris").await;
+            expect(&mut ws, "USER twixel 0 * twixel").await;
+            reply(&mut ws, ":irc.example.com CAP * ACK :sasl").await;
+            expect(&mut ws, "AUTHENTICATE PLAIN").await;
+            reply(&mut ws, "AUTHENTICATE +").await;
+            expect(&mut ws, "AUTHENTICATE AGppbGxlcwBzZXNhbWU=").await;
+            reply(
+                &mut ws,
+                ":irc.example.com 903 ferris :SASL authentication successful",
+            )
+            .await;
+            expect(&mut ws, "CAP END").await;
+            reply(&mut ws, ":irc.example.com 001 ferris :Welcome ferris").await;
+            let _ = ws.next().await;
+        })
+        .await;
+
+        let mut conn: Connection<_> = Connection::with_profile(
+            core::iter::empty::<ChannelLogin>(),
+            sasl_auth(),
+            GenericServer::new(url).without_capabilities(),
+        );
+        conn.start().await.unwrap();
+
+        let logs = logs.contents();
+        assert!(logs.contains("AUTHENTICATE PLAIN"), "{logs}");
+        assert!(
+            logs.contains("AUTHENTICATE [credentials redacted]"),
+            "{logs}"
+        );
+        assert!(!logs.contains("AGppbGxlcwBzZXNhbWU="), "{logs}");
+    }
+}
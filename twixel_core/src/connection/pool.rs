@@ -1,59 +1,227 @@
-use std::task::Poll;
+use std::{
+    sync::{Arc, Mutex},
+    task::{Poll, Waker},
+    time::Duration,
+};
 
+use ::metrics::{counter, gauge};
 use either::Either;
-use futures_util::{FutureExt, Sink, SinkExt, Stream, future::select_all};
+use futures_util::{Sink, SinkExt, Stream};
 use hashbrown::HashMap;
+use tokio::sync::broadcast;
 
 use crate::{
-    auth::AuthProvider,
+    auth::{Anonymous, AuthProvider},
     irc_message::{ToIrcMessage, builder::MessageBuilder, message::IrcMessage},
+    login::ChannelLogin,
+    metrics,
+    recording::Recorder,
+    runtime::{self, Runtime},
 };
 
-use super::{Connection, error::PoolError};
+use super::{
+    Connection, ConnectionState, ConnectionStorage, DEFAULT_KEEPALIVE_INTERVAL, Dedup,
+    LifecycleEvent, Proxy, ServerProfile, error::PoolError, lifecycle,
+};
 
 // current limit
 const MAX_CHANNELS_PER_CONNECTION: usize = 100;
 
+/// Auth of the connections in a pool, read connections of a
+/// [split](ConnectionPool::split) pool are anonymous
+#[derive(Debug, Clone)]
+enum PoolAuth<A> {
+    Authenticated(A),
+    Anonymous,
+}
+
+impl<A: AuthProvider> AuthProvider for PoolAuth<A> {
+    fn pass_nick(&mut self) -> (String, String) {
+        match self {
+            Self::Authenticated(auth) => auth.pass_nick(),
+            Self::Anonymous => Anonymous.pass_nick(),
+        }
+    }
+
+    fn sasl_plain(&mut self) -> Option<(String, String)> {
+        match self {
+            Self::Authenticated(auth) => auth.sasl_plain(),
+            Self::Anonymous => Anonymous.sasl_plain(),
+        }
+    }
+}
+
 /// A pool of [Connection](super::Connection)s, useful for bots that requires being connected to more
-/// than 100 channels
-pub struct ConnectionPool<A: AuthProvider + Clone> {
-    pool: Vec<Connection<A>>,
+/// than 100 channels.
+///
+/// A [split](Self::split) pool joins channels with anonymous read connections
+/// and sends messages through a few authenticated write connections, so a
+/// lagging write socket doesn't hold up reads
+pub struct ConnectionPool<A: AuthProvider + Clone, C: ConnectionStorage = String> {
+    // the write connections come first, then the read connections
+    pool: Vec<Connection<PoolAuth<A>, C>>,
+    // amount of write connections, 0 if reads and writes share connections
+    writers: usize,
     // relation between channel and connection index in the pool
-    channels: HashMap<String, Option<usize>>,
+    channels: HashMap<ChannelLogin, Option<usize>>,
     auth_info: Box<A>,
+    profile: ServerProfile,
+    recorder: Option<Arc<Mutex<Recorder>>>,
+    runtime: Arc<dyn Runtime>,
+    proxy: Option<Proxy>,
+    keepalive: Option<Duration>,
+    events: broadcast::Sender<LifecycleEvent>,
+    dedup: Option<Dedup>,
+    // index of the connection polled first for the next message
+    next_poll: usize,
+    // task waiting to receive while every connection is closed
+    waiting: Option<Waker>,
+    #[cfg(feature = "backfill")]
+    backfill: Option<super::Backfill>,
 }
 
-impl<A: AuthProvider + Clone> ConnectionPool<A> {
+impl<A: AuthProvider + Clone, C: ConnectionStorage> ConnectionPool<A, C> {
     /// Create a new [ConnectionPool] that joins `channels immediately
     pub async fn new(
-        channels: impl IntoIterator<Item = impl Into<String>>,
+        channels: impl IntoIterator<Item = impl Into<ChannelLogin>>,
+        auth: A,
+    ) -> Result<Self, PoolError> {
+        Self::with_profile(channels, auth, ServerProfile::Twitch).await
+    }
+
+    /// Create a new [ConnectionPool] connected to the server described by
+    /// `profile` that joins `channels` immediately
+    pub async fn with_profile(
+        channels: impl IntoIterator<Item = impl Into<ChannelLogin>>,
         auth: A,
+        profile: impl Into<ServerProfile>,
     ) -> Result<Self, PoolError> {
-        let mut pool = Vec::new();
-        let mut channel_list = HashMap::new();
-        let channels: Vec<String> = channels.into_iter().map(|c| c.into()).collect();
+        Self::start(channels, auth, profile.into(), 0).await
+    }
 
-        for (i, window) in channels.windows(MAX_CHANNELS_PER_CONNECTION).enumerate() {
-            let mut conn = Connection::new(window, auth.clone());
-            conn.start().await?;
-            pool.push(conn);
-            for channel in window.iter() {
-                channel_list.insert(channel.to_owned(), Some(i));
+    /// Create a new [ConnectionPool] that joins `channels` immediately with
+    /// anonymous read connections and sends messages through `writers`
+    /// connections authenticated with `auth`
+    pub async fn split(
+        channels: impl IntoIterator<Item = impl Into<ChannelLogin>>,
+        auth: A,
+        writers: usize,
+    ) -> Result<Self, PoolError> {
+        Self::split_with_profile(channels, auth, writers, ServerProfile::Twitch).await
+    }
+
+    /// Like [split](Self::split), connected to the server described by
+    /// `profile`. The server must accept anonymous logins like Twitch does
+    pub async fn split_with_profile(
+        channels: impl IntoIterator<Item = impl Into<ChannelLogin>>,
+        auth: A,
+        writers: usize,
+        profile: impl Into<ServerProfile>,
+    ) -> Result<Self, PoolError> {
+        Self::start(channels, auth, profile.into(), writers.max(1)).await
+    }
+
+    async fn start(
+        channels: impl IntoIterator<Item = impl Into<ChannelLogin>>,
+        auth: A,
+        profile: ServerProfile,
+        writers: usize,
+    ) -> Result<Self, PoolError> {
+        let mut pool = Self {
+            pool: Vec::new(),
+            writers: 0,
+            channels: HashMap::new(),
+            auth_info: Box::new(auth),
+            profile,
+            recorder: None,
+            runtime: runtime::default_runtime(),
+            proxy: None,
+            keepalive: Some(DEFAULT_KEEPALIVE_INTERVAL),
+            events: broadcast::channel(lifecycle::EVENT_CHANNEL_SIZE).0,
+            dedup: None,
+            next_poll: 0,
+            waiting: None,
+            #[cfg(feature = "backfill")]
+            backfill: None,
+        };
+        pool.start_writers(writers).await?;
+
+        let channels: Vec<ChannelLogin> = channels.into_iter().map(|c| c.into()).collect();
+        for chunk in channels.chunks(MAX_CHANNELS_PER_CONNECTION) {
+            let idx = pool
+                .start_connection(chunk.iter().cloned(), pool.read_auth())
+                .await?;
+            for channel in chunk.iter() {
+                pool.channels.insert(channel.to_owned(), Some(idx));
             }
         }
+        Ok(pool)
+    }
 
-        Ok(Self {
-            pool,
-            channels: channel_list,
-            auth_info: Box::new(auth),
-        })
+    /// Starts `count` write connections authenticated with the pool's auth,
+    /// turning the pool into a split one that joins channels with anonymous
+    /// read connections, see [split](Self::split). This allows configuring
+    /// the pool, such as with [set_proxy](Self::set_proxy), before its
+    /// write connections are started.
+    ///
+    /// Errors if the pool already has connections
+    pub async fn start_writers(&mut self, count: usize) -> Result<(), PoolError> {
+        if !self.pool.is_empty() {
+            return Err(PoolError::NotEmpty);
+        }
+        for _ in 0..count {
+            let auth = PoolAuth::Authenticated((*self.auth_info).clone());
+            self.start_connection(core::iter::empty(), auth).await?;
+            self.writers += 1;
+        }
+        Ok(())
+    }
+
+    /// Auth of new read connections
+    fn read_auth(&self) -> PoolAuth<A> {
+        if self.writers == 0 {
+            PoolAuth::Authenticated((*self.auth_info).clone())
+        } else {
+            PoolAuth::Anonymous
+        }
+    }
+
+    /// Starts a new connection at the end of the pool that joins `channels`,
+    /// returns its index
+    async fn start_connection(
+        &mut self,
+        channels: impl IntoIterator<Item = ChannelLogin>,
+        auth: PoolAuth<A>,
+    ) -> Result<usize, PoolError> {
+        let idx = self.pool.len();
+        let mut conn = Connection::with_profile(channels, auth, self.profile.clone());
+        conn.set_events(self.events.clone(), idx);
+        conn.set_runtime(Arc::clone(&self.runtime));
+        conn.set_proxy(self.proxy.clone());
+        conn.set_keepalive(self.keepalive);
+        if let Some(recorder) = &self.recorder {
+            conn.record_to(Arc::clone(recorder), idx);
+        }
+        #[cfg(feature = "backfill")]
+        if let Some(backfill) = &self.backfill {
+            conn.enable_backfill(backfill.clone()).await;
+        }
+        conn.start().await?;
+        self.pool.push(conn);
+        gauge!(metrics::POOL_CONNECTIONS).set(self.pool.len() as f64);
+        self.wake_waiting();
+        Ok(idx)
     }
 
     /// Part a specific channel
-    pub async fn part_channel(&mut self, channel_login: &str) -> Result<(), PoolError> {
+    pub async fn part_channel(
+        &mut self,
+        channel_login: impl Into<ChannelLogin>,
+    ) -> Result<(), PoolError> {
+        let channel_login = channel_login.into();
         match self
             .channels
-            .remove(channel_login)
+            .remove(&channel_login)
             .flatten()
             .and_then(|c| self.pool.get_mut(c))
         {
@@ -66,44 +234,145 @@ impl<A: AuthProvider + Clone> ConnectionPool<A> {
     }
 
     /// Join a specific channel
-    pub async fn join_channel(&mut self, channel_login: &str) -> Result<(), PoolError> {
+    pub async fn join_channel(
+        &mut self,
+        channel_login: impl Into<ChannelLogin>,
+    ) -> Result<(), PoolError> {
+        let channel_login = channel_login.into();
         match self
             .pool
             .iter_mut()
             .enumerate()
+            .skip(self.writers)
             .find(|c| c.1.get_channel_count() < MAX_CHANNELS_PER_CONNECTION)
         {
             Some((idx, conn)) => {
-                conn.join(channel_login).await?;
-                self.channels.insert(channel_login.into(), Some(idx));
+                conn.join(channel_login.clone()).await?;
+                self.channels.insert(channel_login, Some(idx));
                 Ok(())
             }
             None => {
-                let mut conn =
-                    Connection::new(core::iter::once(channel_login), (*self.auth_info).clone());
-                conn.start().await?;
-                self.pool.push(conn);
-                self.channels
-                    .insert(channel_login.into(), Some(self.pool.len() - 1));
-
+                let idx = self
+                    .start_connection(core::iter::once(channel_login.clone()), self.read_auth())
+                    .await?;
+                self.channels.insert(channel_login, Some(idx));
                 Ok(())
             }
         }
     }
 
+    /// Subscribes to the [LifecycleEvent]s of every connection of this pool,
+    /// including ones added later. Events of connections started before
+    /// subscribing, like the ones joining the initial channels, are lost
+    pub fn subscribe(&self) -> broadcast::Receiver<LifecycleEvent> {
+        self.events.subscribe()
+    }
+
+    /// Sets the runtime connections open their websockets with, including
+    /// ones added later. Connections that are already started keep their
+    /// websocket until they are restarted
+    pub fn set_runtime(&mut self, runtime: Arc<dyn Runtime>) {
+        for conn in self.pool.iter_mut() {
+            conn.set_runtime(Arc::clone(&runtime));
+        }
+        self.runtime = runtime;
+    }
+
+    /// Sets the proxy connections are tunneled through, including ones added
+    /// later. Connections that are already started keep their websocket
+    /// until they are restarted
+    pub fn set_proxy(&mut self, proxy: Option<Proxy>) {
+        for conn in self.pool.iter_mut() {
+            conn.set_proxy(proxy.clone());
+        }
+        self.proxy = proxy;
+    }
+
+    /// Sets how often connections send a keepalive `PING` while the pool is
+    /// polled, including ones added later, see [Connection::set_keepalive]
+    pub fn set_keepalive(&mut self, interval: Option<Duration>) {
+        for conn in self.pool.iter_mut() {
+            conn.set_keepalive(interval);
+        }
+        self.keepalive = interval;
+    }
+
+    /// Drops messages that were already returned recently by another
+    /// connection of the pool, see [Dedup]. `None` disables it
+    pub fn set_dedup(&mut self, dedup: Option<Dedup>) {
+        self.dedup = dedup;
+    }
+
+    /// The deduplication of received messages, if enabled, which counts how
+    /// many messages were [dropped](Dedup::dropped)
+    pub fn dedup(&self) -> Option<&Dedup> {
+        self.dedup.as_ref()
+    }
+
+    /// Records every raw IRC line received and sent by the connections of
+    /// this pool, including ones added later, with `recorder`
+    pub fn record_to(&mut self, recorder: Recorder) {
+        let recorder = Arc::new(Mutex::new(recorder));
+        for (idx, conn) in self.pool.iter_mut().enumerate() {
+            conn.record_to(Arc::clone(&recorder), idx);
+        }
+        self.recorder = Some(recorder);
+    }
+
+    /// Fetches the recent messages of channels when they are joined, including
+    /// the ones that already are, see [Connection::enable_backfill]
+    #[cfg(feature = "backfill")]
+    pub async fn enable_backfill(&mut self, backfill: super::Backfill) {
+        for conn in self.pool.iter_mut() {
+            conn.enable_backfill(backfill.clone()).await;
+        }
+        self.backfill = Some(backfill);
+    }
+
+    /// Number of connections in the pool
+    pub fn connection_count(&self) -> usize {
+        self.pool.len()
+    }
+
     /// Get the index of the connection that is joined to the specified channel
     pub fn get_conn_idx(&self, channel_login: &str) -> Option<usize> {
         self.channels.get(channel_login).copied().flatten()
     }
 
-    /// Send a `PRIVMSG` to the connection that is joined to the specified channel
-    pub async fn send_to_channel(&mut self, message: &str, channel: &str) -> Result<(), PoolError> {
+    /// Whether the pool sends messages through separate write connections
+    pub fn is_split(&self) -> bool {
+        self.writers > 0
+    }
+
+    /// Get the index of the connection messages to the specified channel are
+    /// sent through. A split pool always sends a channel's messages through
+    /// the same write connection, so they arrive in the order they were sent
+    pub fn get_write_conn_idx(&self, channel_login: &str) -> Result<usize, PoolError> {
         let conn_idx = self
             .channels
-            .get(channel)
-            .ok_or(PoolError::ChannelNotFound(channel.into()))?
-            .ok_or(PoolError::NoConnectionAssigned(channel.into()))?;
+            .get(channel_login)
+            .ok_or(PoolError::ChannelNotFound(channel_login.into()))?
+            .ok_or(PoolError::NoConnectionAssigned(channel_login.into()))?;
+        if self.writers == 0 {
+            return Ok(conn_idx);
+        }
+        // FNV-1a, so a channel keeps its writer across builds and platforms
+        let hash = channel_login
+            .bytes()
+            .fold(0xcbf2_9ce4_8422_2325_u64, |hash, b| {
+                (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+            });
+        Ok((hash % self.writers as u64) as usize)
+    }
 
+    /// Send a `PRIVMSG` to the specified channel, through the connection that
+    /// is joined to it or a write connection of a split pool
+    pub async fn send_to_channel(&mut self, message: &str, channel: &str) -> Result<(), PoolError> {
+        let conn_idx = self.get_write_conn_idx(channel)?;
+        let (channel, _) = self
+            .channels
+            .get_key_value(channel)
+            .expect("write connection found for an unknown channel");
         let conn = self
             .pool
             .get_mut(conn_idx)
@@ -121,9 +390,17 @@ impl<A: AuthProvider + Clone> ConnectionPool<A> {
             .ok_or(PoolError::IndexOutOfBounds(index, pool_len))?
             .restart()
             .await?;
+        self.wake_waiting();
         Ok(())
     }
 
+    /// Wakes the task waiting to receive while every connection was closed
+    fn wake_waiting(&mut self) {
+        if let Some(waker) = self.waiting.take() {
+            waker.wake();
+        }
+    }
+
     /// Send an arbitrary IRC message to a connection specified by its index
     pub async fn send_to_connection(
         &mut self,
@@ -140,8 +417,18 @@ impl<A: AuthProvider + Clone> ConnectionPool<A> {
     }
 }
 
-impl<A: AuthProvider + Clone> Stream for ConnectionPool<A> {
-    type Item = Result<(IrcMessage, usize), PoolError>;
+/// Returns messages received by every connection along with the index of the
+/// connection, without the duplicates dropped by [Dedup] if it is
+/// [enabled](ConnectionPool::set_dedup). Chat is received by the read
+/// connections, write connections of a split pool only receive what is sent
+/// to them directly, like `PING`s and `USERSTATE`s, which still need to be
+/// handled. Connections that failed are skipped until they are
+/// [restarted](ConnectionPool::restart_connection).
+///
+/// The stream never ends, while every connection is closed it waits for one
+/// to be restarted
+impl<A: AuthProvider + Clone, C: ConnectionStorage> Stream for ConnectionPool<A, C> {
+    type Item = Result<(IrcMessage<C>, usize), PoolError>;
 
     fn poll_next(
         mut self: std::pin::Pin<&mut Self>,
@@ -151,19 +438,47 @@ impl<A: AuthProvider + Clone> Stream for ConnectionPool<A> {
             return Poll::Ready(Some(Err(PoolError::NoConnections)));
         }
 
-        if let Poll::Ready((received, idx, _futures)) =
-            select_all(self.pool.iter_mut().map(|c| Box::pin(c.receive()))).poll_unpin(cx)
-        {
-            let received = received.map_err(Into::<PoolError>::into);
-            Poll::Ready(Some(received.map(|r| (r, idx))))
-        } else {
-            Poll::Pending
+        let this = &mut *self;
+        'received: loop {
+            let mut open = false;
+            // connections are polled starting after the last one that
+            // returned a message, so a busy one can't starve the others
+            let len = this.pool.len();
+            for offset in 0..len {
+                let idx = (this.next_poll + offset) % len;
+                let conn = &mut this.pool[idx];
+                // closed connections have nothing to receive until they are restarted
+                if conn.state() == ConnectionState::Closed {
+                    continue;
+                }
+                open = true;
+                let Poll::Ready(received) = conn.poll_receive(cx) else {
+                    continue;
+                };
+                this.next_poll = idx + 1;
+                if let (Ok(msg), Some(dedup)) = (&received, this.dedup.as_mut())
+                    && !dedup.check(msg)
+                {
+                    counter!(metrics::DUPLICATES_DROPPED).increment(1);
+                    continue 'received;
+                }
+                if received.is_err() {
+                    counter!(metrics::RECEIVE_ERRORS).increment(1);
+                }
+                let received = received.map_err(|e| PoolError::ReceiveFailed(idx, e));
+                return Poll::Ready(Some(received.map(|r| (r, idx))));
+            }
+            if !open {
+                // woken once a connection is restarted
+                this.waiting = Some(cx.waker().clone());
+            }
+            return Poll::Pending;
         }
     }
 }
 
-impl<T: ToIrcMessage, A: AuthProvider + Clone> Sink<(Either<usize, &str>, T)>
-    for ConnectionPool<A>
+impl<T: ToIrcMessage, A: AuthProvider + Clone, C: ConnectionStorage> Sink<(Either<usize, &str>, T)>
+    for ConnectionPool<A, C>
 {
     type Error = PoolError;
 
@@ -173,7 +488,9 @@ impl<T: ToIrcMessage, A: AuthProvider + Clone> Sink<(Either<usize, &str>, T)>
     ) -> Poll<Result<(), Self::Error>> {
         let mut readied = 0;
         for i in self.pool.iter_mut() {
-            match futures_util::ready!(<Connection<A> as SinkExt<T>>::poll_ready_unpin(i, cx)) {
+            match futures_util::ready!(
+                <Connection<PoolAuth<A>, C> as SinkExt<T>>::poll_ready_unpin(i, cx)
+            ) {
                 Ok(()) => readied += 1,
                 Err(e) => return Poll::Ready(Err(e.into())),
             }
@@ -193,10 +510,7 @@ impl<T: ToIrcMessage, A: AuthProvider + Clone> Sink<(Either<usize, &str>, T)>
     ) -> Result<(), Self::Error> {
         let conn_idx = match target {
             Either::Left(idx) => idx,
-            Either::Right(chan) => match self.get_conn_idx(chan) {
-                Some(idx) => idx,
-                None => return Err(PoolError::ChannelNotFound(chan.to_string())),
-            },
+            Either::Right(chan) => self.get_write_conn_idx(chan)?,
         };
         let Some(conn) = self.pool.get_mut(conn_idx) else {
             return Err(PoolError::IndexOutOfBounds(conn_idx, self.pool.len()));
@@ -210,7 +524,9 @@ impl<T: ToIrcMessage, A: AuthProvider + Clone> Sink<(Either<usize, &str>, T)>
     ) -> Poll<Result<(), Self::Error>> {
         let mut flushed = 0;
         for i in self.pool.iter_mut() {
-            match futures_util::ready!(<Connection<A> as SinkExt<T>>::poll_flush_unpin(i, cx)) {
+            match futures_util::ready!(
+                <Connection<PoolAuth<A>, C> as SinkExt<T>>::poll_flush_unpin(i, cx)
+            ) {
                 Ok(()) => flushed += 1,
                 Err(e) => return Poll::Ready(Err(e.into())),
             }
@@ -230,7 +546,9 @@ impl<T: ToIrcMessage, A: AuthProvider + Clone> Sink<(Either<usize, &str>, T)>
     ) -> Poll<Result<(), Self::Error>> {
         let mut closed = 0;
         for i in self.pool.iter_mut() {
-            match futures_util::ready!(<Connection<A> as SinkExt<T>>::poll_close_unpin(i, cx)) {
+            match futures_util::ready!(
+                <Connection<PoolAuth<A>, C> as SinkExt<T>>::poll_close_unpin(i, cx)
+            ) {
                 Ok(()) => closed += 1,
                 Err(e) => return Poll::Ready(Err(e.into())),
             }
@@ -244,3 +562,238 @@ impl<T: ToIrcMessage, A: AuthProvider + Clone> Sink<(Either<usize, &str>, T)>
         }
     }
 }
+
+#[cfg(all(test, feature = "tokio-runtime"))]
+mod tests {
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicBool, Ordering},
+        },
+        task::Context,
+        time::Duration,
+    };
+
+    use futures_util::{StreamExt, task::ArcWake};
+
+    use super::ConnectionPool;
+    use crate::{
+        ChannelLogin, IrcCommand,
+        auth::OAuth,
+        connection::{ConnectionEvent, Dedup, LifecycleEvent},
+        testing::MockServer,
+    };
+
+    #[tokio::test]
+    async fn split_reads_and_writes() {
+        let server = MockServer::start().await.unwrap();
+        let auth = OAuth {
+            oauth: "token".into(),
+            nick: "bot".into(),
+        };
+        let foo = ChannelLogin::parse("foo").unwrap();
+        let mut pool: ConnectionPool<_> =
+            ConnectionPool::split_with_profile([foo.clone()], auth, 1, server.profile())
+                .await
+                .unwrap();
+        assert!(pool.is_split());
+
+        // the write connection starts first
+        let nick = |client| {
+            server.wait_for(move |m| {
+                m.client == client && m.message.get_command() == IrcCommand::Nick
+            })
+        };
+        assert_eq!(nick(0).await.message.get_param(0), Some("bot"));
+        assert!(
+            nick(1)
+                .await
+                .message
+                .get_param(0)
+                .unwrap()
+                .starts_with("justinfan")
+        );
+
+        // channels are joined by the read connection
+        pool.join_channel(ChannelLogin::parse("bar").unwrap())
+            .await
+            .unwrap();
+        assert_eq!(pool.get_conn_idx("foo"), Some(1));
+        assert_eq!(pool.get_conn_idx("bar"), Some(1));
+
+        pool.send_to_channel("hi", "foo").await.unwrap();
+        let privmsg = server.wait_for_command(IrcCommand::PrivMsg).await;
+        assert_eq!(privmsg.client, 0);
+        assert!(
+            server
+                .received()
+                .iter()
+                .all(|m| m.client == 1 || m.message.get_command() != IrcCommand::Join)
+        );
+
+        server.send_raw_to(1, ":baz!baz@baz.tmi.twitch.tv PRIVMSG #foo :hello");
+        loop {
+            let (msg, idx) = pool.next().await.unwrap().unwrap();
+            if msg.get_command() == IrcCommand::PrivMsg {
+                assert_eq!(idx, 1);
+                assert_eq!(msg.get_param(1), Some("hello"));
+                break;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn dedup_across_connections() {
+        let server = MockServer::start().await.unwrap();
+        let auth = OAuth {
+            oauth: "token".into(),
+            nick: "bot".into(),
+        };
+        let foo = ChannelLogin::parse("foo").unwrap();
+        let mut pool: ConnectionPool<_> =
+            ConnectionPool::split_with_profile([foo], auth, 1, server.profile())
+                .await
+                .unwrap();
+        pool.set_dedup(Some(Dedup::new()));
+        server.wait_for_command(IrcCommand::Join).await;
+
+        // both connections receive the same messages, then one that differs
+        for client in 0..2 {
+            for text in ["hello", "bye"] {
+                server.send_raw_to(
+                    client,
+                    &format!("@id={text} :baz!baz@baz.tmi.twitch.tv PRIVMSG #foo :{text}"),
+                );
+            }
+            server.send_raw_to(
+                client,
+                &format!(":baz!baz@baz.tmi.twitch.tv PRIVMSG #foo :end {client}"),
+            );
+        }
+        let mut received: Vec<String> = Vec::new();
+        while received.iter().filter(|t| t.starts_with("end")).count() < 2 {
+            let (msg, _) = pool.next().await.unwrap().unwrap();
+            if msg.get_command() == IrcCommand::PrivMsg {
+                received.push(msg.get_param(1).unwrap().to_owned());
+            }
+        }
+        received.sort();
+        assert_eq!(received, ["bye", "end 0", "end 1", "hello"]);
+        assert_eq!(pool.dedup().unwrap().dropped(), 2);
+    }
+
+    #[tokio::test]
+    async fn channels_pinned_to_writers() {
+        let server = MockServer::start().await.unwrap();
+        let auth = OAuth {
+            oauth: "token".into(),
+            nick: "bot".into(),
+        };
+        let channels: Vec<_> = (0..16)
+            .map(|i| ChannelLogin::parse(&format!("chan{i}")).unwrap())
+            .collect();
+        let pool: ConnectionPool<_> =
+            ConnectionPool::split_with_profile(channels.clone(), auth, 3, server.profile())
+                .await
+                .unwrap();
+
+        let writers: Vec<_> = channels
+            .iter()
+            .map(|c| pool.get_write_conn_idx(c).unwrap())
+            .collect();
+        for (channel, writer) in channels.iter().zip(&writers) {
+            assert!(*writer < 3);
+            // sending again goes through the same writer
+            for _ in 0..4 {
+                assert_eq!(pool.get_write_conn_idx(channel).unwrap(), *writer);
+            }
+        }
+        // channels are spread across the writers
+        assert!((0..3).all(|w| writers.contains(&w)), "{writers:?}");
+        // and every build picks the same ones
+        assert_eq!(writers[..4], [2, 1, 1, 0]);
+    }
+
+    #[tokio::test]
+    async fn keepalive() {
+        let server = MockServer::start().await.unwrap();
+        let auth = OAuth {
+            oauth: "token".into(),
+            nick: "bot".into(),
+        };
+        let mut pool: ConnectionPool<_> = ConnectionPool::with_profile(
+            [ChannelLogin::parse("foo").unwrap()],
+            auth,
+            server.profile(),
+        )
+        .await
+        .unwrap();
+        pool.set_keepalive(Some(Duration::from_millis(50)));
+        let mut events = pool.subscribe();
+
+        // nothing is received besides the answers to the keepalive PINGs
+        let measured = async {
+            loop {
+                tokio::select! {
+                    received = pool.next() => {
+                        received.unwrap().unwrap();
+                    }
+                    event = events.recv() => {
+                        if let LifecycleEvent {
+                            event: ConnectionEvent::LatencyMeasured(_),
+                            connection: 0,
+                        } = event.unwrap()
+                        {
+                            break;
+                        }
+                    }
+                }
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), measured)
+            .await
+            .unwrap();
+        server.wait_for_command(IrcCommand::Ping).await;
+    }
+
+    #[tokio::test]
+    async fn waits_for_restart() {
+        struct Woken(AtomicBool);
+
+        impl ArcWake for Woken {
+            fn wake_by_ref(arc_self: &Arc<Self>) {
+                arc_self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let server = MockServer::start().await.unwrap();
+        let auth = OAuth {
+            oauth: "token".into(),
+            nick: "bot".into(),
+        };
+        let mut pool: ConnectionPool<_> = ConnectionPool::with_profile(
+            [ChannelLogin::parse("foo").unwrap()],
+            auth,
+            server.profile(),
+        )
+        .await
+        .unwrap();
+        server.wait_for_command(IrcCommand::Join).await;
+        server.disconnect(0);
+        loop {
+            if pool.next().await.unwrap().is_err() {
+                break;
+            }
+        }
+
+        // every connection is closed
+        let woken = Arc::new(Woken(AtomicBool::new(false)));
+        let waker = futures_util::task::waker(Arc::clone(&woken));
+        let mut cx = Context::from_waker(&waker);
+        assert!(pool.poll_next_unpin(&mut cx).is_pending());
+        assert!(!woken.0.load(Ordering::SeqCst));
+
+        pool.restart_connection(0).await.unwrap();
+        assert!(woken.0.load(Ordering::SeqCst));
+    }
+}
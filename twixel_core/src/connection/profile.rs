@@ -0,0 +1,127 @@
+use std::time::Duration;
+
+/// Twitch's IRC over websockets endpoint
+pub const TWITCH_IRC_URL: &str = "wss://irc-ws.chat.twitch.tv:443";
+
+/// How long a [GenericServer] has to accept the registration by default
+pub const DEFAULT_REGISTRATION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What kind of IRC server a [Connection](super::Connection) talks to, which
+/// decides how it registers with it
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum ServerProfile {
+    /// Twitch IRC, authenticated with `PASS` and `NICK`, requesting Twitch's
+    /// capabilities and joining channels right away
+    #[default]
+    Twitch,
+    /// Twitch IRC served at another websocket URL, such as the mock server in
+    /// `twixel_core::testing`
+    TwitchAt(String),
+    /// Any other IRCv3 server that accepts IRC over websockets
+    Generic(GenericServer),
+}
+
+impl ServerProfile {
+    /// The websocket URL of the server
+    pub fn url(&self) -> &str {
+        match self {
+            ServerProfile::Twitch => TWITCH_IRC_URL,
+            ServerProfile::TwitchAt(url) => url,
+            ServerProfile::Generic(server) => &server.url,
+        }
+    }
+}
+
+impl From<GenericServer> for ServerProfile {
+    fn from(value: GenericServer) -> Self {
+        Self::Generic(value)
+    }
+}
+
+/// An IRCv3 server other than Twitch.
+///
+/// Registering with it requests [capabilities](GenericServer::capability),
+/// authenticates through SASL `PLAIN` if the
+/// [AuthProvider](crate::auth::AuthProvider) supports it, sends `PASS` if the
+/// password isn't empty, then `NICK` and `USER`, and waits for the server to
+/// accept the registration before joining channels
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenericServer {
+    url: String,
+    username: String,
+    realname: String,
+    capabilities: Vec<String>,
+    registration_timeout: Duration,
+}
+
+impl GenericServer {
+    /// Creates a new [GenericServer] at the websocket `url`, such as
+    /// `wss://irc.example.com:443`, that requests the `message-tags` and
+    /// `server-time` capabilities
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            username: "twixel".into(),
+            realname: "twixel".into(),
+            capabilities: vec!["message-tags".into(), "server-time".into()],
+            registration_timeout: DEFAULT_REGISTRATION_TIMEOUT,
+        }
+    }
+
+    /// Sets the username sent in `USER`, `twixel` by default
+    pub fn username(mut self, username: impl Into<String>) -> Self {
+        self.username = username.into();
+        self
+    }
+
+    /// Sets the real name sent in `USER`, `twixel` by default
+    pub fn realname(mut self, realname: impl Into<String>) -> Self {
+        self.realname = realname.into();
+        self
+    }
+
+    /// Adds a capability to request, all of them are requested at once so the
+    /// server rejects all of them if it doesn't support one
+    pub fn capability(mut self, capability: impl Into<String>) -> Self {
+        self.capabilities.push(capability.into());
+        self
+    }
+
+    /// Sets how long the server has to accept or reject the registration before
+    /// starting the connection fails, [DEFAULT_REGISTRATION_TIMEOUT] by default
+    pub fn registration_timeout(mut self, timeout: Duration) -> Self {
+        self.registration_timeout = timeout;
+        self
+    }
+
+    /// Removes every capability requested by default
+    pub fn without_capabilities(mut self) -> Self {
+        self.capabilities.clear();
+        self
+    }
+
+    /// The websocket URL of the server
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// The username sent in `USER`
+    pub fn get_username(&self) -> &str {
+        &self.username
+    }
+
+    /// The real name sent in `USER`
+    pub fn get_realname(&self) -> &str {
+        &self.realname
+    }
+
+    /// The capabilities that are requested
+    pub fn capabilities(&self) -> &[String] {
+        &self.capabilities
+    }
+
+    /// How long the server has to accept the registration
+    pub fn get_registration_timeout(&self) -> Duration {
+        self.registration_timeout
+    }
+}
@@ -0,0 +1,98 @@
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+
+use tokio::time::Instant;
+
+use crate::runtime::{self, Timer};
+
+/// Limits how many messages are sent within a sliding window of time, like
+/// Twitch does for PRIVMSGs.
+///
+/// See <https://dev.twitch.tv/docs/chat/#rate-limits>
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    max: usize,
+    window: Duration,
+    sent: VecDeque<Instant>,
+    timer: Arc<dyn Timer>,
+}
+
+impl RateLimiter {
+    /// Creates a [RateLimiter] that allows `max` messages every `window`
+    pub fn new(max: usize, window: Duration) -> Self {
+        let max = max.max(1);
+        Self {
+            max,
+            window,
+            sent: VecDeque::with_capacity(max),
+            timer: runtime::default_timer(),
+        }
+    }
+
+    /// Sets the timer [acquire](Self::acquire) waits with, the
+    /// [default timer](runtime::default_timer) otherwise
+    pub fn timer(mut self, timer: Arc<dyn Timer>) -> Self {
+        self.timer = timer;
+        self
+    }
+
+    /// The limit for regular users, 20 messages every 30 seconds
+    pub fn twitch_user() -> Self {
+        Self::new(20, Duration::from_secs(30))
+    }
+
+    /// The limit for the broadcaster and moderators, 100 messages every 30
+    /// seconds
+    pub fn twitch_moderator() -> Self {
+        Self::new(100, Duration::from_secs(30))
+    }
+
+    /// Records a message as sent if the limit allows it, otherwise returns how
+    /// long to wait until it does
+    pub fn try_acquire(&mut self) -> Result<(), Duration> {
+        let now = Instant::now();
+        while self
+            .sent
+            .front()
+            .is_some_and(|sent| now.duration_since(*sent) >= self.window)
+        {
+            self.sent.pop_front();
+        }
+
+        if self.sent.len() < self.max {
+            self.sent.push_back(now);
+            Ok(())
+        } else {
+            // the queue is full, so there is always an oldest message
+            let oldest = self.sent[0];
+            Err(self.window - now.duration_since(oldest))
+        }
+    }
+
+    /// Waits until the limit allows another message, then records it as sent
+    pub async fn acquire(&mut self) {
+        while let Err(wait) = self.try_acquire() {
+            self.timer.sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::RateLimiter;
+
+    #[test]
+    fn sliding_window() {
+        let mut limiter = RateLimiter::new(2, Duration::from_secs(60));
+        assert!(limiter.try_acquire().is_ok());
+        assert!(limiter.try_acquire().is_ok());
+
+        let wait = limiter.try_acquire().unwrap_err();
+        assert!(wait <= Duration::from_secs(60) && wait > Duration::from_secs(59));
+
+        let mut limiter = RateLimiter::new(1, Duration::ZERO);
+        assert!(limiter.try_acquire().is_ok());
+        assert!(limiter.try_acquire().is_ok());
+    }
+}
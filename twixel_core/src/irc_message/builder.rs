@@ -1,8 +1,10 @@
-use std::{borrow::Cow, fmt::Write};
+use std::{borrow::Cow, fmt::Write, ops::Deref};
 
-use hashbrown::HashMap;
-
-use crate::irc_message::{PrivMsg, tags::escape_tag_value};
+use crate::{
+    auth::is_public_authenticate,
+    irc_message::{PrivMsg, tags::escape_tag_value},
+    login::ChannelLogin,
+};
 
 use super::{ToIrcMessage, command::IrcCommand, prefix::OwnedPrefix, tags::OwnedTag};
 
@@ -13,7 +15,7 @@ pub struct MessageBuilder<'a> {
     #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_tags"))]
     #[cfg_attr(feature = "serde", serde(deserialize_with = "deserialize_tags"))]
     #[cfg_attr(feature = "serde", serde(borrow))]
-    tags: HashMap<OwnedTag, Cow<'a, str>>,
+    tags: Vec<(OwnedTag, Cow<'a, str>)>,
     prefix: Option<OwnedPrefix>,
     /// The IRCv3 command
     pub command: IrcCommand,
@@ -27,6 +29,27 @@ pub enum MessageBuilderError {
     /// The message used to create a [MessageBuilder] was missing a required tag
     #[error("could not create builder from message due to a missing tag")]
     MissingTag,
+    /// The message used to create a [MessageBuilder] had a missing or
+    /// malformed channel param
+    #[error("could not create builder from message due to a missing or malformed channel")]
+    InvalidChannel,
+    /// A param contained a CR, LF or NUL character, which could be used to
+    /// inject other IRC messages
+    #[error("param {0} contains a CR, LF or NUL character")]
+    ForbiddenCharacter(usize),
+    /// A param other than the last one was empty, started with a `:` or
+    /// contained a space
+    #[error("param {0} is not the last one, but is empty, starts with ':' or contains a space")]
+    InvalidMiddleParam(usize),
+    /// A tag key did not match the IRCv3 `[+][vendor/]key` grammar
+    #[error("invalid tag key {0:?}")]
+    InvalidTagKey(String),
+    /// A tag value contained an unescaped CR, LF, NUL, space or `;`
+    #[error("value of tag {0:?} contains an unescaped CR, LF, NUL, space or ';'")]
+    InvalidTagValue(String),
+    /// The prefix contained a CR, LF, NUL or space character
+    #[error("prefix contains a CR, LF, NUL or space character")]
+    InvalidPrefix,
 }
 
 impl std::fmt::Debug for MessageBuilder<'_> {
@@ -37,10 +60,17 @@ impl std::fmt::Debug for MessageBuilder<'_> {
             .field("command", &self.command)
             .field(
                 "params",
-                if self.command == IrcCommand::Pass {
-                    &"[TOKEN REDACTED]"
-                } else {
-                    &self.params
+                match self.command {
+                    IrcCommand::Pass => &"[TOKEN REDACTED]",
+                    IrcCommand::Authenticate
+                        if !self
+                            .params
+                            .first()
+                            .is_some_and(|p| is_public_authenticate(p)) =>
+                    {
+                        &"[CREDENTIALS REDACTED]"
+                    }
+                    _ => &self.params,
                 },
             )
             .finish()
@@ -51,7 +81,7 @@ impl<'a> MessageBuilder<'a> {
     /// Make a new [MessageBuilder] with the Given [IrcCommand]
     pub fn new(command: IrcCommand) -> Self {
         Self {
-            tags: HashMap::new(),
+            tags: Vec::new(),
             prefix: None,
             command,
             params: vec![],
@@ -67,24 +97,39 @@ impl<'a> MessageBuilder<'a> {
         else {
             return Err(MessageBuilderError::MissingTag);
         };
-        Ok(
-            Self::privmsg(msg.get_param(0).unwrap().split_at(1).1, message)
-                .add_tag(OwnedTag::ReplyParentMsgId, parent_id),
-        )
+        let Some(channel) = msg.channel_login() else {
+            return Err(MessageBuilderError::InvalidChannel);
+        };
+        Ok(Self::privmsg(&channel, message).add_tag(OwnedTag::ReplyParentMsgId, parent_id))
     }
 
     /// Build a finished IRC message in string form
-    pub fn build(self) -> String {
+    ///
+    /// Tags are written in the order they were first added. The last param is
+    /// only prefixed with a `:` when it needs to be
+    ///
+    /// # Errors
+    /// If any of the params contain CR, LF or NUL characters, if any param but
+    /// the last one is empty, starts with a `:` or contains a space, if a tag
+    /// key doesn't follow the IRCv3 `[+][vendor/]key` grammar, if a tag value
+    /// contains unescaped special characters, or if the prefix contains CR, LF,
+    /// NUL or space characters
+    pub fn build(&self) -> Result<String, MessageBuilderError> {
         let mut out = String::new();
 
         // tags
-        for (idx, tag) in self.tags.iter().enumerate() {
-            if idx == 0 {
-                write!(&mut out, "@").unwrap();
-            } else {
-                write!(&mut out, ";").unwrap();
+        for (idx, (tag, value)) in self.tags.iter().enumerate() {
+            let key = tag.to_string();
+            if !is_valid_tag_key(&key) {
+                return Err(MessageBuilderError::InvalidTagKey(key));
+            }
+            // values are escaped when added, so these can only come from a
+            // deserialized builder
+            if value.contains(['\r', '\n', '\0', ' ', ';']) {
+                return Err(MessageBuilderError::InvalidTagValue(key));
             }
-            write!(&mut out, "{}={}", tag.0, tag.1).unwrap()
+            out.push(if idx == 0 { '@' } else { ';' });
+            write!(&mut out, "{key}={value}").unwrap();
         }
 
         if !self.tags.is_empty() {
@@ -93,6 +138,10 @@ impl<'a> MessageBuilder<'a> {
 
         // prefix
         if let Some(prefix) = &self.prefix {
+            let prefix = prefix.to_string();
+            if prefix.len() < 2 || prefix.contains(['\r', '\n', '\0', ' ']) {
+                return Err(MessageBuilderError::InvalidPrefix);
+            }
             write!(&mut out, "{prefix} ").unwrap();
         }
 
@@ -100,20 +149,31 @@ impl<'a> MessageBuilder<'a> {
         write!(&mut out, "{}", self.command).unwrap();
 
         // params
-        if let [params @ .., last] = &self.params[..] {
-            for param in params {
-                write!(&mut out, " {param}",).unwrap();
+        for (idx, param) in self.params.iter().enumerate() {
+            if param.contains(['\r', '\n', '\0']) {
+                return Err(MessageBuilderError::ForbiddenCharacter(idx));
+            }
+            let needs_colon = param.is_empty() || param.starts_with(':') || param.contains(' ');
+            if idx + 1 < self.params.len() {
+                if needs_colon {
+                    return Err(MessageBuilderError::InvalidMiddleParam(idx));
+                }
+                write!(&mut out, " {param}").unwrap();
+            } else if needs_colon {
+                write!(&mut out, " :{param}").unwrap();
+            } else {
+                write!(&mut out, " {param}").unwrap();
             }
-            write!(&mut out, " :{last}").unwrap();
         }
 
         // CRLF EOL
         write!(&mut out, "\r\n").unwrap();
 
-        out
+        Ok(out)
     }
 
-    /// Add new tag-value pair
+    /// Add new tag-value pair, replacing the value of the tag if it was already
+    /// added
     pub fn add_tag(mut self, tag: OwnedTag, value: impl Into<Cow<'a, str>>) -> Self {
         let value = match value.into() {
             Cow::Borrowed(s) => escape_tag_value(s),
@@ -122,11 +182,16 @@ impl<'a> MessageBuilder<'a> {
                 Cow::Owned(s) => Cow::Owned(s),
             },
         };
-        self.tags.insert(tag, value);
+        match self.tags.iter_mut().find(|(t, _)| *t == tag) {
+            Some((_, old)) => *old = value,
+            None => self.tags.push((tag, value)),
+        }
         self
     }
 
-    /// Add new param
+    /// Add new param, only the last param may contain spaces or start with a
+    /// `:`, and no param may contain CR, LF or NUL characters, otherwise
+    /// [build](Self::build) fails
     pub fn add_param(mut self, param: impl Into<Cow<'a, str>>) -> Self {
         self.params.push(param.into());
         self
@@ -138,16 +203,12 @@ impl<'a> MessageBuilder<'a> {
         self
     }
 
-    /// Convenience method to make a new `PRIVMSG` message
-    pub fn privmsg(channel: &'a str, message: &str) -> Self {
-        let chan_param = if channel.starts_with('#') {
-            Cow::Borrowed(channel)
-        } else {
-            Cow::Owned(format!("#{channel}"))
-        };
+    /// Convenience method to make a new `PRIVMSG` message, any line breaks in
+    /// `message` are replaced by spaces and NUL characters are removed
+    pub fn privmsg<S: Deref<Target = str>>(channel: &ChannelLogin<S>, message: &str) -> Self {
         Self::new(IrcCommand::PrivMsg)
-            .add_param(chan_param)
-            .add_param(format!(":{message}"))
+            .add_param(channel.irc_param())
+            .add_param(strip_line_breaks(message).into_owned())
     }
 
     /// Convenience method to repond to data from a `PING`
@@ -158,7 +219,9 @@ impl<'a> MessageBuilder<'a> {
     }
 
     /// Convenience method to make a new `JOIN` message for many channels
-    pub fn join(channels: impl IntoIterator<Item = impl std::fmt::Display>) -> Self {
+    pub fn join<S: Deref<Target = str>>(
+        channels: impl IntoIterator<Item = ChannelLogin<S>>,
+    ) -> Self {
         let mut channel_list = String::new();
         for (idx, chan) in channels.into_iter().enumerate() {
             if idx > 0 {
@@ -170,7 +233,9 @@ impl<'a> MessageBuilder<'a> {
     }
 
     /// Convenience method to make a new `PART` message for many channels
-    pub fn part(channels: impl IntoIterator<Item = impl std::fmt::Display>) -> Self {
+    pub fn part<S: Deref<Target = str>>(
+        channels: impl IntoIterator<Item = ChannelLogin<S>>,
+    ) -> Self {
         let mut channel_list = String::new();
         for (idx, chan) in channels.into_iter().enumerate() {
             if idx > 0 {
@@ -185,7 +250,81 @@ impl<'a> MessageBuilder<'a> {
     pub fn cap_req() -> Self {
         Self::new(IrcCommand::Cap)
             .add_param("REQ")
-            .add_param(":twitch.tv/commands twitch.tv/tags")
+            .add_param("twitch.tv/commands twitch.tv/tags")
+    }
+
+    /// Convenience method to make a new `CAP REQ` message for any capabilities
+    pub fn cap_req_with(capabilities: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        let mut caps = String::new();
+        for (idx, cap) in capabilities.into_iter().enumerate() {
+            if idx > 0 {
+                caps.push(' ');
+            }
+            caps.push_str(cap.as_ref());
+        }
+        Self::new(IrcCommand::Cap).add_param("REQ").add_param(caps)
+    }
+
+    /// Convenience method to make a new `CAP END` message, which ends
+    /// capability negotiation
+    pub fn cap_end() -> Self {
+        Self::new(IrcCommand::Cap).add_param("END")
+    }
+
+    /// Convenience method to make a new `USER` message, used when registering
+    /// with IRC servers other than Twitch
+    pub fn user(username: &'a str, realname: &'a str) -> Self {
+        Self::new(IrcCommand::User)
+            .add_param(username)
+            .add_param("0")
+            .add_param("*")
+            .add_param(realname)
+    }
+
+    /// Convenience method to make a new `AUTHENTICATE` message, used for SASL
+    pub fn authenticate(data: &'a str) -> Self {
+        Self::new(IrcCommand::Authenticate).add_param(data)
+    }
+
+    /// Convenience method to make a new `MODE` message, `changes` are the mode
+    /// changes followed by their arguments, such as `["+o", "ferris"]`
+    pub fn mode(target: &'a str, changes: impl IntoIterator<Item = &'a str>) -> Self {
+        changes.into_iter().fold(
+            Self::new(IrcCommand::Mode).add_param(target),
+            Self::add_param,
+        )
+    }
+
+    /// Convenience method to make a new `TOPIC` message that sets a channel's
+    /// topic
+    pub fn topic<S: Deref<Target = str>>(channel: &ChannelLogin<S>, topic: &'a str) -> Self {
+        Self::new(IrcCommand::Topic)
+            .add_param(channel.irc_param())
+            .add_param(topic)
+    }
+
+    /// Convenience method to make a new `KICK` message
+    pub fn kick<S: Deref<Target = str>>(
+        channel: &ChannelLogin<S>,
+        nick: &'a str,
+        reason: Option<&'a str>,
+    ) -> Self {
+        let msg = Self::new(IrcCommand::Kick)
+            .add_param(channel.irc_param())
+            .add_param(nick);
+        match reason {
+            Some(reason) => msg.add_param(reason),
+            None => msg,
+        }
+    }
+
+    /// Convenience method to make a new `QUIT` message
+    pub fn quit(reason: Option<&'a str>) -> Self {
+        let msg = Self::new(IrcCommand::Quit);
+        match reason {
+            Some(reason) => msg.add_param(reason),
+            None => msg,
+        }
     }
 
     /// Convert from a [MessageBuilder] using borrowed data to using owned data
@@ -211,35 +350,67 @@ impl ToIrcMessage for MessageBuilder<'_> {
         self.command
     }
 
-    fn to_message(self) -> String {
+    fn to_message(self) -> Result<String, MessageBuilderError> {
         self.build()
     }
 }
 
+/// Checks `key` against the IRCv3 tag key grammar, `[+][vendor/]key`, where
+/// `key` is made of ASCII letters, digits and `-`, and `vendor` is a hostname
+fn is_valid_tag_key(key: &str) -> bool {
+    let key = key.strip_prefix('+').unwrap_or(key);
+    let (vendor, name) = match key.rsplit_once('/') {
+        Some((vendor, name)) => (Some(vendor), name),
+        None => (None, key),
+    };
+    let vendor_ok = vendor.is_none_or(|v| {
+        !v.is_empty()
+            && v.bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'.')
+    });
+    vendor_ok && !name.is_empty() && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+}
+
+/// Replaces CR and LF with spaces and removes NUL characters, so that user
+/// provided text can't end the message early
+fn strip_line_breaks(text: &str) -> Cow<'_, str> {
+    if !text.contains(['\r', '\n', '\0']) {
+        return Cow::Borrowed(text);
+    }
+    Cow::Owned(
+        text.replace("\r\n", " ")
+            .replace(['\r', '\n'], " ")
+            .replace('\0', ""),
+    )
+}
+
 #[cfg(feature = "serde")]
 fn serialize_tags<S: serde::Serializer>(
-    value: &HashMap<OwnedTag, Cow<'_, str>>,
+    value: &[(OwnedTag, Cow<'_, str>)],
     ser: S,
 ) -> Result<S::Ok, S::Error> {
-    ser.collect_map(value.iter())
+    ser.collect_map(value.iter().map(|(t, v)| (t, v)))
 }
 
 #[cfg(feature = "serde")]
 fn deserialize_tags<'de, D: serde::Deserializer<'de>>(
     deser: D,
-) -> Result<HashMap<OwnedTag, Cow<'de, str>>, D::Error> {
+) -> Result<Vec<(OwnedTag, Cow<'de, str>)>, D::Error> {
     struct MapVisitor;
     impl<'v> serde::de::Visitor<'v> for MapVisitor {
-        type Value = HashMap<OwnedTag, Cow<'v, str>>;
+        type Value = Vec<(OwnedTag, Cow<'v, str>)>;
 
         fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
         where
             A: serde::de::MapAccess<'v>,
         {
-            let mut tags = HashMap::new();
+            let mut tags: Vec<(OwnedTag, Cow<'v, str>)> = Vec::new();
 
             while let Some((key, value)) = map.next_entry()? {
-                tags.insert(key, value);
+                match tags.iter_mut().find(|(t, _)| *t == key) {
+                    Some((_, old)) => *old = value,
+                    None => tags.push((key, value)),
+                }
             }
 
             Ok(tags)
@@ -271,9 +442,194 @@ fn message_builder() {
         })
         .add_param("#juliapixel")
         .add_param("hi hello there!")
-        .build();
+        .build()
+        .unwrap();
 
     let built_parsed: IrcMessage = built.parse().unwrap();
 
     assert_eq!(built_parsed, test_parsed);
 }
+
+#[test]
+fn deterministic_tag_order() {
+    let built = MessageBuilder::privmsg(&ChannelLogin::new("juliapixel").unwrap(), "hi")
+        .add_tag(OwnedTag::Id, "1")
+        .add_tag(OwnedTag::Color, "#ffffff")
+        .add_tag(OwnedTag::Unknown("+example.com/a-b".into()), "x y;z")
+        .add_tag(OwnedTag::Id, "2")
+        .build()
+        .unwrap();
+
+    assert_eq!(
+        built,
+        "@id=2;color=#ffffff;+example.com/a-b=x\\sy\\:z PRIVMSG #juliapixel hi\r\n"
+    );
+}
+
+#[test]
+fn tag_key_validation() {
+    for key in [
+        "a=b",
+        "a;b c",
+        "",
+        "+",
+        "vendor/",
+        "/key",
+        "key_name",
+        "a\r\nPART #x",
+    ] {
+        assert!(
+            matches!(
+                MessageBuilder::new(IrcCommand::PrivMsg)
+                    .add_tag(OwnedTag::Unknown(key.into()), "c")
+                    .build(),
+                Err(MessageBuilderError::InvalidTagKey(_))
+            ),
+            "{key:?} should be rejected"
+        );
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn deserialized_injection() {
+    let builder: MessageBuilder = serde_json::from_str(
+        r##"{"tags":{"id":"1 PART #x"},"prefix":null,"command":"PRIVMSG","params":["#x","hi"]}"##,
+    )
+    .unwrap();
+    assert!(matches!(
+        builder.build(),
+        Err(MessageBuilderError::InvalidTagValue(_))
+    ));
+}
+
+#[test]
+fn prefix_validation() {
+    let builder = MessageBuilder::new(IrcCommand::PrivMsg)
+        .prefix(OwnedPrefix::OnlyHostname {
+            host: "host\r\nPART #x".into(),
+        })
+        .add_param("#x")
+        .add_param("hi");
+    assert!(matches!(
+        builder.build(),
+        Err(MessageBuilderError::InvalidPrefix)
+    ));
+
+    let builder = MessageBuilder::new(IrcCommand::PrivMsg)
+        .prefix(OwnedPrefix::OnlyHostname {
+            host: "evil.host PART".into(),
+        })
+        .add_param("#x")
+        .add_param("hi");
+    assert!(matches!(
+        builder.build(),
+        Err(MessageBuilderError::InvalidPrefix)
+    ));
+}
+
+#[test]
+fn param_validation() {
+    let trailing = MessageBuilder::new(IrcCommand::PrivMsg)
+        .add_param("#room")
+        .add_param(":) hi")
+        .build()
+        .unwrap();
+    assert_eq!(trailing, "PRIVMSG #room ::) hi\r\n");
+
+    let empty = MessageBuilder::new(IrcCommand::PrivMsg)
+        .add_param("#room")
+        .add_param("")
+        .build()
+        .unwrap();
+    assert_eq!(empty, "PRIVMSG #room :\r\n");
+
+    assert!(matches!(
+        MessageBuilder::new(IrcCommand::PrivMsg)
+            .add_param("#room other")
+            .add_param("hi")
+            .build(),
+        Err(MessageBuilderError::InvalidMiddleParam(0))
+    ));
+
+    assert!(matches!(
+        MessageBuilder::new(IrcCommand::PrivMsg)
+            .add_param("#room")
+            .add_param("hi\r\nPRIVMSG #other :injected")
+            .build(),
+        Err(MessageBuilderError::ForbiddenCharacter(1))
+    ));
+}
+
+#[test]
+fn privmsg_injection() {
+    use crate::IrcMessage;
+
+    let built = MessageBuilder::privmsg(&ChannelLogin::new("room").unwrap(), "hi\r\nPART #room\0")
+        .build()
+        .unwrap();
+    assert_eq!(built, "PRIVMSG #room :hi PART #room\r\n");
+
+    let msgs: Vec<IrcMessage> = IrcMessage::new_multiline(&built)
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(msgs.len(), 1);
+    assert_eq!(msgs[0].get_param(1), Some("hi PART #room"));
+}
+
+#[test]
+fn join_channels() {
+    let channels = ["#Foo", "bar"].map(|c| ChannelLogin::parse(c).unwrap());
+    let built = MessageBuilder::join(channels.iter().map(ChannelLogin::borrowed))
+        .build()
+        .unwrap();
+    assert_eq!(built, "JOIN #foo,#bar\r\n");
+
+    let built = MessageBuilder::part(channels).build().unwrap();
+    assert_eq!(built, "PART #foo,#bar\r\n");
+}
+
+#[test]
+fn standard_commands() {
+    let build = |b: MessageBuilder| b.build().unwrap();
+
+    assert_eq!(
+        build(MessageBuilder::user("twixel", "twixel bot")),
+        "USER twixel 0 * :twixel bot\r\n"
+    );
+    assert_eq!(
+        build(MessageBuilder::cap_req_with(["sasl", "message-tags"])),
+        "CAP REQ :sasl message-tags\r\n"
+    );
+    assert_eq!(build(MessageBuilder::cap_end()), "CAP END\r\n");
+    assert_eq!(
+        build(MessageBuilder::mode("#rust", ["+o", "ferris"])),
+        "MODE #rust +o ferris\r\n"
+    );
+
+    let chan = ChannelLogin::parse_irc("#rust").unwrap();
+    assert_eq!(
+        build(MessageBuilder::topic(&chan, "crabs only")),
+        "TOPIC #rust :crabs only\r\n"
+    );
+    assert_eq!(
+        build(MessageBuilder::kick(&chan, "ferris", Some("no crabs"))),
+        "KICK #rust ferris :no crabs\r\n"
+    );
+    assert_eq!(build(MessageBuilder::quit(None)), "QUIT\r\n");
+}
+
+#[test]
+fn debug_redacts_credentials() {
+    let pass = format!(
+        "{:?}",
+        MessageBuilder::new(IrcCommand::Pass).add_param("oauth:hunter2")
+    );
+    assert!(!pass.contains("hunter2"), "{pass}");
+
+    let auth = format!("{:?}", MessageBuilder::authenticate("AGppbGxlcwBzZXNhbWU="));
+    assert!(!auth.contains("AGppbGxlcwBzZXNhbWU="), "{auth}");
+
+    let plain = format!("{:?}", MessageBuilder::authenticate("PLAIN"));
+    assert!(plain.contains("PLAIN"), "{plain}");
+}
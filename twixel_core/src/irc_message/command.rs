@@ -1,7 +1,12 @@
+use std::{fmt::Display, ops::Deref};
+
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+/// Maximum length in bytes of the name of an [IrcCommand::Other]
+pub const MAX_OTHER_COMMAND_LEN: usize = 31;
+
 macro_rules! commands {
     (
         $name:ident, $error:ident,
@@ -13,19 +18,25 @@ macro_rules! commands {
         ]
         $($key:literal = $val:ident),+
     ) => {
-        /// All of Twitch's supported IRC commands
-        #[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(into = "&'static str", try_from = "&str"))]
+        /// All of Twitch's supported IRC commands and the common standard
+        /// ones, along with any other valid IRC command that isn't known
+        #[cfg_attr(feature = "serde", derive(Deserialize), serde(try_from = "&str"))]
         #[derive(Debug, PartialEq, Eq, Clone, Copy)]
         pub enum $name {
             $(
                 $(#[$comment])*
                 $var,
             )*
+            /// A numeric reply that isn't known, such as the ones sent during
+            /// Twitch's MOTD messages
+            Numeric(u16),
+            /// A command that isn't known
+            Other(OtherCommand),
         }
 
-        /// An unidentified IRC command was received
+        /// An invalid IRC command was received
         #[derive(Debug, Clone, PartialEq, Eq, Error)]
-        #[error("the IRC command \"{0}\" was not identified!")]
+        #[error("the IRC command \"{0}\" is not valid!")]
         pub struct $error(String);
 
         impl TryFrom<&str> for $name {
@@ -34,23 +45,47 @@ macro_rules! commands {
             fn try_from(val: &str) -> Result<Self, $error> {
                 match val {
                     $($key => Ok(Self::$val),)*
-                    _ => Err($error(String::from(val)))
+                    _ => {
+                        if let [a @ b'0'..=b'9', b @ b'0'..=b'9', c @ b'0'..=b'9'] = val.as_bytes() {
+                            Ok(Self::Numeric(
+                                (a - b'0') as u16 * 100 + (b - b'0') as u16 * 10 + (c - b'0') as u16,
+                            ))
+                        } else {
+                            OtherCommand::new(val).map(Self::Other)
+                        }
+                    }
                 }
             }
         }
 
-        #[allow(unreachable_patterns)]
+        impl $name {
+            /// Name of a known command, `None` for [Numeric](Self::Numeric) and
+            /// [Other](Self::Other) commands
+            #[allow(unreachable_patterns)]
+            pub fn known_name(&self) -> Option<&'static str> {
+                match self {
+                    $($name::$val => Some($key),)*
+                    $name::Numeric(_) | $name::Other(_) => None,
+                }
+            }
+        }
+
+        /// Name of a known command. [Numeric](IrcCommand::Numeric) and
+        /// [Other](IrcCommand::Other) commands have no static name and become
+        /// an empty string, their [Display] impl writes their name
         impl From<$name> for &str {
             fn from(val: $name) -> &'static str {
-                match val {
-                    $($name::$val => $key,)*
-                }
+                val.known_name().unwrap_or_default()
             }
         }
 
-        impl std::fmt::Display for $name {
+        impl Display for $name {
             fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-                write!(f, "{}", Into::<&str>::into(*self))
+                match self {
+                    $name::Numeric(n) => write!(f, "{n:03}"),
+                    $name::Other(o) => f.write_str(o),
+                    known => f.write_str(known.known_name().unwrap_or_default()),
+                }
             }
         }
     };
@@ -95,14 +130,24 @@ commands! {
         Reconnect,
         /// The IRC `WHISPER` command
         Whisper,
+        /// The IRC `USER` command
+        User,
+        /// The IRC `MODE` command
+        Mode,
+        /// The IRC `TOPIC` command
+        Topic,
+        /// The IRC `KICK` command
+        Kick,
+        /// The IRC `QUIT` command
+        Quit,
+        /// The IRC `AUTHENTICATE` command, used for SASL
+        Authenticate,
         /// The IRC `421` command
         UnsupportedError,
         /// The IRC `353` and `366` commands
         UserList,
         /// The IRC `001` command
-        AuthSuccessful,
-        /// Many different IRC commands that are sent during twitch's MOTD messages
-        Useless
+        AuthSuccessful
     ]
     "PASS" = Pass,
     "NICK" = Nick,
@@ -122,14 +167,240 @@ commands! {
     "USERNOTICE" = UserNotice,
     "RECONNECT" = Reconnect,
     "WHISPER" = Whisper,
+    "USER" = User,
+    "MODE" = Mode,
+    "TOPIC" = Topic,
+    "KICK" = Kick,
+    "QUIT" = Quit,
+    "AUTHENTICATE" = Authenticate,
     "421" = UnsupportedError,
     "353" = UserList,
     "366" = UserList,
-    "001" = AuthSuccessful,
-    "002" = Useless,
-    "003" = Useless,
-    "004" = Useless,
-    "375" = Useless,
-    "372" = Useless,
-    "376" = Useless
+    "001" = AuthSuccessful
+}
+
+/// Names of common numeric replies, as defined by
+/// [Modern IRC](https://modern.ircdocs.horse/#numerics)
+pub mod numeric {
+    use super::IrcCommand;
+
+    /// The server accepted the registration
+    pub const RPL_WELCOME: IrcCommand = IrcCommand::AuthSuccessful;
+
+    /// Name and version of the server
+    pub const RPL_YOURHOST: IrcCommand = IrcCommand::Numeric(2);
+
+    /// When the server was created
+    pub const RPL_CREATED: IrcCommand = IrcCommand::Numeric(3);
+
+    /// Supported user and channel modes
+    pub const RPL_MYINFO: IrcCommand = IrcCommand::Numeric(4);
+
+    /// Features supported by the server
+    pub const RPL_ISUPPORT: IrcCommand = IrcCommand::Numeric(5);
+
+    /// The client's user modes
+    pub const RPL_UMODEIS: IrcCommand = IrcCommand::Numeric(221);
+
+    /// A channel has no topic
+    pub const RPL_NOTOPIC: IrcCommand = IrcCommand::Numeric(331);
+
+    /// The topic of a channel
+    pub const RPL_TOPIC: IrcCommand = IrcCommand::Numeric(332);
+
+    /// A list of users in a channel
+    pub const RPL_NAMREPLY: IrcCommand = IrcCommand::UserList;
+
+    /// The end of a list of users in a channel
+    pub const RPL_ENDOFNAMES: IrcCommand = IrcCommand::UserList;
+
+    /// A line of the message of the day
+    pub const RPL_MOTD: IrcCommand = IrcCommand::Numeric(372);
+
+    /// The start of the message of the day
+    pub const RPL_MOTDSTART: IrcCommand = IrcCommand::Numeric(375);
+
+    /// The end of the message of the day
+    pub const RPL_ENDOFMOTD: IrcCommand = IrcCommand::Numeric(376);
+
+    /// No user with the given nickname exists
+    pub const ERR_NOSUCHNICK: IrcCommand = IrcCommand::Numeric(401);
+
+    /// No channel with the given name exists
+    pub const ERR_NOSUCHCHANNEL: IrcCommand = IrcCommand::Numeric(403);
+
+    /// A message couldn't be sent to a channel
+    pub const ERR_CANNOTSENDTOCHAN: IrcCommand = IrcCommand::Numeric(404);
+
+    /// The server doesn't know a command
+    pub const ERR_UNKNOWNCOMMAND: IrcCommand = IrcCommand::UnsupportedError;
+
+    /// The server has no message of the day
+    pub const ERR_NOMOTD: IrcCommand = IrcCommand::Numeric(422);
+
+    /// A nickname contains invalid characters
+    pub const ERR_ERRONEUSNICKNAME: IrcCommand = IrcCommand::Numeric(432);
+
+    /// A nickname is already in use
+    pub const ERR_NICKNAMEINUSE: IrcCommand = IrcCommand::Numeric(433);
+
+    /// A command was sent before registering
+    pub const ERR_NOTREGISTERED: IrcCommand = IrcCommand::Numeric(451);
+
+    /// A command is missing params
+    pub const ERR_NEEDMOREPARAMS: IrcCommand = IrcCommand::Numeric(461);
+
+    /// The password was wrong or missing
+    pub const ERR_PASSWDMISMATCH: IrcCommand = IrcCommand::Numeric(464);
+
+    /// The client is banned from the server
+    pub const ERR_YOUREBANNEDCREEP: IrcCommand = IrcCommand::Numeric(465);
+
+    /// A channel is full
+    pub const ERR_CHANNELISFULL: IrcCommand = IrcCommand::Numeric(471);
+
+    /// A channel requires an invite
+    pub const ERR_INVITEONLYCHAN: IrcCommand = IrcCommand::Numeric(473);
+
+    /// The client is banned from a channel
+    pub const ERR_BANNEDFROMCHAN: IrcCommand = IrcCommand::Numeric(474);
+
+    /// The key of a channel was wrong or missing
+    pub const ERR_BADCHANNELKEY: IrcCommand = IrcCommand::Numeric(475);
+
+    /// The client isn't a channel operator
+    pub const ERR_CHANOPRIVSNEEDED: IrcCommand = IrcCommand::Numeric(482);
+
+    /// The client logged into an account
+    pub const RPL_LOGGEDIN: IrcCommand = IrcCommand::Numeric(900);
+
+    /// The client logged out of its account
+    pub const RPL_LOGGEDOUT: IrcCommand = IrcCommand::Numeric(901);
+
+    /// SASL failed because the account is locked
+    pub const ERR_NICKLOCKED: IrcCommand = IrcCommand::Numeric(902);
+
+    /// SASL authentication succeeded
+    pub const RPL_SASLSUCCESS: IrcCommand = IrcCommand::Numeric(903);
+
+    /// SASL authentication failed
+    pub const ERR_SASLFAIL: IrcCommand = IrcCommand::Numeric(904);
+
+    /// A SASL message was too long
+    pub const ERR_SASLTOOLONG: IrcCommand = IrcCommand::Numeric(905);
+
+    /// SASL authentication was aborted
+    pub const ERR_SASLABORTED: IrcCommand = IrcCommand::Numeric(906);
+
+    /// The client already authenticated with SASL
+    pub const ERR_SASLALREADY: IrcCommand = IrcCommand::Numeric(907);
+
+    /// SASL mechanisms supported by the server
+    pub const RPL_SASLMECHS: IrcCommand = IrcCommand::Numeric(908);
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for IrcCommand {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+/// Name of an IRC command unknown to [IrcCommand], stored inline so that
+/// [IrcCommand] can stay [Copy]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OtherCommand {
+    len: u8,
+    buf: [u8; MAX_OTHER_COMMAND_LEN],
+}
+
+impl OtherCommand {
+    /// Creates a new [OtherCommand], which must be made of only ASCII letters
+    /// and be at most [MAX_OTHER_COMMAND_LEN] bytes long
+    pub fn new(name: &str) -> Result<Self, IrcCommandError> {
+        if name.is_empty()
+            || name.len() > MAX_OTHER_COMMAND_LEN
+            || !name.bytes().all(|b| b.is_ascii_alphabetic())
+        {
+            return Err(IrcCommandError(String::from(name)));
+        }
+
+        let mut buf = [0; MAX_OTHER_COMMAND_LEN];
+        buf[..name.len()].copy_from_slice(name.as_bytes());
+        Ok(Self {
+            len: name.len() as u8,
+            buf,
+        })
+    }
+
+    /// The command's name
+    pub fn as_str(&self) -> &str {
+        // only ever constructed from ASCII letters
+        std::str::from_utf8(&self.buf[..self.len as usize]).unwrap_or_default()
+    }
+}
+
+impl Deref for OtherCommand {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        self.as_str()
+    }
+}
+
+impl std::fmt::Debug for OtherCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl Display for OtherCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IrcCommand, OtherCommand, numeric};
+
+    #[test]
+    fn known_commands() {
+        assert_eq!(IrcCommand::try_from("PRIVMSG"), Ok(IrcCommand::PrivMsg));
+        assert_eq!(IrcCommand::try_from("001"), Ok(IrcCommand::AuthSuccessful));
+        assert_eq!(IrcCommand::try_from("366"), Ok(IrcCommand::UserList));
+        assert_eq!(IrcCommand::PrivMsg.to_string(), "PRIVMSG");
+        assert_eq!(IrcCommand::try_from("KICK"), Ok(IrcCommand::Kick));
+        assert_eq!(
+            IrcCommand::try_from("AUTHENTICATE"),
+            Ok(IrcCommand::Authenticate)
+        );
+    }
+
+    #[test]
+    fn numeric_commands() {
+        assert_eq!(IrcCommand::try_from("002"), Ok(IrcCommand::Numeric(2)));
+        assert_eq!(IrcCommand::try_from("376"), Ok(IrcCommand::Numeric(376)));
+        assert_eq!(IrcCommand::Numeric(2).to_string(), "002");
+        assert_ne!(IrcCommand::Numeric(372), IrcCommand::Numeric(376));
+        assert_eq!(IrcCommand::try_from("433"), Ok(numeric::ERR_NICKNAMEINUSE));
+    }
+
+    #[test]
+    fn other_commands() {
+        let cmd = IrcCommand::try_from("FOOBAR").unwrap();
+        assert_eq!(cmd, IrcCommand::Other(OtherCommand::new("FOOBAR").unwrap()));
+        assert_eq!(cmd.to_string(), "FOOBAR");
+        assert_eq!(cmd.known_name(), None);
+        assert_eq!(<&str>::from(cmd), "");
+        assert_eq!(<&str>::from(IrcCommand::PrivMsg), "PRIVMSG");
+
+        assert!(IrcCommand::try_from("").is_err());
+        assert!(IrcCommand::try_from("12").is_err());
+        assert!(IrcCommand::try_from("FOO BAR").is_err());
+        assert!(IrcCommand::try_from("A".repeat(32).as_str()).is_err());
+    }
 }
@@ -4,7 +4,7 @@ use memchr::memchr;
 
 use crate::IrcMessage;
 
-use super::error::IrcMessageParseError;
+use super::{error::IrcMessageParseError, storage::MessageStorage};
 
 /// Iterator over many IRC messages in a single string, separated by CRLF sequences
 pub struct IrcMessageParseIter<'a, C> {
@@ -36,6 +36,35 @@ impl<'a, C: for<'b> From<&'b str> + Deref<Target = str>> Iterator for IrcMessage
     }
 }
 
+/// Iterator over many IRC messages in a single buffer, separated by CRLF
+/// sequences, where each message shares the buffer if its [MessageStorage]
+/// allows it
+pub struct IrcMessageSplitIter<C> {
+    pos: usize,
+    inner: C,
+}
+
+impl<C> IrcMessageSplitIter<C> {
+    /// Create a new [IrcMessageSplitIter]
+    pub(crate) fn new(buffer: C) -> Self {
+        Self {
+            inner: buffer,
+            pos: 0,
+        }
+    }
+}
+
+impl<C: MessageStorage> Iterator for IrcMessageSplitIter<C> {
+    type Item = Result<IrcMessage<C>, IrcMessageParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = memchr(b'\n', &self.inner.as_bytes()[self.pos..])?;
+        let line = self.inner.slice((self.pos..self.pos + next + 1).into());
+        self.pos += next + 1;
+        Some(IrcMessage::new(line))
+    }
+}
+
 /// Iterate over a user's badges
 pub struct BadgeIter<'a> {
     src: &'a str,
@@ -61,13 +90,10 @@ impl<'a> Iterator for BadgeIter<'a> {
             None => cur_slice.len(),
         };
         let single_badge = &cur_slice[..boundary];
-        let slash_pos =
-            memchr::memchr(b'/', single_badge.as_bytes()).expect("badge did not containt a \"/\"");
         self.pos += boundary + 1;
 
-        single_badge
-            .split_at_checked(slash_pos)
-            .map(|(k, v)| (k, v.split_at(1).1))
+        // badges without a version are yielded with an empty one
+        Some(single_badge.split_once('/').unwrap_or((single_badge, "")))
     }
 }
 
@@ -7,23 +7,40 @@ use std::{
     str::FromStr,
 };
 
+#[cfg(feature = "connection")]
+use async_tungstenite::tungstenite::Message as WsMessage;
 #[cfg(feature = "serde")]
 use serde::{
-    Serialize,
+    Deserialize, Serialize,
+    de::{
+        MapAccess, SeqAccess, Visitor, value::MapAccessDeserializer, value::SeqAccessDeserializer,
+    },
     ser::{SerializeStruct, SerializeStructVariant},
 };
 use smallvec::SmallVec;
-#[cfg(feature = "connection")]
-use tokio_tungstenite::tungstenite::Message as WsMessage;
 
+#[cfg(feature = "connection")]
+use crate::irc_message::storage::BytesStr;
 use crate::irc_message::{
-    error::IrcMessageStructureError, iter::IrcMessageParseIter, prefix::RawPrefix, tags::RawIrcTags,
+    builder::MessageBuilderError,
+    error::IrcMessageStructureError,
+    iter::{IrcMessageParseIter, IrcMessageSplitIter},
+    prefix::RawPrefix,
+    storage::MessageStorage,
+    tags::RawIrcTags,
 };
 
 use super::{ToIrcMessage, command::IrcCommand, error::IrcMessageParseError, tags::OwnedTag};
+use crate::{auth::is_public_authenticate, user::Badge};
 
 type ParamVec = SmallVec<[Range<usize>; 3]>;
-type MessageParts = (Option<RawIrcTags>, Option<RawPrefix>, IrcCommand, ParamVec);
+type MessageParts = (
+    Option<RawIrcTags>,
+    Option<RawPrefix>,
+    IrcCommand,
+    Range<usize>,
+    ParamVec,
+);
 
 /// An IRCv3 Message
 #[derive(Debug, Clone)]
@@ -32,19 +49,21 @@ pub struct IrcMessage<C: Deref<Target = str> = String> {
     tags: Option<RawIrcTags>,
     prefix: Option<RawPrefix>,
     command: IrcCommand,
+    command_range: Range<usize>,
     params: ParamVec,
 }
 
 impl<C: Deref<Target = str>> IrcMessage<C> {
     /// Parses an IRCv3 message into this struct
     pub fn new(value: impl Into<C> + Deref<Target = str>) -> Result<Self, IrcMessageParseError> {
-        let (tags, prefix, command, params) = Self::get_parts(&value)?;
+        let (tags, prefix, command, command_range, params) = Self::get_parts(&value)?;
 
         Ok(Self {
             raw: value.into(),
             tags,
             prefix,
             command,
+            command_range,
             params,
         })
     }
@@ -55,6 +74,30 @@ impl<C: Deref<Target = str>> IrcMessage<C> {
         IrcMessageParseIter::new(value)
     }
 
+    /// Parses multiple IRCv3 messages from one multiline buffer, where each line
+    /// is a message. Unlike [IrcMessage::new_multiline], each message is sliced
+    /// out of `buffer` itself, so storages like `&str` or
+    /// [BytesStr](super::storage::BytesStr) don't copy anything
+    pub fn split_multiline(buffer: C) -> IrcMessageSplitIter<C>
+    where
+        C: MessageStorage,
+    {
+        IrcMessageSplitIter::new(buffer)
+    }
+
+    /// Converts the backing storage of this message without parsing it again,
+    /// e.g. from `IrcMessage<&str>` to `IrcMessage<String>`
+    pub fn into_storage<D: From<C> + Deref<Target = str>>(self) -> IrcMessage<D> {
+        IrcMessage {
+            raw: self.raw.into(),
+            tags: self.tags,
+            prefix: self.prefix,
+            command: self.command,
+            command_range: self.command_range,
+            params: self.params,
+        }
+    }
+
     fn get_parts(value: &str) -> Result<MessageParts, IrcMessageParseError> {
         use IrcMessageParseError as E;
 
@@ -87,23 +130,30 @@ impl<C: Deref<Target = str>> IrcMessage<C> {
         };
 
         // splits the command from its parameters (if present)
-        let cmd = match memchr::memchr2(b' ', b'\r', raw[pos..].as_bytes()) {
-            Some(s) => {
-                let cmd = &raw[pos..pos + s];
-                pos = pos + s + 1;
-                cmd
-            }
-            None => {
-                let cmd = &raw[pos..raw.len()];
-                if cmd.is_empty() {
-                    return Err(E::NoCommand);
+        let command_range: Range<usize> =
+            match memchr::memchr3(b' ', b'\r', b'\n', raw[pos..].as_bytes()) {
+                Some(s) => {
+                    let cmd = (pos..pos + s).into();
+                    // no params follow the command if it's terminated by a newline
+                    pos = if raw.as_bytes()[pos + s] == b' ' {
+                        pos + s + 1
+                    } else {
+                        raw.len()
+                    };
+                    cmd
                 }
-                pos = raw.len() - 1;
-                cmd
-            }
-        };
+                None => {
+                    let cmd = (pos..raw.len()).into();
+                    pos = raw.len();
+                    cmd
+                }
+            };
 
-        let command = IrcCommand::try_from(cmd)?;
+        if command_range.start == command_range.end {
+            return Err(E::NoCommand);
+        }
+
+        let command = IrcCommand::try_from(&raw[command_range])?;
 
         let mut params = ParamVec::new();
 
@@ -137,17 +187,16 @@ impl<C: Deref<Target = str>> IrcMessage<C> {
             && let Some(start) = raw.as_bytes().get(last_pos)
             && !b"\r\n".contains(start)
         {
+            // a trailing parameter without spaces or CRLF after it
+            let last_pos = if *start == b':' {
+                last_pos + 1
+            } else {
+                last_pos
+            };
             params.push((last_pos..raw.len()).into());
         }
 
-        Ok((tags, prefix, command, params))
-    }
-
-    #[cfg(feature = "connection")]
-    pub(crate) fn from_ws_message<'a>(ws_message: &'a WsMessage) -> IrcMessageParseIter<'a, C> {
-        let text = ws_message.to_text().unwrap_or_default();
-
-        IrcMessageParseIter::new(text)
+        Ok((tags, prefix, command, command_range, params))
     }
 
     /// Returns the message's raw string representation
@@ -155,6 +204,13 @@ impl<C: Deref<Target = str>> IrcMessage<C> {
         &self.raw
     }
 
+    /// Returns the message's raw string representation as it can be logged,
+    /// without the token `PASS` carries or the credentials in SASL
+    /// `AUTHENTICATE` messages
+    pub fn redacted(&self) -> &str {
+        redacted(&self.command, self.get_param(0), &self.raw)
+    }
+
     /// Returns the raw representation of the message
     pub fn into_inner(self) -> C {
         self.raw
@@ -170,6 +226,23 @@ impl<C: Deref<Target = str>> IrcMessage<C> {
             .flatten()
     }
 
+    /// Iterates over the user's badges, merged with the `badge-info` tag
+    pub fn typed_badges(&self) -> impl Iterator<Item = Badge<'_>> {
+        self.get_tag_raw(OwnedTag::Badges)
+            .map(|b| Badge::parse_list(b, self.get_tag_raw(OwnedTag::BadgeInfo)))
+            .into_iter()
+            .flatten()
+    }
+
+    /// Iterates over the user's badges in the channel a shared chat message was
+    /// originally sent in, merged with the `source-badge-info` tag
+    pub fn typed_source_badges(&self) -> impl Iterator<Item = Badge<'_>> {
+        self.get_tag_raw(OwnedTag::SourceBadges)
+            .map(|b| Badge::parse_list(b, self.get_tag_raw(OwnedTag::SourceBadgeInfo)))
+            .into_iter()
+            .flatten()
+    }
+
     /// Iterates over the message's emotes, yields emote id and **char** ranges of ocurrences
     pub fn emotes(&self) -> impl Iterator<Item = (&str, Vec<RangeInclusive<usize>>)> {
         self.tags
@@ -261,11 +334,35 @@ impl<C: Deref<Target = str>> IrcMessage<C> {
     }
 
     #[cfg(feature = "chrono")]
-    /// Returns the timestamp of the message in UTC time
+    /// Returns the timestamp of the message in UTC time, see
+    /// [get_timestamp_millis](Self::get_timestamp_millis)
     pub fn get_timestamp(&self) -> Option<chrono::DateTime<chrono::Utc>> {
         self.tags.as_ref().and_then(|t| t.get_timestamp(&self.raw))
     }
 
+    /// Returns the timestamp of the message in milliseconds since the unix
+    /// epoch, from the `tmi-sent-ts` tag, or the `sent-ts` or IRCv3 `time` tags
+    /// if it isn't present
+    pub fn get_timestamp_millis(&self) -> Option<i64> {
+        self.tags
+            .as_ref()
+            .and_then(|t| t.get_timestamp_millis(&self.raw))
+    }
+
+    /// Returns the timestamp of the message as a [SystemTime](std::time::SystemTime),
+    /// see [get_timestamp_millis](Self::get_timestamp_millis)
+    pub fn get_system_time(&self) -> Option<std::time::SystemTime> {
+        crate::time::millis_to_system_time(self.get_timestamp_millis()?)
+    }
+
+    /// Returns whether the message was sent before the client joined and was
+    /// fetched from a recent-messages service, which marks it with the
+    /// `historical` tag
+    pub fn is_historical(&self) -> bool {
+        self.get_tag_raw(OwnedTag::Historical)
+            .is_some_and(|v| v == "1" || v == "true")
+    }
+
     /// Returns the user's color as RGB8
     pub fn get_color(&self) -> Option<[u8; 3]> {
         self.tags.as_ref().and_then(|t| t.get_color(&self.raw))
@@ -327,6 +424,11 @@ impl<C: Deref<Target = str>> IrcMessage<C> {
     pub fn get_command(&self) -> IrcCommand {
         self.command
     }
+
+    /// Returns the message's command exactly as it was received
+    pub fn raw_command(&self) -> &str {
+        &self.raw[self.command_range]
+    }
 }
 
 impl<C: for<'a> From<&'a str> + Deref<Target = str>> FromStr for IrcMessage<C> {
@@ -341,13 +443,14 @@ impl<'a, C: From<&'a str> + Deref<Target = str>> TryFrom<&'a str> for IrcMessage
     type Error = IrcMessageParseError;
 
     fn try_from(value: &'a str) -> Result<Self, Self::Error> {
-        let (tags, prefix, command, params) = Self::get_parts(value)?;
+        let (tags, prefix, command, command_range, params) = Self::get_parts(value)?;
 
         Ok(Self {
             raw: value.into(),
             tags,
             prefix,
             command,
+            command_range,
             params,
         })
     }
@@ -358,27 +461,42 @@ impl TryFrom<String> for IrcMessage {
 
     #[inline]
     fn try_from(value: String) -> Result<Self, Self::Error> {
-        let (tags, prefix, command, params) = Self::get_parts(&value)?;
+        let (tags, prefix, command, command_range, params) = Self::get_parts(&value)?;
 
         Ok(Self {
             raw: value,
             tags,
             prefix,
             command,
+            command_range,
             params,
         })
     }
 }
 
-impl Display for IrcMessage {
+#[cfg(feature = "connection")]
+impl IrcMessage<BytesStr> {
+    /// Parses every IRC message in a websocket message without copying them
+    pub(crate) fn from_ws_message(ws_message: WsMessage) -> IrcMessageSplitIter<BytesStr> {
+        let buffer = match ws_message {
+            WsMessage::Text(text) => text.into(),
+            WsMessage::Binary(bin) => BytesStr::from_bytes(bin).unwrap_or_default(),
+            _ => BytesStr::default(),
+        };
+
+        IrcMessageSplitIter::new(buffer)
+    }
+}
+
+impl<C: Deref<Target = str>> Display for IrcMessage<C> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", &*self.raw)
     }
 }
 
-impl ToIrcMessage for IrcMessage {
-    fn to_message(self) -> String {
-        self.raw
+impl<C: MessageStorage> ToIrcMessage for IrcMessage<C> {
+    fn to_message(self) -> Result<String, MessageBuilderError> {
+        Ok(self.raw.into_string())
     }
 
     fn get_command(&self) -> IrcCommand {
@@ -386,8 +504,8 @@ impl ToIrcMessage for IrcMessage {
     }
 }
 
-impl PartialEq<IrcMessage> for IrcMessage {
-    fn eq(&self, other: &IrcMessage) -> bool {
+impl<C: Deref<Target = str>, C2: Deref<Target = str>> PartialEq<IrcMessage<C2>> for IrcMessage<C> {
+    fn eq(&self, other: &IrcMessage<C2>) -> bool {
         // is this correct??
         // if self.raw.len() != other.raw.len() { return false }
 
@@ -409,10 +527,10 @@ impl PartialEq<IrcMessage> for IrcMessage {
     }
 }
 
-impl Eq for IrcMessage {}
+impl<C: Deref<Target = str>> Eq for IrcMessage<C> {}
 
-#[cfg(all(feature = "serde", feature = "unstable"))]
-impl Serialize for IrcMessage {
+#[cfg(feature = "serde")]
+impl<C: Deref<Target = str>> Serialize for IrcMessage<C> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
@@ -496,7 +614,7 @@ impl Serialize for IrcMessage {
             }),
         )?;
 
-        msg.serialize_field("command", Into::<&str>::into(self.command))?;
+        msg.serialize_field("command", &self.command)?;
 
         msg.serialize_field(
             "params",
@@ -510,6 +628,152 @@ impl Serialize for IrcMessage {
     }
 }
 
+/// The structured form of an [IrcMessage], as emitted by its [Serialize] impl
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[serde(rename = "IrcMessage")]
+struct StructuredMessage {
+    #[serde(default, deserialize_with = "deserialize_tag_list")]
+    tags: Option<Vec<(OwnedTag, String)>>,
+    #[serde(default)]
+    prefix: Option<crate::irc_message::prefix::OwnedPrefix>,
+    command: IrcCommand,
+    #[serde(default)]
+    params: Vec<String>,
+}
+
+#[cfg(feature = "serde")]
+impl StructuredMessage {
+    /// Rebuilds the raw line of the message, the tag values are expected to be
+    /// already escaped, just like they are serialized
+    fn to_raw(&self) -> Result<String, String> {
+        use std::fmt::Write;
+
+        let mut out = String::new();
+
+        if let Some(tags) = self.tags.as_ref().filter(|t| !t.is_empty()) {
+            for (idx, (tag, value)) in tags.iter().enumerate() {
+                out.push(if idx == 0 { '@' } else { ';' });
+                write!(&mut out, "{tag}={value}").unwrap();
+            }
+            out.push(' ');
+        }
+
+        if let Some(prefix) = &self.prefix {
+            write!(&mut out, "{prefix} ").unwrap();
+        }
+
+        write!(&mut out, "{}", self.command).unwrap();
+
+        if let [middle @ .., trailing] = &self.params[..] {
+            for param in middle {
+                if param.is_empty()
+                    || param.starts_with(':')
+                    || param.contains([' ', '\r', '\n', '\0'])
+                {
+                    return Err(format!("invalid middle param {param:?}"));
+                }
+                write!(&mut out, " {param}").unwrap();
+            }
+            if trailing.contains(['\r', '\n', '\0']) {
+                return Err(format!("invalid trailing param {trailing:?}"));
+            }
+            write!(&mut out, " :{trailing}").unwrap();
+        }
+
+        out.push_str("\r\n");
+
+        Ok(out)
+    }
+}
+
+#[cfg(feature = "serde")]
+fn deserialize_tag_list<'de, D: serde::Deserializer<'de>>(
+    deser: D,
+) -> Result<Option<Vec<(OwnedTag, String)>>, D::Error> {
+    struct TagsVisitor;
+    impl<'v> Visitor<'v> for TagsVisitor {
+        type Value = Option<Vec<(OwnedTag, String)>>;
+
+        fn visit_none<E: serde::de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_unit<E: serde::de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_some<D: serde::Deserializer<'v>>(
+            self,
+            deserializer: D,
+        ) -> Result<Self::Value, D::Error> {
+            deserializer.deserialize_map(self)
+        }
+
+        fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+        where
+            A: MapAccess<'v>,
+        {
+            // keeps the tags in the same order they were in
+            let mut tags = Vec::with_capacity(map.size_hint().unwrap_or_default());
+
+            while let Some(entry) = map.next_entry()? {
+                tags.push(entry);
+            }
+
+            Ok(Some(tags))
+        }
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("a map of tags or null")
+        }
+    }
+
+    deser.deserialize_option(TagsVisitor)
+}
+
+/// Deserializes from either a raw IRC line or the structured form emitted by
+/// the [Serialize] impl
+#[cfg(feature = "serde")]
+impl<'de, C: From<String> + Deref<Target = str>> Deserialize<'de> for IrcMessage<C> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de::Error;
+
+        struct MessageVisitor<C>(std::marker::PhantomData<C>);
+
+        impl<'v, C: From<String> + Deref<Target = str>> Visitor<'v> for MessageVisitor<C> {
+            type Value = IrcMessage<C>;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a raw IRC message or a structured IRC message")
+            }
+
+            fn visit_str<E: Error>(self, v: &str) -> Result<Self::Value, E> {
+                self.visit_string(String::from(v))
+            }
+
+            fn visit_string<E: Error>(self, v: String) -> Result<Self::Value, E> {
+                IrcMessage::new(v).map_err(E::custom)
+            }
+
+            fn visit_map<A: MapAccess<'v>>(self, map: A) -> Result<Self::Value, A::Error> {
+                let structured = StructuredMessage::deserialize(MapAccessDeserializer::new(map))?;
+                self.visit_string(structured.to_raw().map_err(A::Error::custom)?)
+            }
+
+            fn visit_seq<A: SeqAccess<'v>>(self, seq: A) -> Result<Self::Value, A::Error> {
+                let structured = StructuredMessage::deserialize(SeqAccessDeserializer::new(seq))?;
+                self.visit_string(structured.to_raw().map_err(A::Error::custom)?)
+            }
+        }
+
+        deserializer.deserialize_any(MessageVisitor(std::marker::PhantomData))
+    }
+}
+
 /// Iterator over an [IrcMessage]'s params
 pub struct Params<'a> {
     src: &'a str,
@@ -524,6 +788,21 @@ impl<'a> Iterator for Params<'a> {
     }
 }
 
+/// A raw message as it is logged, see [IrcMessage::redacted]
+pub(crate) fn redacted<'a>(
+    command: &IrcCommand,
+    first_param: Option<&str>,
+    raw: &'a str,
+) -> &'a str {
+    match command {
+        IrcCommand::Pass => "[user token redacted]",
+        IrcCommand::Authenticate if !first_param.is_some_and(is_public_authenticate) => {
+            "AUTHENTICATE [credentials redacted]"
+        }
+        _ => raw.trim(),
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::IrcMessage;
@@ -531,14 +810,14 @@ mod test {
     #[test]
     #[cfg(feature = "connection")]
     fn from_ws_message() {
-        use tokio_tungstenite::tungstenite::Message as WsMessage;
+        use async_tungstenite::tungstenite::Message as WsMessage;
 
         const MSGS: &str = "@badge-info=;badges=moments/2;client-nonce=9297a96d510091fa87c81eaa9e5bb8e3;color=#E4E5FF;display-name=MELLOWFLEUR;emotes=;first-msg=0;flags=;id=1ada6902-aafe-452a-8651-1fe711ddd7d1;mod=0;returning-chatter=0;room-id=71092938;subscriber=0;tmi-sent-ts=1680318910689;turbo=0;user-id=45179149;user-type= :mellowfleur!mellowfleur@mellowfleur.tmi.twitch.tv PRIVMSG #xqc :yes\r
 @badge-info=;badges=moments/2;client-nonce=da0ef47ebddf148067c685599dd6bc90;color=#8A2BE2;display-name=lonelythomas;emotes=;first-msg=0;flags=;id=91c3b354-95b7-4509-a337-3b86c194b141;mod=0;returning-chatter=0;room-id=71092938;subscriber=0;tmi-sent-ts=1680318910693;turbo=0;user-id=217061103;user-type= :lonelythomas!lonelythomas@lonelythomas.tmi.twitch.tv PRIVMSG #xqc :LETHIMCOOK\r
 @badge-info=subscriber/19;badges=subscriber/18,bits/100;client-nonce=b937ab21b00c4f01bd6b729e9b47b665;color=#FFFFFF;display-name=ink6h;emotes=;first-msg=0;flags=;id=5364e52d-baa5-42fa-95a5-d719e17e41dd;mod=0;returning-chatter=0;room-id=71092938;subscriber=1;tmi-sent-ts=1680318911064;turbo=0;user-id=168511883;user-type= :ink6h!ink6h@ink6h.tmi.twitch.tv PRIVMSG #xqc :ye\r
 @badge-info=;badges=;color=;display-name=getoutofmyhead123;emote-only=1;emotes=emotesv2_04dd118ef04a49c1aa0caa7fc3144369:0-4,6-10,12-16;first-msg=0;flags=;id=225dcdf8-c734-4f62-bb30-af49f2af32e9;mod=0;returning-chatter=0;room-id=71092938;subscriber=0;tmi-sent-ts=1680318911099;turbo=0;user-id=880902531;user-type= :getoutofmyhead123!getoutofmyhead123@getoutofmyhead123.tmi.twitch.tv PRIVMSG #xqc :xqcLL xqcLL xqcLL\r";
         let msg = WsMessage::Text(MSGS.into());
-        for msg in IrcMessage::<String>::from_ws_message(&msg) {
+        for msg in IrcMessage::from_ws_message(msg) {
             assert!(msg.is_ok(), "{msg:?}");
         }
     }
@@ -579,6 +858,41 @@ mod test {
         assert_eq!(no_crlf_paramless.params().count(), 0);
     }
 
+    #[test]
+    fn no_crlf_trailing_word() {
+        let trailing: IrcMessage = ":user!user@user.tmi.twitch.tv PRIVMSG #room :word"
+            .parse()
+            .unwrap();
+        assert_eq!(trailing.params().count(), 2);
+        assert_eq!(trailing.get_param(1), Some("word"));
+
+        let middle: IrcMessage = ":user!user@user.tmi.twitch.tv PRIVMSG #room word"
+            .parse()
+            .unwrap();
+        assert_eq!(middle.get_param(1), Some("word"));
+    }
+
+    #[test]
+    fn redacted() {
+        let redacted = |raw: &str| {
+            IrcMessage::<String>::new(raw.to_owned())
+                .unwrap()
+                .redacted()
+                .to_owned()
+        };
+        assert_eq!(redacted("PASS oauth:hunter2\r\n"), "[user token redacted]");
+        assert_eq!(
+            redacted("AUTHENTICATE c2VjcmV0"),
+            "AUTHENTICATE [credentials redacted]"
+        );
+        assert_eq!(redacted("AUTHENTICATE PLAIN"), "AUTHENTICATE PLAIN");
+        assert_eq!(redacted("AUTHENTICATE +\r\n"), "AUTHENTICATE +");
+        assert_eq!(
+            redacted(":a!a@a.tmi.twitch.tv PRIVMSG #foo :hi"),
+            ":a!a@a.tmi.twitch.tv PRIVMSG #foo :hi"
+        );
+    }
+
     #[test]
     fn with_crlf() {
         let with_crlf: IrcMessage = ":user!user@user.tmi.twitch.tv PRIVMSG #room no_CRLF\r\n"
@@ -607,6 +921,87 @@ mod test {
         assert_eq!(with_crlf_paramless.params().count(), 0);
     }
 
+    const MULTILINE: &str = "@badge-info=;badges=;color=;display-name=a;id=1;room-id=2;tmi-sent-ts=3;user-id=4 :a!a@a.tmi.twitch.tv PRIVMSG #room :first\r
+@badge-info=;badges=;color=;display-name=b;id=5;room-id=2;tmi-sent-ts=6;user-id=7 :b!b@b.tmi.twitch.tv PRIVMSG #room :second message\r
+PING :tmi.twitch.tv\r
+";
+
+    #[test]
+    fn borrowed_storage() {
+        use crate::irc_message::{AnySemantic, PrivMsg};
+
+        let msgs: Vec<IrcMessage<&str>> = IrcMessage::split_multiline(MULTILINE)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(msgs.len(), 3);
+        assert!(
+            MULTILINE
+                .as_bytes()
+                .as_ptr_range()
+                .contains(&msgs[1].inner().as_ptr())
+        );
+
+        let any = AnySemantic::from(msgs[1].clone());
+        let privmsg = PrivMsg::from_any_ref(&any).unwrap();
+        assert_eq!(privmsg.message_text(), "second message");
+        assert_eq!(privmsg.sender_login().unwrap(), "b");
+
+        let owned: AnySemantic = any.into_storage();
+        assert_eq!(owned.get_param(1), Some("second message"));
+    }
+
+    #[test]
+    fn shared_storage() {
+        use std::sync::Arc;
+
+        let buffer: Arc<str> = Arc::from(MULTILINE);
+        let msgs: Vec<IrcMessage<Arc<str>>> = IrcMessage::split_multiline(buffer)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let owned: Vec<IrcMessage> = IrcMessage::new_multiline(MULTILINE)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(msgs, owned);
+    }
+
+    #[test]
+    #[cfg(feature = "bytes")]
+    fn bytes_storage() {
+        use crate::irc_message::storage::BytesStr;
+
+        let buffer = BytesStr::from(MULTILINE);
+        let range = buffer.as_bytes().as_ptr_range();
+        let msgs: Vec<IrcMessage<BytesStr>> = IrcMessage::split_multiline(buffer)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(msgs.len(), 3);
+        for msg in &msgs {
+            assert!(range.contains(&msg.inner().as_ptr()));
+        }
+        assert_eq!(msgs[0].get_param(1), Some("first"));
+        assert_eq!(msgs[2].get_param(0), Some("tmi.twitch.tv"));
+    }
+
+    #[test]
+    fn unknown_commands() {
+        use crate::{IrcCommand, irc_message::AnySemantic};
+
+        let numeric: IrcMessage =
+            ":tmi.twitch.tv 372 justinfan123 :You are in a maze of twisty passages\r\n"
+                .parse()
+                .unwrap();
+        assert_eq!(numeric.get_command(), IrcCommand::Numeric(372));
+        assert_eq!(numeric.raw_command(), "372");
+        assert!(matches!(AnySemantic::from(numeric), AnySemantic::Numeric(n) if n.code() == 372));
+
+        let other: IrcMessage = ":tmi.twitch.tv SOMETHINGNEW #room :hi\r\n".parse().unwrap();
+        assert_eq!(other.raw_command(), "SOMETHINGNEW");
+        assert_eq!(other.get_param(1), Some("hi"));
+        assert!(
+            matches!(AnySemantic::from(other), AnySemantic::Other(o) if o.command_name() == "SOMETHINGNEW")
+        );
+    }
+
     /// From RFC-2812:
     /// > After extracting the parameter list, all parameters are equal
     /// > whether matched by <middle> or <trailing>. <trailing> is just a
@@ -1,9 +1,17 @@
+/// Builder for outgoing IRC messages
 pub mod builder;
+/// IRC commands
 pub mod command;
+/// Iterators over IRC messages and their contents
 pub mod iter;
+/// The untyped [IrcMessage] type
 pub mod message;
+/// The prefix segment of IRC messages
 pub mod prefix;
 pub mod semantic;
+/// Backing storage types for [IrcMessage]
+pub mod storage;
+/// IRCv3 message tags
 pub mod tags;
 
 pub use command::IrcCommand;
@@ -13,7 +21,11 @@ pub use semantic::*;
 /// Trait for types which can be sent over an IRC connection
 pub trait ToIrcMessage {
     /// Convert to a valid IRC message
-    fn to_message(self) -> String;
+    ///
+    /// # Errors
+    /// If the message is not valid and could not be serialized, see
+    /// [MessageBuilder::build](builder::MessageBuilder::build)
+    fn to_message(self) -> Result<String, builder::MessageBuilderError>;
 
     /// Get the message's IRC command
     fn get_command(&self) -> IrcCommand;
@@ -139,7 +151,7 @@ mod tests {
         assert_eq!(parsed.to_string(), TEST_MESSAGE);
     }
 
-    #[cfg(all(feature = "serde", feature = "unstable"))]
+    #[cfg(feature = "serde")]
     #[test]
     fn roundtrip_deserialization() {
         use crate::MessageBuilder;
@@ -152,10 +164,57 @@ mod tests {
         let deserialized_owned: MessageBuilder =
             serde_json::from_str(&json_parsed).expect(&json_parsed);
 
-        let rebuilt = IrcMessage::new(deserialized_owned.build()).unwrap();
+        let rebuilt: IrcMessage = IrcMessage::new(deserialized_owned.build().unwrap()).unwrap();
         assert_eq!(
             parsed, rebuilt,
             "an OwnedIrcMessage could not be deserialized from a serialized IrcMessage"
         );
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn message_deserialization() {
+        use crate::irc_message::{AnySemantic, PrivMsg};
+
+        const TEST_MESSAGES: &[&str] = &[
+            "@vip=1 :guh PRIVMSG #a :hi there\r\n",
+            "@tag1=val1;tag2=val2;tag3=val3 :juliapixel!julia@juliapixel.com PRIVMSG #juliapixel :hi",
+            ":user!user@user.tmi.twitch.tv PRIVMSG #room no_CRLF",
+            ":user!user@user.tmi.twitch.tv PRIVMSG\r\n",
+            "@badge-info=;badges=;color=;display-name=b;emotes=;id=5;room-id=2;tmi-sent-ts=6;user-id=7;msg=a\\sb :b!b@b.tmi.twitch.tv PRIVMSG #room :second message\r\n",
+            ":tmi.twitch.tv 372 justinfan123 :You are in a maze of twisty passages\r\n",
+            ":tmi.twitch.tv SOMETHINGNEW #room :hi\r\n",
+            "PING :tmi.twitch.tv\r\n",
+        ];
+
+        for msg in TEST_MESSAGES.iter().copied().chain(SHIT_TON.lines()) {
+            let parsed: IrcMessage = msg.parse().unwrap();
+
+            let structured = serde_json::to_string(&parsed).unwrap();
+            let from_structured: IrcMessage = serde_json::from_str(&structured).expect(&structured);
+            assert_eq!(parsed, from_structured, "{structured}");
+            assert_eq!(
+                parsed.get_tag(OwnedTag::Id),
+                from_structured.get_tag(OwnedTag::Id)
+            );
+
+            let raw = serde_json::to_string(msg).unwrap();
+            let from_raw: IrcMessage = serde_json::from_str(&raw).unwrap();
+            assert_eq!(parsed, from_raw, "{raw}");
+
+            let any: AnySemantic = serde_json::from_str(&structured).unwrap();
+            assert_eq!(serde_json::to_string(&any).unwrap(), structured);
+        }
+
+        let privmsg: PrivMsg = serde_json::from_str(r#""PRIVMSG #room :hi\r\n""#).unwrap();
+        assert_eq!(privmsg.get_param(1), Some("hi"));
+        assert!(serde_json::from_str::<PrivMsg>(r#""PING :tmi.twitch.tv\r\n""#).is_err());
+
+        assert!(
+            serde_json::from_str::<IrcMessage>(
+                r##"{"tags":null,"prefix":null,"command":"PRIVMSG","params":["#a b","hi"]}"##
+            )
+            .is_err()
+        );
+    }
 }
@@ -1,8 +1,14 @@
-use crate::irc_message::tags::OwnedTag;
+use std::{borrow::Cow, ops::Deref};
 
-use super::ClearChat;
+use crate::{
+    irc_message::tags::OwnedTag,
+    login::{ChannelLogin, UserId, UserLogin},
+};
+
+use super::{ClearChat, moderation::ModerationEvent};
 
 /// Duration of the timeout/ban
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TimeoutDuration {
     /// Permanent ban
     Permanent,
@@ -10,37 +16,74 @@ pub enum TimeoutDuration {
     Temporary(std::time::Duration),
 }
 
-impl ClearChat {
+impl<C: Deref<Target = str>> ClearChat<C> {
     /// User ID of the target of the timeout/ban
-    pub fn target_user_id(&self) -> Option<&str> {
+    pub fn target_user_id(&self) -> Option<UserId<&str>> {
         self.get_tag_raw(OwnedTag::TargetUserId)
+            .and_then(|id| UserId::new(id).ok())
     }
 
     /// ID of the channel the timeout/ban occurred in
-    pub fn room_id(&self) -> Option<&str> {
+    pub fn room_id(&self) -> Option<UserId<&str>> {
         self.get_tag_raw(OwnedTag::RoomId)
+            .and_then(|id| UserId::new(id).ok())
     }
 
-    /// Duration of time timeout/ban
-    pub fn duration(&self) -> TimeoutDuration {
-        match self.get_tag(OwnedTag::BanDuration) {
-            Some(dur) if dur.is_empty() => TimeoutDuration::Permanent,
-            Some(dur) => {
-                TimeoutDuration::Temporary(std::time::Duration::from_secs(dur.parse().unwrap()))
-            }
-            None => TimeoutDuration::Permanent,
+    /// Duration of time timeout/ban, `None` if the whole chat was cleared
+    /// instead or if the duration is malformed
+    pub fn duration(&self) -> Option<TimeoutDuration> {
+        self.target_login()?;
+        match self.get_tag_raw(OwnedTag::BanDuration) {
+            Some(dur) if !dur.is_empty() => Some(TimeoutDuration::Temporary(
+                std::time::Duration::from_secs(dur.parse().ok()?),
+            )),
+            _ => Some(TimeoutDuration::Permanent),
         }
     }
 
-    /// Login of the target of the timeout/ban
-    pub fn channel_login(&self) -> Option<&str> {
+    /// Login of the channel the timeout/ban occurred in
+    pub fn channel_login(&self) -> Option<ChannelLogin<Cow<'_, str>>> {
         self.get_param(0)
-            .and_then(|p| p.split_at_checked(1).map(|s| s.1))
+            .and_then(|p| ChannelLogin::from_irc_param(p).ok())
     }
 
-    /// Login of the channel the timeout/ban occurred in
-    pub fn target_login(&self) -> Option<&str> {
-        self.get_param(1)
-            .and_then(|p| p.split_at_checked(1).map(|s| s.1))
+    /// Login of the target of the timeout/ban, `None` if the whole chat was
+    /// cleared
+    pub fn target_login(&self) -> Option<UserLogin<&str>> {
+        self.get_param(1).and_then(|p| UserLogin::new(p).ok())
+    }
+
+    /// The moderation action this message represents
+    pub fn event(&self) -> Option<ModerationEvent<'_>> {
+        let channel_login = self.channel_login()?;
+        let room_id = self.room_id();
+        let timestamp = self.get_system_time();
+
+        let Some(target_login) = self.target_login() else {
+            return Some(ModerationEvent::ChatCleared {
+                channel_login,
+                room_id,
+                timestamp,
+            });
+        };
+        let target_user_id = self.target_user_id();
+
+        Some(match self.duration()? {
+            TimeoutDuration::Permanent => ModerationEvent::UserBanned {
+                channel_login,
+                room_id,
+                target_login,
+                target_user_id,
+                timestamp,
+            },
+            TimeoutDuration::Temporary(duration) => ModerationEvent::UserTimedOut {
+                channel_login,
+                room_id,
+                target_login,
+                target_user_id,
+                duration,
+                timestamp,
+            },
+        })
     }
 }
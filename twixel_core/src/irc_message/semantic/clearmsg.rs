@@ -1,8 +1,13 @@
-use crate::irc_message::tags::OwnedTag;
+use std::{borrow::Cow, ops::Deref};
 
-use super::{ClearMsg, util::msg_from_param};
+use crate::{
+    irc_message::tags::OwnedTag,
+    login::{ChannelLogin, UserId, UserLogin},
+};
 
-impl ClearMsg {
+use super::{ClearMsg, moderation::ModerationEvent, util::msg_from_param};
+
+impl<C: Deref<Target = str>> ClearMsg<C> {
     /// ID of the message that was deleted
     pub fn target_msg_id(&self) -> Option<&str> {
         self.get_tag_raw(OwnedTag::TargetMsgId)
@@ -14,18 +19,39 @@ impl ClearMsg {
     }
 
     /// ID of the user whose message was deleted
-    pub fn target_user_id(&self) -> Option<&str> {
+    pub fn target_user_id(&self) -> Option<UserId<&str>> {
         self.get_tag_raw(OwnedTag::TargetUserId)
+            .and_then(|id| UserId::new(id).ok())
     }
 
     /// ID of the channel where the message was deleted
-    pub fn room_id(&self) -> Option<&str> {
+    pub fn room_id(&self) -> Option<UserId<&str>> {
         self.get_tag_raw(OwnedTag::RoomId)
+            .and_then(|id| UserId::new(id).ok())
     }
 
     /// Login of the channel where the message was deleted
-    pub fn channel_login(&self) -> Option<&str> {
+    pub fn channel_login(&self) -> Option<ChannelLogin<Cow<'_, str>>> {
         self.get_param(0)
-            .and_then(|p| p.split_at_checked(1).map(|s| s.1))
+            .and_then(|p| ChannelLogin::from_irc_param(p).ok())
+    }
+
+    /// Login of the user whose message was deleted
+    pub fn target_login(&self) -> Option<UserLogin<&str>> {
+        self.get_tag_raw(OwnedTag::Login)
+            .and_then(|l| UserLogin::new(l).ok())
+    }
+
+    /// The moderation action this message represents
+    pub fn event(&self) -> Option<ModerationEvent<'_>> {
+        Some(ModerationEvent::MessageDeleted {
+            channel_login: self.channel_login()?,
+            room_id: self.room_id(),
+            target_login: self.target_login(),
+            target_user_id: self.target_user_id(),
+            target_msg_id: self.target_msg_id()?,
+            message_text: self.message_text(),
+            timestamp: self.get_system_time(),
+        })
     }
 }
@@ -5,34 +5,43 @@
 pub mod clearchat;
 /// Utilities related to the [CLEARMSG](ClearMsg) message kind
 pub mod clearmsg;
+/// Moderation actions derived from [CLEARCHAT](ClearChat) and
+/// [CLEARMSG](ClearMsg) messages
+pub mod moderation;
 /// Utilities related to the [NOTICE](Notice) message kind
 pub mod notice;
+/// Utilities related to [Numeric] and [Other] message kinds
+pub mod other;
 /// Utilities related to the [PING](Ping) message kind
 pub mod ping;
 /// Utilities related to the [PRIVMSG](PrivMsg) message kind
 pub mod privmsg;
+/// Utilities related to the standard [KICK](Kick), [TOPIC](Topic),
+/// [MODE](Mode) and [QUIT](Quit) message kinds, which Twitch doesn't send
+pub mod standard;
 /// Utilities related to the [USERSTATE](UserState) message kind
 pub mod userstate;
 
 mod util;
 
-use std::fmt::Display;
+use std::{fmt::Display, ops::Deref};
 
 use either::Either;
 
 use crate::IrcMessage;
 
-/// Trait for the semantic wrappers around the different message types
-pub trait SemanticIrcMessage: Sized + private::Sealed {
+/// Trait for the semantic wrappers around the different message types, generic
+/// over the message's backing storage
+pub trait SemanticIrcMessage<C: Deref<Target = str> = String>: Sized + private::Sealed {
     /// Take the untyped [IrcMessage]
-    fn to_inner(self) -> IrcMessage;
+    fn to_inner(self) -> IrcMessage<C>;
 
     /// Take a reference to the untyped [IrcMessage]
-    fn inner(&self) -> &IrcMessage;
+    fn inner(&self) -> &IrcMessage<C>;
 
     /// Convert from an untyped [IrcMessage]
     #[allow(clippy::result_large_err, reason = "intended")]
-    fn from_message(msg: IrcMessage) -> Result<Self, IrcMessage>;
+    fn from_message(msg: IrcMessage<C>) -> Result<Self, IrcMessage<C>>;
 }
 
 mod private {
@@ -41,31 +50,32 @@ mod private {
 
 impl<L, R> private::Sealed for either::Either<L, R>
 where
-    L: SemanticIrcMessage,
-    R: SemanticIrcMessage,
+    L: private::Sealed,
+    R: private::Sealed,
 {
 }
 
-impl<L, R> SemanticIrcMessage for either::Either<L, R>
+impl<C, L, R> SemanticIrcMessage<C> for either::Either<L, R>
 where
-    L: SemanticIrcMessage,
-    R: SemanticIrcMessage,
+    C: Deref<Target = str>,
+    L: SemanticIrcMessage<C>,
+    R: SemanticIrcMessage<C>,
 {
-    fn to_inner(self) -> IrcMessage {
+    fn to_inner(self) -> IrcMessage<C> {
         match self {
             either::Either::Left(l) => l.to_inner(),
             either::Either::Right(r) => r.to_inner(),
         }
     }
 
-    fn inner(&self) -> &IrcMessage {
+    fn inner(&self) -> &IrcMessage<C> {
         match self {
             either::Either::Left(l) => l.inner(),
             either::Either::Right(r) => r.inner(),
         }
     }
 
-    fn from_message(msg: IrcMessage) -> Result<Self, IrcMessage> {
+    fn from_message(msg: IrcMessage<C>) -> Result<Self, IrcMessage<C>> {
         match L::from_message(msg) {
             Ok(l) => Ok(Either::Left(l)),
             Err(m) => R::from_message(m).map(|r| Either::Right(r)),
@@ -80,31 +90,50 @@ macro_rules! impl_semantic {
             #[cfg_attr(feature = "serde", derive(serde::Serialize))]
             #[cfg_attr(feature = "serde", serde(transparent))]
             #[doc = concat!("a semantic wrapper around a [", stringify!($cmd), "](crate::IrcCommand::", stringify!($cmd), ") [IrcMessage](super::message::IrcMessage)")]
-            pub struct $cmd {
-                inner: $crate::irc_message::message::IrcMessage
+            pub struct $cmd<C: Deref<Target = str> = String> {
+                inner: $crate::irc_message::message::IrcMessage<C>
             }
 
-            impl ::std::ops::Deref for $cmd {
-                type Target = $crate::irc_message::message::IrcMessage;
+            impl<C: Deref<Target = str>> ::std::ops::Deref for $cmd<C> {
+                type Target = $crate::irc_message::message::IrcMessage<C>;
 
                 fn deref(&self) -> &Self::Target {
                     &self.inner
                 }
             }
 
-            impl private::Sealed for $cmd {}
+            impl<C: Deref<Target = str>> private::Sealed for $cmd<C> {}
+
+            /// Deserializes from anything an [IrcMessage] can be deserialized
+            /// from, erroring if it isn't of the right command
+            #[cfg(feature = "serde")]
+            impl<'de, C: From<String> + Deref<Target = str>> serde::Deserialize<'de> for $cmd<C> {
+                fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+                where
+                    D: serde::Deserializer<'de>,
+                {
+                    let msg = IrcMessage::<C>::deserialize(deserializer)?;
+                    let command = msg.get_command();
+                    Self::from_message(msg).map_err(|_| {
+                        <D::Error as serde::de::Error>::custom(format_args!(
+                            concat!("expected a ", stringify!($cmd), " message, got {}"),
+                            command
+                        ))
+                    })
+                }
+            }
 
-            impl $crate::irc_message::semantic::SemanticIrcMessage for $cmd {
-                fn to_inner(self) -> IrcMessage {
+            impl<C: Deref<Target = str>> $crate::irc_message::semantic::SemanticIrcMessage<C> for $cmd<C> {
+                fn to_inner(self) -> IrcMessage<C> {
                     self.inner
                 }
 
-                fn inner(&self) -> &$crate::irc_message::message::IrcMessage {
+                fn inner(&self) -> &$crate::irc_message::message::IrcMessage<C> {
                     &self.inner
                 }
 
-                fn from_message(msg: $crate::irc_message::message::IrcMessage) -> Result<Self, IrcMessage> {
-                    if msg.get_command() == $crate::irc_message::command::IrcCommand::$cmd {
+                fn from_message(msg: $crate::irc_message::message::IrcMessage<C>) -> Result<Self, IrcMessage<C>> {
+                    if matches!(msg.get_command(), $crate::irc_message::command::IrcCommand::$cmd { .. }) {
                         Ok(Self { inner: msg })
                     } else {
                         Err(msg)
@@ -112,9 +141,9 @@ macro_rules! impl_semantic {
                 }
             }
 
-            impl $cmd {
+            impl<C: Deref<Target = str>> $cmd<C> {
                 /// Tries to convert from [AnySemantic] to this type
-                pub fn from_any(any: AnySemantic) -> Option<Self> {
+                pub fn from_any(any: AnySemantic<C>) -> Option<Self> {
                     match any {
                         AnySemantic::$cmd(c) => Some(c),
                         _ => None
@@ -122,54 +151,88 @@ macro_rules! impl_semantic {
                 }
 
                 /// Tries to convert from [&AnySemantic](AnySemantic) to a reference to this type
-                pub fn from_any_ref(any: &AnySemantic) -> Option<&Self> {
+                pub fn from_any_ref(any: &AnySemantic<C>) -> Option<&Self> {
                     match any {
                         AnySemantic::$cmd(c) => Some(c),
                         _ => None
                     }
                 }
+
+                /// Converts the backing storage of this message, see [IrcMessage::into_storage]
+                pub fn into_storage<D: From<C> + Deref<Target = str>>(self) -> $cmd<D> {
+                    $cmd { inner: self.inner.into_storage() }
+                }
             }
         )+
 
         /// enum containing all semantic wrappers around [crate::IrcMessage]
         #[derive(Debug, Clone)]
         #[allow(missing_docs)]
-        pub enum AnySemantic {
-            $($cmd($cmd)),+
+        pub enum AnySemantic<C: Deref<Target = str> = String> {
+            $($cmd($cmd<C>)),+
         }
 
-        impl ::std::ops::Deref for AnySemantic {
-            type Target = $crate::irc_message::message::IrcMessage;
+        impl<C: Deref<Target = str>> ::std::ops::Deref for AnySemantic<C> {
+            type Target = $crate::irc_message::message::IrcMessage<C>;
 
             fn deref(&self) -> &Self::Target {
                 &self.inner()
             }
         }
 
-        impl From<IrcMessage> for AnySemantic {
-            fn from(value: IrcMessage) -> Self {
+        impl<C: Deref<Target = str>> From<IrcMessage<C>> for AnySemantic<C> {
+            fn from(value: IrcMessage<C>) -> Self {
                 match value.get_command() {
-                    $($crate::irc_message::command::IrcCommand::$cmd => Self::$cmd($cmd::from_message(value).unwrap()),)+
+                    $($crate::irc_message::command::IrcCommand::$cmd { .. } => Self::$cmd($cmd { inner: value }),)+
                 }
             }
         }
 
-        impl private::Sealed for AnySemantic {}
+        impl<C: Deref<Target = str>> AnySemantic<C> {
+            /// Converts the backing storage of this message, see [IrcMessage::into_storage]
+            pub fn into_storage<D: From<C> + Deref<Target = str>>(self) -> AnySemantic<D> {
+                match self {
+                    $(Self::$cmd(inner) => AnySemantic::$cmd(inner.into_storage())),+
+                }
+            }
+        }
+
+        impl<C: Deref<Target = str>> private::Sealed for AnySemantic<C> {}
+
+        #[cfg(feature = "serde")]
+        impl<C: Deref<Target = str>> serde::Serialize for AnySemantic<C> {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: serde::Serializer,
+            {
+                self.inner().serialize(serializer)
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de, C: From<String> + Deref<Target = str>> serde::Deserialize<'de> for AnySemantic<C> {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: serde::Deserializer<'de>,
+            {
+                IrcMessage::<C>::deserialize(deserializer).map(Self::from)
+            }
+        }
 
-        impl $crate::irc_message::semantic::SemanticIrcMessage for AnySemantic {
-            fn to_inner(self) -> IrcMessage {
+        impl<C: Deref<Target = str>> $crate::irc_message::semantic::SemanticIrcMessage<C> for AnySemantic<C> {
+            fn to_inner(self) -> IrcMessage<C> {
                 match self {
                     $(Self::$cmd(inner) => inner.to_inner()),+
                 }
             }
 
-            fn inner(&self) -> &$crate::irc_message::message::IrcMessage {
+            fn inner(&self) -> &$crate::irc_message::message::IrcMessage<C> {
                 match self {
                     $(Self::$cmd(inner) => inner.inner()),+
                 }
             }
 
-            fn from_message(msg: $crate::irc_message::message::IrcMessage) -> Result<Self, IrcMessage> {
+            fn from_message(msg: $crate::irc_message::message::IrcMessage<C>) -> Result<Self, IrcMessage<C>> {
                 Ok(Self::from(msg))
             }
         }
@@ -195,13 +258,20 @@ impl_semantic!(
     UserNotice,
     Reconnect,
     Whisper,
+    User,
+    Mode,
+    Topic,
+    Kick,
+    Quit,
+    Authenticate,
     UnsupportedError,
     UserList,
     AuthSuccessful,
-    Useless
+    Numeric,
+    Other
 );
 
-impl Display for AnySemantic {
+impl<C: Deref<Target = str>> Display for AnySemantic<C> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str(self.inner().inner())
     }
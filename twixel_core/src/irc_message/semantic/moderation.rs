@@ -0,0 +1,198 @@
+use std::{borrow::Cow, ops::Deref, time::Duration, time::SystemTime};
+
+use crate::login::{ChannelLogin, UserId, UserLogin};
+
+use super::AnySemantic;
+
+/// A moderation action in a channel, from either a
+/// [CLEARCHAT](super::ClearChat) or a [CLEARMSG](super::ClearMsg) message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModerationEvent<'a> {
+    /// Every message in the chat was cleared
+    ChatCleared {
+        /// Login of the channel
+        channel_login: ChannelLogin<Cow<'a, str>>,
+        /// ID of the channel
+        room_id: Option<UserId<&'a str>>,
+        /// When the chat was cleared
+        timestamp: Option<SystemTime>,
+    },
+    /// A user was permanently banned
+    UserBanned {
+        /// Login of the channel
+        channel_login: ChannelLogin<Cow<'a, str>>,
+        /// ID of the channel
+        room_id: Option<UserId<&'a str>>,
+        /// Login of the banned user
+        target_login: UserLogin<&'a str>,
+        /// ID of the banned user
+        target_user_id: Option<UserId<&'a str>>,
+        /// When the user was banned
+        timestamp: Option<SystemTime>,
+    },
+    /// A user was timed out
+    UserTimedOut {
+        /// Login of the channel
+        channel_login: ChannelLogin<Cow<'a, str>>,
+        /// ID of the channel
+        room_id: Option<UserId<&'a str>>,
+        /// Login of the timed out user
+        target_login: UserLogin<&'a str>,
+        /// ID of the timed out user
+        target_user_id: Option<UserId<&'a str>>,
+        /// How long the user was timed out for
+        duration: Duration,
+        /// When the user was timed out
+        timestamp: Option<SystemTime>,
+    },
+    /// A single message was deleted
+    MessageDeleted {
+        /// Login of the channel
+        channel_login: ChannelLogin<Cow<'a, str>>,
+        /// ID of the channel
+        room_id: Option<UserId<&'a str>>,
+        /// Login of the user whose message was deleted
+        target_login: Option<UserLogin<&'a str>>,
+        /// ID of the user whose message was deleted
+        target_user_id: Option<UserId<&'a str>>,
+        /// ID of the deleted message
+        target_msg_id: &'a str,
+        /// Text of the deleted message
+        message_text: Option<&'a str>,
+        /// When the message was deleted
+        timestamp: Option<SystemTime>,
+    },
+}
+
+impl<'a> ModerationEvent<'a> {
+    /// The moderation action a message represents, `None` if it isn't a
+    /// CLEARCHAT or CLEARMSG message or is malformed
+    pub fn from_any<C: Deref<Target = str>>(msg: &'a AnySemantic<C>) -> Option<Self> {
+        match msg {
+            AnySemantic::ClearChat(msg) => msg.event(),
+            AnySemantic::ClearMsg(msg) => msg.event(),
+            _ => None,
+        }
+    }
+
+    /// Login of the channel the action happened in
+    pub fn channel_login(&self) -> ChannelLogin<&str> {
+        match self {
+            Self::ChatCleared { channel_login, .. }
+            | Self::UserBanned { channel_login, .. }
+            | Self::UserTimedOut { channel_login, .. }
+            | Self::MessageDeleted { channel_login, .. } => channel_login.borrowed(),
+        }
+    }
+
+    /// Login of the user the action targets, `None` when the whole chat was
+    /// cleared
+    pub fn target_login(&self) -> Option<UserLogin<&'a str>> {
+        match self {
+            Self::ChatCleared { .. } => None,
+            Self::UserBanned { target_login, .. } | Self::UserTimedOut { target_login, .. } => {
+                Some(*target_login)
+            }
+            Self::MessageDeleted { target_login, .. } => *target_login,
+        }
+    }
+
+    /// When the action happened
+    pub fn timestamp(&self) -> Option<SystemTime> {
+        match self {
+            Self::ChatCleared { timestamp, .. }
+            | Self::UserBanned { timestamp, .. }
+            | Self::UserTimedOut { timestamp, .. }
+            | Self::MessageDeleted { timestamp, .. } => *timestamp,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        IrcMessage,
+        irc_message::AnySemantic,
+        login::{ChannelLogin, UserId, UserLogin},
+        time::millis_to_system_time,
+    };
+
+    use super::ModerationEvent;
+
+    fn any(raw: &str) -> AnySemantic {
+        AnySemantic::from(IrcMessage::new(String::from(raw)).unwrap())
+    }
+
+    #[test]
+    fn clearchat_events() {
+        assert_eq!(
+            ModerationEvent::from_any(&any(
+                "@room-id=12345678;tmi-sent-ts=1642715695392 :tmi.twitch.tv CLEARCHAT #dallas\r\n"
+            )),
+            Some(ModerationEvent::ChatCleared {
+                channel_login: ChannelLogin::new("dallas".into()).unwrap(),
+                room_id: UserId::new("12345678").ok(),
+                timestamp: millis_to_system_time(1642715695392),
+            })
+        );
+
+        assert_eq!(
+            ModerationEvent::from_any(&any(
+                "@room-id=12345678;target-user-id=87654321;tmi-sent-ts=1642715756806 :tmi.twitch.tv CLEARCHAT #dallas :ronni\r\n"
+            )),
+            Some(ModerationEvent::UserBanned {
+                channel_login: ChannelLogin::new("dallas".into()).unwrap(),
+                room_id: UserId::new("12345678").ok(),
+                target_login: UserLogin::new("ronni").unwrap(),
+                target_user_id: UserId::new("87654321").ok(),
+                timestamp: millis_to_system_time(1642715756806),
+            })
+        );
+
+        assert_eq!(
+            ModerationEvent::from_any(&any(
+                "@ban-duration=350;room-id=12345678;target-user-id=87654321;tmi-sent-ts=1642719320727 :tmi.twitch.tv CLEARCHAT #dallas :ronni\r\n"
+            )),
+            Some(ModerationEvent::UserTimedOut {
+                channel_login: ChannelLogin::new("dallas".into()).unwrap(),
+                room_id: UserId::new("12345678").ok(),
+                target_login: UserLogin::new("ronni").unwrap(),
+                target_user_id: UserId::new("87654321").ok(),
+                duration: Duration::from_secs(350),
+                timestamp: millis_to_system_time(1642719320727),
+            })
+        );
+
+        // used to panic
+        assert_eq!(
+            ModerationEvent::from_any(&any(
+                "@ban-duration=soon;room-id=1 :tmi.twitch.tv CLEARCHAT #dallas :ronni\r\n"
+            )),
+            None
+        );
+    }
+
+    #[test]
+    fn clearmsg_events() {
+        let msg = any(
+            "@login=foo;room-id=;target-msg-id=94e6c7ff-bf98-4faa-af5d-7ad633a158a9;tmi-sent-ts=1642720582342 :tmi.twitch.tv CLEARMSG #bar :what a great day\r\n",
+        );
+        let event = ModerationEvent::from_any(&msg).unwrap();
+        assert_eq!(
+            event,
+            ModerationEvent::MessageDeleted {
+                channel_login: ChannelLogin::new("bar".into()).unwrap(),
+                room_id: None,
+                target_login: UserLogin::new("foo").ok(),
+                target_user_id: None,
+                target_msg_id: "94e6c7ff-bf98-4faa-af5d-7ad633a158a9",
+                message_text: Some("what a great day"),
+                timestamp: millis_to_system_time(1642720582342),
+            }
+        );
+        assert_eq!(event.target_login().unwrap(), "foo");
+        assert_eq!(event.channel_login(), "bar");
+    }
+}
@@ -1,8 +1,13 @@
-use crate::irc_message::tags::OwnedTag;
+use std::{borrow::Cow, ops::Deref};
+
+use crate::{
+    irc_message::tags::OwnedTag,
+    login::{ChannelLogin, UserId},
+};
 
 use super::{Notice, util::msg_from_param};
 
-impl Notice {
+impl<C: Deref<Target = str>> Notice<C> {
     /// Text of the message, with invisible and special characters removed
     pub fn message_text(&self) -> &str {
         let msg_param = self
@@ -12,22 +17,17 @@ impl Notice {
         msg_from_param(msg_param)
     }
 
-    /// Login of the channel the NOTICE message relates to
-    pub fn channel_login(&self) -> &str {
-        let chan_param = self
-            .inner
-            .get_param(0)
-            .expect("no channel param in Notice elisWot");
-        if !chan_param.starts_with('#') {
-            panic!("channel param malformed")
-        } else {
-            chan_param.split_at(1).1
-        }
+    /// Login of the channel the NOTICE message relates to, `None` for notices
+    /// that aren't about a channel, which are sent to `*`
+    pub fn channel_login(&self) -> Option<ChannelLogin<Cow<'_, str>>> {
+        self.get_param(0)
+            .and_then(|p| ChannelLogin::from_irc_param(p).ok())
     }
 
     /// ID of the use the NOTICE message relates to
-    pub fn target_user_id(&self) -> Option<&str> {
+    pub fn target_user_id(&self) -> Option<UserId<&str>> {
         self.get_tag_raw(OwnedTag::TargetUserId)
+            .and_then(|id| UserId::new(id).ok())
     }
 
     /// The kind of notice that was received
@@ -0,0 +1,20 @@
+use std::ops::Deref;
+
+use super::{Numeric, Other};
+
+impl<C: Deref<Target = str>> Numeric<C> {
+    /// The numeric reply code of this message
+    pub fn code(&self) -> u16 {
+        match self.get_command() {
+            crate::IrcCommand::Numeric(code) => code,
+            _ => unreachable!("Numeric wrapper around a non-numeric command"),
+        }
+    }
+}
+
+impl<C: Deref<Target = str>> Other<C> {
+    /// Name of the command of this message
+    pub fn command_name(&self) -> &str {
+        self.raw_command()
+    }
+}
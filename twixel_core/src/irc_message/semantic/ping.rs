@@ -1,8 +1,10 @@
+use std::ops::Deref;
+
 use crate::MessageBuilder;
 
 use super::Ping;
 
-impl Ping {
+impl<C: Deref<Target = str>> Ping<C> {
     /// Creates a new [MessageBuilder](crate::MessageBuilder) containing a PONG
     /// for this PING
     pub fn respond(&'_ self) -> MessageBuilder<'_> {
@@ -1,8 +1,18 @@
-use crate::{MessageBuilder, irc_message::tags::OwnedTag, user::ChannelRoles};
+use std::{borrow::Cow, ops::Deref, range::Range};
 
-use super::{PrivMsg, util::msg_from_param};
+use crate::{
+    MessageBuilder,
+    irc_message::tags::OwnedTag,
+    login::{ChannelLogin, UserId, UserLogin},
+    user::ChannelRoles,
+};
 
-impl PrivMsg {
+use super::{
+    PrivMsg,
+    util::{msg_from_param, roles_from_message, source_roles_from_message},
+};
+
+impl<C: Deref<Target = str>> PrivMsg<C> {
     /// Text of the message, with invisible and special characters removed
     pub fn message_text(&self) -> &str {
         let msg_param = self
@@ -15,70 +25,202 @@ impl PrivMsg {
     /// Returns the senders's role in the channel this was sent in, depending on
     /// tags and badges
     pub fn sender_roles(&self) -> ChannelRoles {
-        let mut roles = ChannelRoles::empty();
+        roles_from_message(&self.inner)
+    }
 
-        roles.set(
-            ChannelRoles::Vip,
-            self.get_tag(OwnedTag::Vip)
-                .map(|t| t == "1")
-                .unwrap_or(false),
-        );
-        roles.set(
-            ChannelRoles::Moderator,
-            self.get_tag(OwnedTag::Mod)
-                .map(|t| t == "1")
-                .unwrap_or(false),
-        );
-        roles.set(
-            ChannelRoles::LeadModerator,
-            self.badges().any(|(n, _)| n == "lead_moderator"),
-        );
-        roles.set(
-            ChannelRoles::Subscriber,
-            self.get_tag(OwnedTag::Subscriber)
-                .map(|t| t == "1")
-                .unwrap_or(false),
-        );
-        roles.set(
-            ChannelRoles::Broadcaster,
-            self.badges().any(|(k, _v)| k == "broadcaster"),
-        );
+    /// Returns the sender's role in the channel a shared chat message was
+    /// originally sent in, `None` if this isn't a shared chat message
+    pub fn sender_source_roles(&self) -> Option<ChannelRoles> {
+        source_roles_from_message(&self.inner)
+    }
+
+    /// Shared Chat information of the message, `None` if it wasn't sent during a
+    /// Shared Chat session
+    pub fn shared_chat(&self) -> Option<SharedChat<'_>> {
+        Some(SharedChat {
+            source_room_id: self.get_tag_raw(OwnedTag::SourceRoomId)?,
+            source_message_id: self.get_tag_raw(OwnedTag::SourceId)?,
+        })
+    }
+
+    /// Whether the message was sent in the channel it was received in, which is
+    /// always the case outside of Shared Chat sessions
+    pub fn is_from_current_channel(&self) -> bool {
+        match (self.shared_chat(), self.channel_id()) {
+            (Some(shared), Some(room_id)) => room_id == shared.source_room_id,
+            _ => true,
+        }
+    }
+
+    /// ID of the message in the channel it was originally sent in, which is the
+    /// same for every copy of a Shared Chat message
+    pub fn source_message_id(&self) -> Option<&str> {
+        self.shared_chat()
+            .map(|s| s.source_message_id)
+            .or_else(|| self.get_tag_raw(OwnedTag::Id))
+    }
+
+    /// The message this one directly replies to, `None` if it isn't a reply
+    pub fn reply_parent(&self) -> Option<ReplyParent<'_>> {
+        Some(ReplyParent {
+            msg_id: self.get_tag_raw(OwnedTag::ReplyParentMsgId)?,
+            user_id: self.get_tag_raw(OwnedTag::ReplyParentUserId)?,
+            user_login: self.get_tag_raw(OwnedTag::ReplyParentUserLogin)?,
+            display_name: self
+                .get_tag(OwnedTag::ReplyParentDisplayName)
+                .unwrap_or_default(),
+            msg_body: self
+                .get_tag(OwnedTag::ReplyParentMsgBody)
+                .unwrap_or_default(),
+        })
+    }
+
+    /// The message that started the reply thread this message is in, `None` if
+    /// it isn't a reply
+    pub fn reply_thread(&self) -> Option<ReplyThread<'_>> {
+        Some(ReplyThread {
+            msg_id: self.get_tag_raw(OwnedTag::ReplyThreadParentMsgId)?,
+            user_id: self.get_tag_raw(OwnedTag::ReplyThreadParentuserId)?,
+            user_login: self.get_tag_raw(OwnedTag::ReplyThreadParentUserLogin)?,
+            display_name: self
+                .get_tag(OwnedTag::ReplyThreadParentDisplayName)
+                .unwrap_or_default(),
+        })
+    }
+
+    /// [message_text](Self::message_text) without the `@user` mention Twitch
+    /// inserts at the start of replies, the same as
+    /// [message_text](Self::message_text) if this isn't a reply or the mention
+    /// was removed by the user
+    pub fn text_without_reply_mention(&self) -> &str {
+        let text = self.message_text();
+        let Some(parent) = self.reply_parent() else {
+            return text;
+        };
 
-        roles
+        let Some(after_at) = text.strip_prefix('@') else {
+            return text;
+        };
+        let name_end = after_at.find(' ').unwrap_or(after_at.len());
+        let name = &after_at[..name_end];
+        if name.eq_ignore_ascii_case(parent.user_login) || name == parent.display_name {
+            after_at[name_end..].trim_start_matches(' ')
+        } else {
+            text
+        }
     }
 
     /// Login of the user who sent this PRIVMSG
-    pub fn sender_login(&self) -> Option<&str> {
-        self.get_username()
+    ///
+    /// Messages from Twitch, which carry a `room-id` tag, must have a valid
+    /// Twitch login, while the nickname is taken as is on other IRC networks
+    pub fn sender_login(&self) -> Option<UserLogin<&str>> {
+        if self.get_tag_raw(OwnedTag::RoomId).is_some() {
+            self.get_username().and_then(|l| UserLogin::new(l).ok())
+        } else {
+            self.get_nickname().and_then(|l| UserLogin::new_irc(l).ok())
+        }
     }
 
     /// ID of the user who sent this PRIVMSG
-    pub fn sender_id(&self) -> Option<&str> {
+    pub fn sender_id(&self) -> Option<UserId<&str>> {
         self.get_tag_raw(OwnedTag::UserId)
+            .and_then(|id| UserId::new(id).ok())
     }
 
     /// ID of the chat where this PRIVMSG was sent
-    pub fn channel_id(&self) -> Option<&str> {
+    pub fn channel_id(&self) -> Option<UserId<&str>> {
         self.get_tag_raw(OwnedTag::RoomId)
+            .and_then(|id| UserId::new(id).ok())
     }
 
-    /// Login of the chat where this PRIVMSG was sent
-    pub fn channel_login(&self) -> &str {
-        let chan_param = self
-            .inner
-            .get_param(0)
-            .expect("no channel param in PrivMsg elisWot");
-        if !chan_param.starts_with('#') {
-            panic!("channel param malformed")
-        } else {
-            chan_param.split_at(1).1
-        }
+    /// Login of the chat where this PRIVMSG was sent, `None` if the channel
+    /// param is missing or malformed
+    pub fn channel_login(&self) -> Option<ChannelLogin<Cow<'_, str>>> {
+        self.get_param(0)
+            .and_then(|p| ChannelLogin::from_irc_param(p).ok())
     }
 
     /// Whether the message is a /me command and should be highlighted/colored
     pub fn is_me(&self) -> bool {
         self.get_param(1)
-            .is_some_and(|p| p.starts_with(":\u{0001}ACTION ") && p.ends_with('\u{0001}'))
+            .is_some_and(|p| p.starts_with("\u{0001}ACTION ") && p.ends_with('\u{0001}'))
+    }
+
+    /// Splits [message_text](Self::message_text) into emotes, mentions, links,
+    /// cheermotes and plain text, in the order they appear in the message.
+    ///
+    /// Cheermotes are only recognized if the message has the `bits` tag and
+    /// the amounts of every cheermote-like word, such as `Cheer100`, add up to
+    /// it, otherwise they're all treated as plain text
+    pub fn fragments(&self) -> Vec<Fragment<'_>> {
+        let text = self.message_text();
+
+        // Twitch's emote positions are in code points, relative to the message
+        // text without the `/me` ACTION wrapping
+        let char_starts = text
+            .char_indices()
+            .map(|(idx, _)| idx)
+            .chain(std::iter::once(text.len()))
+            .collect::<Vec<usize>>();
+        let char_starts = &char_starts;
+
+        let mut emotes: Vec<(Range<usize>, &str)> = self
+            .emotes()
+            .flat_map(|(id, ranges)| {
+                ranges.into_iter().filter_map(move |r| {
+                    let start = *char_starts.get(r.start)?;
+                    let end = *char_starts.get(r.last.checked_add(1)?)?;
+                    (start < end).then_some(((start..end).into(), id))
+                })
+            })
+            .collect();
+        emotes.sort_unstable_by_key(|(r, _)| r.start);
+
+        let build = |has_bits: bool| {
+            let mut fragments = FragmentsBuilder {
+                text,
+                fragments: Vec::new(),
+            };
+            let mut pos = 0;
+            for (range, id) in &emotes {
+                // ignores overlapping emotes
+                if range.start < pos {
+                    continue;
+                }
+                fragments.push_words(pos, range.start, has_bits);
+                fragments.fragments.push(Fragment::Emote {
+                    id,
+                    name: &text[*range],
+                });
+                pos = range.end;
+            }
+            fragments.push_words(pos, text.len(), has_bits);
+            fragments.fragments
+        };
+
+        let Some(bits) = self
+            .get_tag_raw(OwnedTag::Bits)
+            .and_then(|b| b.parse::<u64>().ok())
+        else {
+            return build(false);
+        };
+
+        // words like `mp3` look like cheermotes too, so they're only trusted
+        // if they account for exactly the bits that were cheered
+        let fragments = build(true);
+        let cheered = fragments
+            .iter()
+            .map(|f| match f {
+                Fragment::Cheermote { bits, .. } => u64::from(*bits),
+                _ => 0,
+            })
+            .sum::<u64>();
+        if cheered == bits {
+            fragments
+        } else {
+            build(false)
+        }
     }
 
     /// The message ID to be used in the ReplyParentMsgId tag when replying
@@ -88,16 +230,363 @@ impl PrivMsg {
     }
 
     /// Make a new [MessageBuilder](crate::MessageBuilder) that is a reply PRIVMSG
-    /// to this
-    pub fn reply_to(&self, msg: &str) -> MessageBuilder<'_> {
+    /// to this, `None` if the channel param is missing or malformed
+    pub fn reply_to(&self, msg: &str) -> Option<MessageBuilder<'_>> {
         let reply_id = self.reply_to_id();
 
-        let builder = MessageBuilder::privmsg(self.channel_login(), msg);
+        let builder = MessageBuilder::privmsg(&self.channel_login()?, msg);
 
         if let Some(reply_id) = reply_id {
-            builder.add_tag(OwnedTag::ReplyParentMsgId, reply_id)
+            Some(builder.add_tag(OwnedTag::ReplyParentMsgId, reply_id))
         } else {
-            builder
+            Some(builder)
+        }
+    }
+}
+
+/// The message a [PrivMsg] directly replies to, see [PrivMsg::reply_parent]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplyParent<'a> {
+    /// ID of the parent message
+    pub msg_id: &'a str,
+    /// ID of the parent message's sender
+    pub user_id: &'a str,
+    /// Login of the parent message's sender
+    pub user_login: &'a str,
+    /// Display name of the parent message's sender
+    pub display_name: Cow<'a, str>,
+    /// Text of the parent message
+    pub msg_body: Cow<'a, str>,
+}
+
+/// The message that started a reply thread, see [PrivMsg::reply_thread]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplyThread<'a> {
+    /// ID of the thread's first message
+    pub msg_id: &'a str,
+    /// ID of the thread's first message's sender
+    pub user_id: &'a str,
+    /// Login of the thread's first message's sender
+    pub user_login: &'a str,
+    /// Display name of the thread's first message's sender
+    pub display_name: Cow<'a, str>,
+}
+
+/// Where a message sent during a Shared Chat session came from, see
+/// [PrivMsg::shared_chat]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SharedChat<'a> {
+    /// ID of the channel the message was originally sent in
+    pub source_room_id: &'a str,
+    /// ID of the message in the channel it was originally sent in
+    pub source_message_id: &'a str,
+}
+
+/// A part of a [PrivMsg]'s text, see [PrivMsg::fragments]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fragment<'a> {
+    /// Plain text, including whitespace
+    Text(&'a str),
+    /// A Twitch emote
+    Emote {
+        /// The emote's ID
+        id: &'a str,
+        /// The emote's name, as it appears in the message
+        name: &'a str,
+    },
+    /// A mention of another user, such as `@juliapixel`
+    Mention {
+        /// The mention as it appears in the message, including the `@`
+        text: &'a str,
+        /// The mentioned user's login, without the `@`
+        login: &'a str,
+    },
+    /// A link, such as `https://twitch.tv`
+    Url(&'a str),
+    /// A cheermote, such as `Cheer100`
+    Cheermote {
+        /// The cheermote as it appears in the message
+        text: &'a str,
+        /// The cheermote's prefix, such as `Cheer`
+        prefix: &'a str,
+        /// The amount of bits cheered with this cheermote
+        bits: u32,
+    },
+}
+
+impl<'a> Fragment<'a> {
+    /// The fragment's text, exactly as it appears in the message
+    pub fn as_str(&self) -> &'a str {
+        match self {
+            Fragment::Text(text) | Fragment::Url(text) => text,
+            Fragment::Emote { name, .. } => name,
+            Fragment::Mention { text, .. } | Fragment::Cheermote { text, .. } => text,
+        }
+    }
+}
+
+struct FragmentsBuilder<'a> {
+    text: &'a str,
+    fragments: Vec<Fragment<'a>>,
+}
+
+impl<'a> FragmentsBuilder<'a> {
+    /// pushes a text fragment, merging it with the previous one if possible
+    fn push_text(&mut self, start: usize, end: usize) {
+        if start == end {
+            return;
+        }
+        if let Some(Fragment::Text(prev)) = self.fragments.last_mut() {
+            let prev_end = prev.as_ptr() as usize - self.text.as_ptr() as usize + prev.len();
+            if prev_end == start {
+                let prev_start = prev_end - prev.len();
+                *prev = &self.text[prev_start..end];
+                return;
+            }
         }
+        self.fragments.push(Fragment::Text(&self.text[start..end]));
+    }
+
+    /// splits `text[start..end]` at spaces and pushes every word as the
+    /// appropriate fragment
+    fn push_words(&mut self, start: usize, end: usize, has_bits: bool) {
+        let mut pos = start;
+        while pos < end {
+            let word_end = memchr::memchr(b' ', &self.text.as_bytes()[pos..end])
+                .map(|i| i + pos)
+                .unwrap_or(end);
+            self.push_word(pos, word_end, has_bits);
+            let next = (word_end + 1).min(end);
+            self.push_text(word_end, next);
+            pos = next;
+        }
+    }
+
+    fn push_word(&mut self, start: usize, end: usize, has_bits: bool) {
+        let word = &self.text[start..end];
+
+        if let Some(login) = word.strip_prefix('@') {
+            let login_len = login
+                .bytes()
+                .take_while(|b| b.is_ascii_alphanumeric() || *b == b'_')
+                .count();
+            if login_len > 0 {
+                let mention_end = start + 1 + login_len;
+                self.fragments.push(Fragment::Mention {
+                    text: &self.text[start..mention_end],
+                    login: &self.text[start + 1..mention_end],
+                });
+                self.push_text(mention_end, end);
+                return;
+            }
+        }
+
+        if is_url_start(word) {
+            let url = word.trim_end_matches(['.', ',', '!', '?', ':', ';', ')', '\'', '"']);
+            if url.len() > "www.".len() {
+                self.fragments.push(Fragment::Url(url));
+                self.push_text(start + url.len(), end);
+                return;
+            }
+        }
+
+        if has_bits {
+            let digits = word
+                .bytes()
+                .rev()
+                .take_while(|b| b.is_ascii_digit())
+                .count();
+            let (prefix, amount) = word.split_at(word.len() - digits);
+            if !prefix.is_empty()
+                && prefix.bytes().all(|b| b.is_ascii_alphanumeric())
+                && let Ok(bits @ 1..) = amount.parse()
+            {
+                self.fragments.push(Fragment::Cheermote {
+                    text: word,
+                    prefix,
+                    bits,
+                });
+                return;
+            }
+        }
+
+        self.push_text(start, end);
+    }
+}
+
+fn is_url_start(word: &str) -> bool {
+    ["http://", "https://", "www."].iter().any(|scheme| {
+        word.get(..scheme.len())
+            .is_some_and(|s| s.eq_ignore_ascii_case(scheme))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{IrcMessage, irc_message::SemanticIrcMessage};
+
+    use super::{Fragment, PrivMsg, SharedChat};
+
+    fn privmsg(raw: &str) -> PrivMsg {
+        PrivMsg::from_message(IrcMessage::new(String::from(raw)).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn malformed_channel() {
+        let msg = privmsg("@id=abc :a!a@a.tmi.twitch.tv PRIVMSG ab :hi\r\n");
+        assert_eq!(msg.channel_login(), None);
+        assert!(msg.reply_to("hey").is_none());
+
+        let msg = privmsg("@id=abc :a!a@a.tmi.twitch.tv PRIVMSG #ab :hi\r\n");
+        assert_eq!(msg.channel_login().unwrap(), "ab");
+        assert!(msg.reply_to("hey").is_some());
+    }
+
+    #[test]
+    fn sender_login() {
+        let twitch = privmsg("@room-id=1 :Ferris!Ferris@ferris.tmi.twitch.tv PRIVMSG #ab :hi\r\n");
+        assert_eq!(twitch.sender_login(), None);
+        let twitch = privmsg("@room-id=1 :ferris!ferris@ferris.tmi.twitch.tv PRIVMSG #ab :hi\r\n");
+        assert_eq!(twitch.sender_login().unwrap(), "ferris");
+
+        let irc = privmsg(":Ferris[m]!f@host PRIVMSG #Rust :hi\r\n");
+        assert_eq!(irc.sender_login().unwrap(), "Ferris[m]");
+        assert_eq!(irc.channel_login().unwrap(), "rust");
+    }
+
+    #[test]
+    fn replies() {
+        let reply = privmsg(
+            "@id=3;reply-parent-display-name=Juliapixel;reply-parent-msg-body=hi\\sthere\\:\\s:);reply-parent-msg-id=2;reply-parent-user-id=10;reply-parent-user-login=juliapixel;reply-thread-parent-display-name=Foo;reply-thread-parent-msg-id=1;reply-thread-parent-user-id=11;reply-thread-parent-user-login=foo :a!a@a.tmi.twitch.tv PRIVMSG #room :@Juliapixel  translate this\r\n",
+        );
+
+        let parent = reply.reply_parent().unwrap();
+        assert_eq!(parent.msg_id, "2");
+        assert_eq!(parent.user_id, "10");
+        assert_eq!(parent.user_login, "juliapixel");
+        assert_eq!(parent.display_name, "Juliapixel");
+        assert_eq!(parent.msg_body, "hi there; :)");
+
+        let thread = reply.reply_thread().unwrap();
+        assert_eq!(thread.msg_id, "1");
+        assert_eq!(thread.user_login, "foo");
+        assert_eq!(thread.display_name, "Foo");
+
+        assert_eq!(reply.text_without_reply_mention(), "translate this");
+
+        let not_reply = privmsg(":a!a@a.tmi.twitch.tv PRIVMSG #room :@juliapixel hi\r\n");
+        assert_eq!(not_reply.reply_parent(), None);
+        assert_eq!(not_reply.text_without_reply_mention(), "@juliapixel hi");
+    }
+
+    #[test]
+    fn shared_chat() {
+        let origin = privmsg(
+            "@id=abc;room-id=1;source-id=abc;source-room-id=1 :a!a@a.tmi.twitch.tv PRIVMSG #one :hi\r\n",
+        );
+        let copy = privmsg(
+            "@id=def;room-id=2;source-id=abc;source-room-id=1 :a!a@a.tmi.twitch.tv PRIVMSG #two :hi\r\n",
+        );
+        let unshared = privmsg("@id=ghi;room-id=2 :a!a@a.tmi.twitch.tv PRIVMSG #two :hi\r\n");
+
+        assert_eq!(
+            copy.shared_chat(),
+            Some(SharedChat {
+                source_room_id: "1",
+                source_message_id: "abc"
+            })
+        );
+        assert!(origin.is_from_current_channel());
+        assert!(!copy.is_from_current_channel());
+        assert_eq!(origin.source_message_id(), copy.source_message_id());
+
+        assert_eq!(unshared.shared_chat(), None);
+        assert!(unshared.is_from_current_channel());
+        assert_eq!(unshared.source_message_id(), Some("ghi"));
+    }
+
+    #[test]
+    fn emote_fragments() {
+        // the emoji takes up 4 bytes but a single code point
+        let msg = privmsg(
+            "@emotes=25:8-12,14-18;id=1 :a!a@a.tmi.twitch.tv PRIVMSG #room :\u{1F600} hi :) Kappa Kappa!\r\n",
+        );
+        assert_eq!(
+            msg.fragments(),
+            [
+                Fragment::Text("\u{1F600} hi :) "),
+                Fragment::Emote {
+                    id: "25",
+                    name: "Kappa"
+                },
+                Fragment::Text(" "),
+                Fragment::Emote {
+                    id: "25",
+                    name: "Kappa"
+                },
+                Fragment::Text("!"),
+            ]
+        );
+    }
+
+    #[test]
+    fn action_fragments() {
+        let msg = privmsg(
+            "@emotes=25:0-4 :a!a@a.tmi.twitch.tv PRIVMSG #room :\u{0001}ACTION Kappa waves at @Juliapixel, https://twitch.tv.\u{0001}\r\n",
+        );
+        assert!(msg.is_me());
+        assert_eq!(
+            msg.fragments(),
+            [
+                Fragment::Emote {
+                    id: "25",
+                    name: "Kappa"
+                },
+                Fragment::Text(" waves at "),
+                Fragment::Mention {
+                    text: "@Juliapixel",
+                    login: "Juliapixel"
+                },
+                Fragment::Text(", "),
+                Fragment::Url("https://twitch.tv"),
+                Fragment::Text("."),
+            ]
+        );
+    }
+
+    #[test]
+    fn cheer_fragments() {
+        let msg = privmsg(
+            "@bits=150;emotes= :a!a@a.tmi.twitch.tv PRIVMSG #room :Cheer100 nice 4Head50 Cheer0 @ 2 2024\r\n",
+        );
+        assert_eq!(
+            msg.fragments(),
+            [
+                Fragment::Cheermote {
+                    text: "Cheer100",
+                    prefix: "Cheer",
+                    bits: 100
+                },
+                Fragment::Text(" nice "),
+                Fragment::Cheermote {
+                    text: "4Head50",
+                    prefix: "4Head",
+                    bits: 50
+                },
+                Fragment::Text(" Cheer0 @ 2 2024"),
+            ]
+        );
+
+        // the amounts don't add up to the bits cheered
+        let not_cheers = privmsg(
+            "@bits=100;emotes= :a!a@a.tmi.twitch.tv PRIVMSG #room :my abc123 mp3 from 2024\r\n",
+        );
+        assert_eq!(
+            not_cheers.fragments(),
+            [Fragment::Text("my abc123 mp3 from 2024")]
+        );
+
+        let no_bits = privmsg(":a!a@a.tmi.twitch.tv PRIVMSG #room :Cheer100\r\n");
+        assert_eq!(no_bits.fragments(), [Fragment::Text("Cheer100")]);
     }
 }
@@ -0,0 +1,107 @@
+use std::{borrow::Cow, ops::Deref};
+
+use crate::login::{ChannelLogin, UserLogin};
+
+use super::{Kick, Mode, Quit, Topic};
+
+impl<C: Deref<Target = str>> Kick<C> {
+    /// Channel the user was kicked from
+    pub fn channel_login(&self) -> Option<ChannelLogin<Cow<'_, str>>> {
+        self.get_param(0)
+            .and_then(|p| ChannelLogin::from_irc_param(p).ok())
+    }
+
+    /// Nickname of the user who was kicked
+    pub fn target_login(&self) -> Option<UserLogin<&str>> {
+        self.get_param(1).and_then(|p| UserLogin::new_irc(p).ok())
+    }
+
+    /// Nickname of the user who kicked the target
+    pub fn kicker_login(&self) -> Option<UserLogin<&str>> {
+        self.get_nickname().and_then(|n| UserLogin::new_irc(n).ok())
+    }
+
+    /// Why the user was kicked
+    pub fn reason(&self) -> Option<&str> {
+        self.get_param(2)
+    }
+}
+
+impl<C: Deref<Target = str>> Topic<C> {
+    /// Channel whose topic was changed
+    pub fn channel_login(&self) -> Option<ChannelLogin<Cow<'_, str>>> {
+        self.get_param(0)
+            .and_then(|p| ChannelLogin::from_irc_param(p).ok())
+    }
+
+    /// The new topic, empty if it was cleared
+    pub fn topic(&self) -> &str {
+        self.get_param(1).unwrap_or_default()
+    }
+}
+
+impl<C: Deref<Target = str>> Mode<C> {
+    /// Channel or nickname whose modes were changed
+    pub fn target(&self) -> Option<&str> {
+        self.get_param(0)
+    }
+
+    /// The mode changes, such as `+o`, followed by their arguments
+    pub fn changes(&self) -> impl Iterator<Item = &str> {
+        self.params().skip(1)
+    }
+}
+
+impl<C: Deref<Target = str>> Quit<C> {
+    /// Nickname of the user who quit
+    pub fn user_login(&self) -> Option<UserLogin<&str>> {
+        self.get_nickname().and_then(|n| UserLogin::new_irc(n).ok())
+    }
+
+    /// Why the user quit
+    pub fn reason(&self) -> Option<&str> {
+        self.get_param(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{IrcMessage, irc_message::AnySemantic};
+
+    fn any(raw: &str) -> AnySemantic {
+        AnySemantic::from(IrcMessage::new(String::from(raw)).unwrap())
+    }
+
+    #[test]
+    fn standard_commands() {
+        let AnySemantic::Kick(kick) = any(":Op!op@host KICK #Rust-Lang ferris :no crabs\r\n")
+        else {
+            panic!("not a KICK")
+        };
+        assert_eq!(kick.channel_login().unwrap(), "rust-lang");
+        assert_eq!(kick.target_login().unwrap(), "ferris");
+        assert_eq!(kick.kicker_login().unwrap(), "Op");
+        assert_eq!(kick.reason(), Some("no crabs"));
+
+        let AnySemantic::Topic(topic) = any(":op!op@host TOPIC #rust :hello world\r\n") else {
+            panic!("not a TOPIC")
+        };
+        assert_eq!(topic.channel_login().unwrap(), "rust");
+        assert_eq!(topic.topic(), "hello world");
+
+        let AnySemantic::Mode(mode) = any(":op!op@host MODE #rust +ov ferris corro\r\n") else {
+            panic!("not a MODE")
+        };
+        assert_eq!(mode.target(), Some("#rust"));
+        assert_eq!(
+            mode.changes().collect::<Vec<_>>(),
+            ["+ov", "ferris", "corro"]
+        );
+
+        let AnySemantic::Quit(quit) = any(":ferris!f@host QUIT :Quit: bye\r\n") else {
+            panic!("not a QUIT")
+        };
+        assert_eq!(quit.user_login().unwrap(), "ferris");
+        assert_eq!(quit.reason(), Some("Quit: bye"));
+    }
+}
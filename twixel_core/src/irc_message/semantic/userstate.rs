@@ -1,55 +1,25 @@
-use crate::{irc_message::tags::OwnedTag, user::ChannelRoles};
+use std::{borrow::Cow, ops::Deref};
 
-use super::UserState;
+use crate::{login::ChannelLogin, user::ChannelRoles};
 
-impl UserState {
-    /// Login of the channel the USERSTATE message relates to
-    pub fn channel_login(&self) -> &str {
+use super::{UserState, util::roles_from_message};
+
+impl<C: Deref<Target = str>> UserState<C> {
+    /// Login of the channel the USERSTATE message relates to, `None` if the
+    /// channel param is missing or malformed
+    pub fn channel_login(&self) -> Option<ChannelLogin<Cow<'_, str>>> {
         self.get_param(0)
-            .expect("malformed channel login param")
-            .split_at(1)
-            .1
+            .and_then(|p| ChannelLogin::from_irc_param(p).ok())
     }
 
     /// Returns the user's role in a chanel, depending on tags and badges
     pub fn roles(&self) -> ChannelRoles {
-        let mut roles = ChannelRoles::empty();
-
-        roles.set(
-            ChannelRoles::Vip,
-            self.get_tag(OwnedTag::Vip)
-                .map(|t| t == "1")
-                .unwrap_or(false),
-        );
-        roles.set(
-            ChannelRoles::Moderator,
-            self.get_tag(OwnedTag::Mod)
-                .map(|t| t == "1")
-                .unwrap_or(false),
-        );
-        roles.set(
-            ChannelRoles::LeadModerator,
-            self.badges().any(|(n, _)| n == "lead_moderator"),
-        );
-        roles.set(
-            ChannelRoles::Subscriber,
-            self.get_tag(OwnedTag::Subscriber)
-                .map(|t| t == "1")
-                .unwrap_or(false),
-        );
-        roles.set(
-            ChannelRoles::Broadcaster,
-            self.badges().any(|(k, _v)| k == "broadcaster"),
-        );
-
-        roles
+        roles_from_message(&self.inner)
     }
 
     /// Returns whether the user is a moderator or lead moderator
     pub fn is_mod(&self) -> bool {
-        self.get_tag(OwnedTag::Mod).is_some()
-            || self
-                .badges()
-                .any(|(k, _)| k == "lead_moderator" || k == "moderator")
+        self.roles()
+            .intersects(ChannelRoles::Moderator | ChannelRoles::LeadModerator)
     }
 }
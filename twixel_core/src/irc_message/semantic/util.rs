@@ -1,3 +1,7 @@
+use std::ops::Deref;
+
+use crate::{IrcMessage, irc_message::tags::OwnedTag, user::ChannelRoles};
+
 pub(crate) fn msg_from_param(param_str: &str) -> &str {
     if param_str.starts_with("\u{0001}ACTION ") && param_str.ends_with('\u{0001}') {
         &param_str[("\u{0001}ACTION ".len())..(param_str.len() - 1)]
@@ -5,3 +9,34 @@ pub(crate) fn msg_from_param(param_str: &str) -> &str {
         param_str
     }
 }
+
+/// Roles of the user who sent a message, from its badges and the `mod`, `vip`
+/// and `subscriber` tags
+pub(crate) fn roles_from_message<C: Deref<Target = str>>(msg: &IrcMessage<C>) -> ChannelRoles {
+    let mut roles = ChannelRoles::from_badges(msg.typed_badges());
+
+    let flag = |tag| msg.get_tag_raw(tag).is_some_and(|t| t == "1");
+    roles.set(
+        ChannelRoles::Vip,
+        roles.contains(ChannelRoles::Vip) || flag(OwnedTag::Vip),
+    );
+    roles.set(
+        ChannelRoles::Moderator,
+        roles.contains(ChannelRoles::Moderator) || flag(OwnedTag::Mod),
+    );
+    roles.set(
+        ChannelRoles::Subscriber,
+        roles.contains(ChannelRoles::Subscriber) || flag(OwnedTag::Subscriber),
+    );
+
+    roles
+}
+
+/// Roles of the user who sent a shared chat message in the channel it was
+/// originally sent in, from its `source-badges`
+pub(crate) fn source_roles_from_message<C: Deref<Target = str>>(
+    msg: &IrcMessage<C>,
+) -> Option<ChannelRoles> {
+    msg.get_tag_raw(OwnedTag::SourceBadges)?;
+    Some(ChannelRoles::from_badges(msg.typed_source_badges()))
+}
@@ -0,0 +1,178 @@
+use std::{ops::Deref, range::Range, rc::Rc, sync::Arc};
+
+/// String storage that can back an [IrcMessage](super::IrcMessage), such as
+/// [String], `&str`, [`Arc<str>`] or [BytesStr]
+pub trait MessageStorage: Deref<Target = str> + Sized {
+    /// Returns a new storage with only the contents of `range`, sharing the
+    /// underlying buffer if this storage supports it, or copying it otherwise
+    fn slice(&self, range: Range<usize>) -> Self;
+
+    /// Converts into an owned [String]
+    fn into_string(self) -> String {
+        String::from(&*self)
+    }
+}
+
+impl MessageStorage for String {
+    fn slice(&self, range: Range<usize>) -> Self {
+        String::from(&self[range])
+    }
+
+    fn into_string(self) -> String {
+        self
+    }
+}
+
+impl MessageStorage for &str {
+    fn slice(&self, range: Range<usize>) -> Self {
+        let src: &str = self;
+        &src[range]
+    }
+}
+
+impl MessageStorage for Box<str> {
+    fn slice(&self, range: Range<usize>) -> Self {
+        Box::from(&self[range])
+    }
+
+    fn into_string(self) -> String {
+        String::from(self)
+    }
+}
+
+impl MessageStorage for Arc<str> {
+    fn slice(&self, range: Range<usize>) -> Self {
+        Arc::from(&self[range])
+    }
+}
+
+impl MessageStorage for Rc<str> {
+    fn slice(&self, range: Range<usize>) -> Self {
+        Rc::from(&self[range])
+    }
+}
+
+#[cfg(feature = "bytes")]
+pub use bytes_str::BytesStr;
+
+#[cfg(feature = "bytes")]
+mod bytes_str {
+    use std::{ops::Deref, range::Range, rc::Rc, str::Utf8Error, sync::Arc};
+
+    use bytes::Bytes;
+
+    use super::MessageStorage;
+
+    /// A reference counted UTF-8 string backed by [Bytes], slicing it doesn't
+    /// copy, so many messages can be parsed out of a single buffer and shared
+    /// cheaply
+    #[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct BytesStr(
+        // invariant: always valid UTF-8
+        Bytes,
+    );
+
+    impl BytesStr {
+        /// Creates a new [BytesStr] from a static string without copying
+        pub const fn from_static(value: &'static str) -> Self {
+            Self(Bytes::from_static(value.as_bytes()))
+        }
+
+        /// Tries to create a new [BytesStr] from [Bytes], errors if it isn't
+        /// valid UTF-8
+        pub fn from_bytes(value: Bytes) -> Result<Self, Utf8Error> {
+            std::str::from_utf8(&value)?;
+            Ok(Self(value))
+        }
+
+        /// Returns the string as a `&str`
+        pub fn as_str(&self) -> &str {
+            // SAFETY: the inner buffer is always valid UTF-8, it is either
+            // validated upon creation or comes from an already valid string, and
+            // it is only ever sliced at char boundaries
+            unsafe { std::str::from_utf8_unchecked(&self.0) }
+        }
+
+        /// Returns the underlying [Bytes]
+        pub fn into_bytes(self) -> Bytes {
+            self.0
+        }
+    }
+
+    impl MessageStorage for BytesStr {
+        fn slice(&self, range: Range<usize>) -> Self {
+            // makes sure range is at char boundaries, panicking otherwise
+            let _ = &self.as_str()[range];
+            Self(self.0.slice(core::ops::Range::from(range)))
+        }
+    }
+
+    impl Deref for BytesStr {
+        type Target = str;
+
+        fn deref(&self) -> &Self::Target {
+            self.as_str()
+        }
+    }
+
+    impl AsRef<str> for BytesStr {
+        fn as_ref(&self) -> &str {
+            self.as_str()
+        }
+    }
+
+    impl std::fmt::Debug for BytesStr {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            std::fmt::Debug::fmt(self.as_str(), f)
+        }
+    }
+
+    impl std::fmt::Display for BytesStr {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str(self.as_str())
+        }
+    }
+
+    impl From<String> for BytesStr {
+        fn from(value: String) -> Self {
+            Self(Bytes::from(value))
+        }
+    }
+
+    impl From<&str> for BytesStr {
+        fn from(value: &str) -> Self {
+            Self(Bytes::copy_from_slice(value.as_bytes()))
+        }
+    }
+
+    #[cfg(feature = "connection")]
+    impl From<async_tungstenite::tungstenite::Utf8Bytes> for BytesStr {
+        fn from(value: async_tungstenite::tungstenite::Utf8Bytes) -> Self {
+            Self(Bytes::from(value))
+        }
+    }
+
+    impl From<BytesStr> for String {
+        fn from(value: BytesStr) -> Self {
+            String::from(value.as_str())
+        }
+    }
+
+    impl From<BytesStr> for Box<str> {
+        fn from(value: BytesStr) -> Self {
+            Box::from(value.as_str())
+        }
+    }
+
+    impl From<BytesStr> for Arc<str> {
+        fn from(value: BytesStr) -> Self {
+            Arc::from(value.as_str())
+        }
+    }
+
+    impl From<BytesStr> for Rc<str> {
+        fn from(value: BytesStr) -> Self {
+            Rc::from(value.as_str())
+        }
+    }
+}
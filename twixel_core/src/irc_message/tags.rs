@@ -1,14 +1,10 @@
 #[cfg(feature = "chrono")]
 use chrono::{DateTime, Utc};
-use hashbrown::HashMap;
 use memchr::memchr_iter;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
-use std::{
-    borrow::Cow,
-    range::Range,
-};
+use std::{borrow::Cow, range::Range, sync::OnceLock};
 use thiserror::Error;
 
 use super::iter::BadgeIter;
@@ -68,7 +64,7 @@ pub(crate) fn unescape_tag_value(val: &str) -> Cow<'_, str> {
 pub(crate) fn escape_tag_value(val: &str) -> Cow<'_, str> {
     let mut last = 0;
     let mut out = String::new();
-    for (idx, escapable) in val.match_indices(['\\', ' ', '\r', '\n', ';']) {
+    for (idx, escapable) in val.match_indices(['\\', ' ', '\r', '\n', ';', '\0']) {
         out.push_str(&val[last..idx]);
         out.push_str(match escapable {
             "\\" => "\\\\",
@@ -76,6 +72,8 @@ pub(crate) fn escape_tag_value(val: &str) -> Cow<'_, str> {
             "\r" => "\\r",
             "\n" => "\\n",
             ";" => "\\:",
+            // NUL isn't allowed anywhere in a message
+            "\0" => "",
             _ => unreachable!(),
         });
         last = idx + 1;
@@ -145,12 +143,30 @@ macro_rules! raw_tags {
                 }
             }
 
-            pub fn as_str<'a>(&self, src: &'a str) -> &'a str {
+            /// Amount of known tags
+            pub const KNOWN_COUNT: usize = [$($key),+].len();
+
+            const KNOWN: [Self; Self::KNOWN_COUNT] = [$(Self::$name),+];
+
+            /// Dense index of a known tag, `None` for unknown tags
+            #[inline]
+            pub fn index(&self) -> Option<usize> {
+                #[allow(clippy::enum_variant_names)]
+                enum Discriminant {
+                    $($name),+
+                }
+
                 match self {
-                    $(Self::$name => $key,)*
-                    Self::Unknown(r) => &src[*r]
+                    $(Self::$name => Some(Discriminant::$name as usize),)+
+                    Self::Unknown(_) => None
                 }
             }
+
+            /// Known tag from its [index](Self::index)
+            #[inline]
+            pub fn from_index(idx: usize) -> Self {
+                Self::KNOWN[idx]
+            }
         }
 
         #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -176,6 +192,15 @@ macro_rules! raw_tags {
                     Self::Unknown(_) => panic!(concat!("Cannot convert from ", stringify!($tag::Unknown), " to ", stringify!($raw_tag)))
                 }
             }
+
+            /// Dense index of a known tag, `None` for unknown tags
+            #[inline]
+            pub(crate) fn index(&self) -> Option<usize> {
+                match self {
+                    Self::Unknown(_) => None,
+                    known => known.to_raw().index(),
+                }
+            }
         }
 
         impl From<&str> for $tag {
@@ -323,7 +348,13 @@ raw_tags!(
     "msg-param-mass-gift-count" = MsgParamMassGiftCount,
     "msg-param-gift-month-being-redeemed" = MsgParamGiftMonthBeingRedeemed,
     "msg-param-anon-gift" = MsgParamAnonGift,
-    "custom-reward-id" = CustomRewardId
+    "custom-reward-id" = CustomRewardId,
+    /// added by recent-messages services to messages sent before the client
+    /// joined
+    "historical" = Historical,
+    /// when a recent-messages service received the message, in milliseconds
+    /// since the unix epoch
+    "rm-received-ts" = RmReceivedTs
 );
 
 /// Error enum for erros when parsing tags
@@ -340,33 +371,50 @@ pub enum IRCTagParseError {
     UnknownIdentifier(String),
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Compact byte range into the message, so that the tag index stays small
+/// enough to be kept inline
+type Span = Range<u32>;
+
+#[inline]
+fn span(range: Range<usize>) -> Span {
+    // messages are never anywhere close to 4GiB long
+    (range.start as u32..range.end as u32).into()
+}
+
+#[inline]
+fn unspan(span: Span) -> Range<usize> {
+    (span.start as usize..span.end as usize).into()
+}
+
+/// The tags of an [IrcMessage](crate::IrcMessage), which are only split and
+/// indexed the first time they are accessed
+#[derive(Debug, Clone)]
 pub(crate) struct RawIrcTags {
-    /// first item is the [RawTag] enum and the second is the position of the tag's value
-    pub(crate) tags: HashMap<RawTag, Range<usize>>,
-    pub(crate) unknown: SmallVec<[(Range<usize>, Range<usize>); 4]>,
+    /// position of the tags in the message, without the leading `@` and the
+    /// trailing space
+    span: Range<usize>,
+    index: OnceLock<TagIndex>,
 }
 
-impl RawIrcTags {
-    /// tries to parse a [RawIrcTags] from the tags part of an IRC message, without
-    /// the leading `@` and the trailing space
-    pub(crate) fn new(raw: &str, raw_start_idx: usize, raw_end_idx: usize) -> Option<Self> {
-        let mut tags = HashMap::new();
-        let mut unknown = SmallVec::new();
-
-        let mut insert_tag =
-            |start: Range<usize>, end: Range<usize>| match RawTag::parse(raw, start) {
-                RawTag::Unknown(range) => {
-                    let key = &raw[range];
-                    match unknown.binary_search_by_key(&key, |(k, _)| &raw[*k]) {
-                        Ok(found) => unknown[found] = (range, end),
-                        Err(idx) => unknown.insert(idx, (range, end)),
-                    }
-                }
-                t => {
-                    tags.insert(t, end);
-                }
-            };
+#[derive(Debug, Clone)]
+struct TagIndex {
+    /// for every known [RawTag], one plus its position in `known`, or 0 if it
+    /// isn't present
+    slots: [u8; RawTag::KNOWN_COUNT],
+    /// known tags, by their [index](RawTag::index), and their values, in order
+    /// of appearance
+    known: SmallVec<[(u8, Span); 24]>,
+    /// unknown tags and their values, sorted by key
+    unknown: SmallVec<[(Span, Span); 4]>,
+}
+
+impl TagIndex {
+    fn new(raw: &str, raw_start_idx: usize, raw_end_idx: usize) -> Self {
+        let mut index = Self {
+            slots: [0; RawTag::KNOWN_COUNT],
+            known: SmallVec::new(),
+            unknown: SmallVec::new(),
+        };
 
         // position of last found start of tag
         let mut last_pos: usize = raw_start_idx;
@@ -377,9 +425,13 @@ impl RawIrcTags {
             // positon of current parsed tag's divider
             if let Some(divider) = memchr::memchr(b'=', &raw.as_bytes()[last_pos..pos - 1]) {
                 let divider = divider + last_pos;
-                insert_tag((last_pos..divider).into(), (divider + 1..pos - 1).into());
+                index.insert(
+                    raw,
+                    (last_pos..divider).into(),
+                    (divider + 1..pos - 1).into(),
+                );
             } else {
-                insert_tag((last_pos..pos - 1).into(), (pos - 1..pos - 1).into());
+                index.insert(raw, (last_pos..pos - 1).into(), (pos - 1..pos - 1).into());
             }
 
             last_pos = pos;
@@ -387,53 +439,119 @@ impl RawIrcTags {
 
         // parsing the last tag
         if let Some(divider) =
-            memchr::memchr(b'=', &raw.as_bytes()[last_pos..]).map(|d| d + last_pos)
+            memchr::memchr(b'=', &raw.as_bytes()[last_pos..raw_end_idx]).map(|d| d + last_pos)
         {
-            insert_tag(
+            index.insert(
+                raw,
                 (last_pos..divider).into(),
                 (divider + 1..raw_end_idx).into(),
             )
         } else {
-            insert_tag(
+            index.insert(
+                raw,
                 (last_pos..raw_end_idx).into(),
                 (raw_end_idx..raw_end_idx).into(),
             );
         }
 
-        Some(Self { tags, unknown })
+        index
+    }
+
+    /// inserts a tag, replacing the value of a previous tag with the same key
+    #[inline]
+    fn insert(&mut self, raw: &str, key: Range<usize>, value: Range<usize>) {
+        match RawTag::parse(raw, key).index() {
+            Some(idx) => match self.slots[idx] {
+                0 => {
+                    self.known.push((idx as u8, span(value)));
+                    self.slots[idx] = self.known.len() as u8;
+                }
+                slot => self.known[slot as usize - 1].1 = span(value),
+            },
+            None => {
+                let key_str = &raw[key];
+                match self
+                    .unknown
+                    .binary_search_by_key(&key_str, |(k, _)| &raw[unspan(*k)])
+                {
+                    Ok(found) => self.unknown[found] = (span(key), span(value)),
+                    Err(idx) => self.unknown.insert(idx, (span(key), span(value))),
+                }
+            }
+        }
+    }
+
+    #[inline]
+    fn get_known(&self, idx: usize) -> Option<Range<usize>> {
+        match self.slots[idx] {
+            0 => None,
+            slot => Some(unspan(self.known[slot as usize - 1].1)),
+        }
+    }
+
+    #[inline]
+    fn get_unknown(&self, src: &str, key: &str) -> Option<Range<usize>> {
+        self.unknown
+            .binary_search_by_key(&key, |(k, _)| &src[unspan(*k)])
+            .ok()
+            .map(|found| unspan(self.unknown[found].1))
+    }
+
+    fn get_by_str(&self, src: &str, tag: &str) -> Option<Range<usize>> {
+        match OwnedTag::from(tag).index() {
+            Some(idx) => self.get_known(idx),
+            None => self.get_unknown(src, tag),
+        }
+    }
+}
+
+impl RawIrcTags {
+    /// creates a [RawIrcTags] from the tags part of an IRC message, without
+    /// the leading `@` and the trailing space. The tags themselves are only
+    /// parsed when first accessed
+    pub(crate) fn new(raw: &str, raw_start_idx: usize, raw_end_idx: usize) -> Option<Self> {
+        raw.get(raw_start_idx..raw_end_idx)?;
+        Some(Self {
+            span: (raw_start_idx..raw_end_idx).into(),
+            index: OnceLock::new(),
+        })
+    }
+
+    #[inline]
+    fn index(&self, src: &str) -> &TagIndex {
+        self.index
+            .get_or_init(|| TagIndex::new(src, self.span.start, self.span.end))
     }
 
     pub fn value_eq(&self, src: &str, other: &Self, other_src: &str) -> bool {
-        self.tags.len() == other.tags.len()
-            && self.tags.iter().all(|(k, v)| {
-                other
-                    .tags
-                    .get(k)
-                    .is_some_and(|ov| other_src[*ov] == src[*v])
+        let (lhs, rhs) = (self.index(src), other.index(other_src));
+        lhs.known.len() == rhs.known.len()
+            && lhs.known.iter().all(|(k, v)| {
+                rhs.get_known(*k as usize)
+                    .is_some_and(|ov| other_src[ov] == src[unspan(*v)])
             })
-            && self.unknown.len() == other.unknown.len()
-            && self.unknown.iter().all(|(k, v)| {
-                other
-                    .unknown
-                    .binary_search_by_key(&(&src[*k], &src[*v]), |(k, v)| {
-                        (&other_src[*k], &other_src[*v])
-                    })
-                    .is_ok()
+            && lhs.unknown.len() == rhs.unknown.len()
+            && lhs.unknown.iter().all(|(k, v)| {
+                rhs.get_unknown(other_src, &src[unspan(*k)])
+                    .is_some_and(|ov| other_src[ov] == src[unspan(*v)])
             })
     }
 
+    fn get(&self, src: &str, tag: &OwnedTag) -> Option<Range<usize>> {
+        let index = self.index(src);
+        match tag {
+            OwnedTag::Unknown(tag) => index.get_unknown(src, tag),
+            known => index.get_known(known.index()?),
+        }
+    }
+
     /// Retrieves the value associated with the given tag.
     /// # Returns
     /// - `None` if the tag is not present
     /// - An empty string if the tag is present but no key is present
     /// - The value associated with the tag, with escape sequences removed
     pub fn get_value<'a>(&self, src: &'a str, tag: OwnedTag) -> Option<Cow<'a, str>> {
-        let found = if let OwnedTag::Unknown(tag) = tag {
-            self.unknown.iter().find(|(k, _)| src[*k] == tag)?.1
-        } else {
-            *self.tags.get(&tag.to_raw())?
-        };
-        src.get(found).map(unescape_tag_value)
+        src.get(self.get(src, &tag)?).map(unescape_tag_value)
     }
 
     /// Retrieves the value associated with the given tag.
@@ -442,12 +560,7 @@ impl RawIrcTags {
     /// - An empty string if the tag is present but no key is present
     /// - The value associated with the tag, with escape sequences not removed
     pub fn get_raw_value<'a>(&self, src: &'a str, tag: OwnedTag) -> Option<&'a str> {
-        let found = if let OwnedTag::Unknown(tag) = tag {
-            self.unknown.iter().find(|(k, _)| src[*k] == tag)?.1
-        } else {
-            *self.tags.get(&tag.to_raw())?
-        };
-        src.get(found)
+        src.get(self.get(src, &tag)?)
     }
 
     /// Retrieves the value associated with the given tag.
@@ -456,18 +569,8 @@ impl RawIrcTags {
     /// - An empty string if the tag is present but no key is present
     /// - The value associated with the tag, with escape sequences removed
     pub fn get_value_by_str<'a>(&self, src: &'a str, tag: &str) -> Option<Cow<'a, str>> {
-        let found = self
-            .tags
-            .iter()
-            .find(|t| t.0.as_str(src) == tag)
-            .map(|t| *t.1)
-            .or_else(|| {
-                self.unknown
-                    .iter()
-                    .find(|(k, _)| &src[*k] == tag)
-                    .map(|t| t.1)
-            })?;
-        src.get(found).map(unescape_tag_value)
+        src.get(self.index(src).get_by_str(src, tag)?)
+            .map(unescape_tag_value)
     }
 
     /// Retrieves the value associated with the given tag.
@@ -476,22 +579,11 @@ impl RawIrcTags {
     /// - An empty string if the tag is present but no key is present
     /// - The value associated with the tag, with escape sequences not removed
     pub fn get_raw_value_by_str<'a>(&self, src: &'a str, tag: &str) -> Option<&'a str> {
-        let found = self
-            .tags
-            .iter()
-            .find(|t| t.0.as_str(src) == tag)
-            .map(|t| *t.1)
-            .or_else(|| {
-                self.unknown
-                    .iter()
-                    .find(|(k, _)| &src[*k] == tag)
-                    .map(|t| t.1)
-            })?;
-        src.get(found)
+        src.get(self.index(src).get_by_str(src, tag)?)
     }
 
     pub fn iter<'a>(&'a self, src: &'a str) -> TagsIter<'a> {
-        TagsIter::new(self, src)
+        TagsIter::new(self.index(src), src)
     }
 
     pub fn badge_iter<'a>(&'a self, src: &'a str) -> BadgeIter<'a> {
@@ -515,13 +607,21 @@ impl RawIrcTags {
         }
     }
 
+    /// milliseconds since the unix epoch from `tmi-sent-ts`, `sent-ts` or the
+    /// IRCv3 `time` tag, in that order
+    pub fn get_timestamp_millis(&self, src: &str) -> Option<i64> {
+        self.get_raw_value(src, OwnedTag::TmiSentTs)
+            .or_else(|| self.get_raw_value(src, OwnedTag::SentTs))
+            .and_then(|ts| ts.parse().ok())
+            .or_else(|| {
+                self.get_raw_value_by_str(src, "time")
+                    .and_then(crate::time::parse_server_time)
+            })
+    }
+
     #[cfg(feature = "chrono")]
     pub fn get_timestamp(&self, src: &str) -> Option<DateTime<Utc>> {
-        let ts = self
-            .get_value(src, OwnedTag::TmiSentTs)?
-            .parse::<i64>()
-            .ok()?;
-        DateTime::<Utc>::from_timestamp(ts / 1000, 0)
+        DateTime::<Utc>::from_timestamp_millis(self.get_timestamp_millis(src)?)
     }
 }
 
@@ -529,16 +629,16 @@ impl RawIrcTags {
 #[derive(Debug, Clone)]
 pub struct TagsIter<'a> {
     src: &'a str,
-    tags: hashbrown::hash_map::Iter<'a, RawTag, Range<usize>>,
-    unknown: core::slice::Iter<'a, (Range<usize>, Range<usize>)>,
+    known: core::slice::Iter<'a, (u8, Span)>,
+    unknown: core::slice::Iter<'a, (Span, Span)>,
 }
 
 impl<'a> TagsIter<'a> {
-    fn new(raw: &'a RawIrcTags, src: &'a str) -> Self {
+    fn new(index: &'a TagIndex, src: &'a str) -> Self {
         Self {
             src,
-            tags: raw.tags.iter(),
-            unknown: raw.unknown.iter(),
+            known: index.known.iter(),
+            unknown: index.unknown.iter(),
         }
     }
 }
@@ -547,15 +647,28 @@ impl<'a> Iterator for TagsIter<'a> {
     type Item = (OwnedTag, &'a str);
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.tags
+        self.known
             .next()
-            .map(|(rt, range)| (rt.to_owned_tag(self.src), &self.src[*range]))
+            .map(|(idx, v)| {
+                (
+                    RawTag::from_index(*idx as usize).to_owned_tag(self.src),
+                    &self.src[unspan(*v)],
+                )
+            })
             .or_else(|| {
-                self.unknown
-                    .next()
-                    .map(|(k, v)| (RawTag::Unknown(*k).to_owned_tag(self.src), &self.src[*v]))
+                self.unknown.next().map(|(k, v)| {
+                    (
+                        RawTag::Unknown(unspan(*k)).to_owned_tag(self.src),
+                        &self.src[unspan(*v)],
+                    )
+                })
             })
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.known.len() + self.unknown.len();
+        (len, Some(len))
+    }
 }
 
 #[cfg(test)]
@@ -7,14 +7,38 @@
 // utf-8 char boundary checking is cool
 #![allow(clippy::sliced_string_as_bytes)]
 
+/// Authentication methods for connecting to Twitch IRC
 pub mod auth;
+/// A high level client that handles connections, rate limiting and channel
+/// state
+#[cfg(feature = "tokio-runtime")]
+pub mod client;
+/// Websocket connections to Twitch IRC
 #[cfg(feature = "connection")]
 pub mod connection;
+/// IRCv3 message parsing and building
 pub mod irc_message;
+/// Validated logins and IDs of channels and users
+pub mod login;
+/// Names of the metrics recorded by connections and pools
+pub mod metrics;
+/// Recording raw chat logs and replaying them
+pub mod recording;
+/// Async runtimes connections run on, abstracting their sockets and timers
+pub mod runtime;
+/// A local mock of Twitch IRC to test clients against
+#[cfg(all(feature = "tokio-runtime", any(test, feature = "testing")))]
+pub mod testing;
+/// Message timestamps and clock synchronization with the server
+pub mod time;
+/// Utilities related to chat users
 pub mod user;
 
+#[cfg(feature = "tokio-runtime")]
+pub use crate::client::Client;
 #[cfg(feature = "connection")]
 pub use crate::connection::{Connection, ConnectionPool};
 pub use crate::irc_message::builder::MessageBuilder;
 pub use crate::irc_message::command::IrcCommand;
 pub use crate::irc_message::message::IrcMessage;
+pub use crate::login::{ChannelLogin, UserId, UserLogin};
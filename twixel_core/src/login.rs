@@ -0,0 +1,401 @@
+use std::{
+    borrow::{Borrow, Cow},
+    fmt::Display,
+    ops::Deref,
+    str::FromStr,
+};
+
+use thiserror::Error;
+
+/// Maximum length of a Twitch login
+const MAX_LOGIN_LEN: usize = 25;
+/// Maximum length of a channel name or nickname on other IRC networks, most
+/// servers use much shorter limits
+const MAX_IRC_NAME_LEN: usize = 64;
+
+/// Error returned when a login or user ID is invalid
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum IdentifierError {
+    /// The login or ID was empty
+    #[error("logins and IDs can't be empty")]
+    Empty,
+    /// The login was longer than allowed
+    #[error("logins can be at most {max} characters long, this one is {len}")]
+    TooLong {
+        /// Length of the login
+        len: usize,
+        /// Maximum length it could have been
+        max: usize,
+    },
+    /// The login or ID contained a character it can't contain
+    #[error("invalid character {0:?}")]
+    InvalidCharacter(char),
+}
+
+fn validate_login(login: &str) -> Result<(), IdentifierError> {
+    if login.is_empty() {
+        return Err(IdentifierError::Empty);
+    }
+    if let Some(c) = login
+        .chars()
+        .find(|c| !matches!(c, 'a'..='z' | '0'..='9' | '_'))
+    {
+        return Err(IdentifierError::InvalidCharacter(c));
+    }
+    if login.len() > MAX_LOGIN_LEN {
+        return Err(IdentifierError::TooLong {
+            len: login.len(),
+            max: MAX_LOGIN_LEN,
+        });
+    }
+    Ok(())
+}
+
+/// Looser validation for the channel names and nicknames of IRC networks other
+/// than Twitch, which only rejects characters that would break IRC messages
+fn validate_irc_name(name: &str) -> Result<(), IdentifierError> {
+    if name.is_empty() {
+        return Err(IdentifierError::Empty);
+    }
+    if let Some(c) = name
+        .chars()
+        .find(|c| matches!(c, ' ' | ',' | ':' | '!' | '@' | '\x07' | '\0' | '\r' | '\n'))
+    {
+        return Err(IdentifierError::InvalidCharacter(c));
+    }
+    if name.len() > MAX_IRC_NAME_LEN {
+        return Err(IdentifierError::TooLong {
+            len: name.len(),
+            max: MAX_IRC_NAME_LEN,
+        });
+    }
+    Ok(())
+}
+
+fn validate_user_id(id: &str) -> Result<(), IdentifierError> {
+    if id.is_empty() {
+        return Err(IdentifierError::Empty);
+    }
+    match id.chars().find(|c| !c.is_ascii_digit()) {
+        Some(c) => Err(IdentifierError::InvalidCharacter(c)),
+        None => Ok(()),
+    }
+}
+
+macro_rules! identifier {
+    (
+        $(#[$meta:meta])*
+        $name:ident, $validate:ident, $deserialize:ident, |$raw:ident| $normalize:expr
+    ) => {
+        $(#[$meta])*
+        ///
+        /// `S` is the storage of the identifier, `&str` when it is borrowed
+        /// from a message and [String] when it is owned
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize), serde(transparent))]
+        pub struct $name<S = String>(S);
+
+        impl<S: Deref<Target = str>> $name<S> {
+            /// Checks that `value` is valid as is, without normalizing it
+            pub fn new(value: S) -> Result<Self, IdentifierError> {
+                $validate(&value)?;
+                Ok(Self(value))
+            }
+
+            /// The identifier as a [str]
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+
+            /// The identifier borrowed from this one
+            pub fn borrowed(&self) -> $name<&str> {
+                $name(&self.0)
+            }
+
+            /// Copies the identifier into an owned one
+            pub fn into_owned(self) -> $name {
+                $name(self.0.to_owned())
+            }
+
+            /// Returns the underlying storage
+            pub fn into_inner(self) -> S {
+                self.0
+            }
+        }
+
+        impl $name {
+            /// Normalizes `value` and checks that it is valid
+            pub fn parse(value: &str) -> Result<Self, IdentifierError> {
+                let $raw = value.trim();
+                let normalized: String = $normalize;
+                Self::new(normalized)
+            }
+        }
+
+        impl<S: Deref<Target = str>> Deref for $name<S> {
+            type Target = str;
+
+            fn deref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl<S: Deref<Target = str>> AsRef<str> for $name<S> {
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl Borrow<str> for $name {
+            fn borrow(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl<S: Deref<Target = str>> Display for $name<S> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl<S: Deref<Target = str>> PartialEq<str> for $name<S> {
+            fn eq(&self, other: &str) -> bool {
+                &*self.0 == other
+            }
+        }
+
+        impl<S: Deref<Target = str>> PartialEq<&str> for $name<S> {
+            fn eq(&self, other: &&str) -> bool {
+                &*self.0 == *other
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = IdentifierError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Self::parse(s)
+            }
+        }
+
+        impl TryFrom<&str> for $name {
+            type Error = IdentifierError;
+
+            fn try_from(value: &str) -> Result<Self, Self::Error> {
+                Self::parse(value)
+            }
+        }
+
+        impl TryFrom<String> for $name {
+            type Error = IdentifierError;
+
+            fn try_from(value: String) -> Result<Self, Self::Error> {
+                Self::parse(&value)
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(value: $name) -> Self {
+                value.0
+            }
+        }
+
+        impl From<$name<&str>> for $name {
+            fn from(value: $name<&str>) -> Self {
+                value.into_owned()
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de> serde::Deserialize<'de> for $name {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let value = String::deserialize(deserializer)?;
+                Self::$deserialize(&value).map_err(serde::de::Error::custom)
+            }
+        }
+    };
+}
+
+identifier! {
+    /// The login of a Twitch channel, such as `juliapixel`
+    ///
+    /// Logins are lowercase and only contain ASCII letters, numbers and
+    /// underscores, parsing one lowercases it and strips a leading `#`.
+    /// Channels of other IRC networks are made with
+    /// [new_irc](ChannelLogin::new_irc) and [parse_irc](ChannelLogin::parse_irc),
+    /// which deserializing also uses
+    ChannelLogin, validate_login, parse_irc, |raw| raw.strip_prefix('#').unwrap_or(raw).to_lowercase()
+}
+
+identifier! {
+    /// The login of a Twitch user, such as `juliapixel`
+    ///
+    /// Logins are lowercase and only contain ASCII letters, numbers and
+    /// underscores, parsing one lowercases it and strips a leading `@`.
+    /// Nicknames on other IRC networks are made with
+    /// [new_irc](UserLogin::new_irc) and [parse_irc](UserLogin::parse_irc),
+    /// which deserializing also uses
+    UserLogin, validate_login, parse_irc, |raw| raw.strip_prefix('@').unwrap_or(raw).to_lowercase()
+}
+
+identifier! {
+    /// The numeric ID of a Twitch user or channel
+    UserId, validate_user_id, parse, |raw| raw.to_owned()
+}
+
+impl<S: Deref<Target = str>> ChannelLogin<S> {
+    /// Checks that `value` is a valid channel name on any IRC network, without
+    /// the leading `#`
+    pub fn new_irc(value: S) -> Result<Self, IdentifierError> {
+        validate_irc_name(&value)?;
+        Ok(Self(value))
+    }
+
+    /// The channel as an IRC param, such as `#juliapixel`
+    pub fn irc_param(&self) -> String {
+        format!("#{}", &*self.0)
+    }
+
+    /// The login of the channel's owner
+    pub fn into_user(self) -> UserLogin<S> {
+        UserLogin(self.0)
+    }
+}
+
+impl ChannelLogin {
+    /// Normalizes `value` and checks that it is a valid channel name on any IRC
+    /// network, such as `#Rust-Lang`
+    pub fn parse_irc(value: &str) -> Result<Self, IdentifierError> {
+        let value = value.trim();
+        Self::new_irc(value.strip_prefix('#').unwrap_or(value).to_lowercase())
+    }
+}
+
+impl<'a> ChannelLogin<Cow<'a, str>> {
+    /// Reads a channel from an IRC param, such as `#juliapixel`, lowercasing
+    /// it like [parse_irc](ChannelLogin::parse_irc) does. The param is only
+    /// copied if it isn't lowercase already, which Twitch's always are
+    ///
+    /// Channels of any IRC network are accepted, so the channel isn't
+    /// guaranteed to be a valid Twitch login
+    pub fn from_irc_param(param: &'a str) -> Result<Self, IdentifierError> {
+        let name = param.strip_prefix('#').unwrap_or_default();
+        if name.chars().any(char::is_uppercase) {
+            Self::new_irc(Cow::Owned(name.to_lowercase()))
+        } else {
+            Self::new_irc(Cow::Borrowed(name))
+        }
+    }
+}
+
+impl<S: Deref<Target = str>> UserLogin<S> {
+    /// Checks that `value` is a valid nickname on any IRC network
+    pub fn new_irc(value: S) -> Result<Self, IdentifierError> {
+        validate_irc_name(&value)?;
+        Ok(Self(value))
+    }
+
+    /// The channel owned by this user
+    pub fn into_channel(self) -> ChannelLogin<S> {
+        ChannelLogin(self.0)
+    }
+}
+
+impl UserLogin {
+    /// Normalizes `value` and checks that it is a valid nickname on any IRC
+    /// network, such as `@Ferris[m]`
+    pub fn parse_irc(value: &str) -> Result<Self, IdentifierError> {
+        let value = value.trim();
+        Self::new_irc(value.strip_prefix('@').unwrap_or(value).to_lowercase())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hashbrown::HashSet;
+
+    use super::{ChannelLogin, IdentifierError, UserId, UserLogin};
+
+    #[test]
+    fn login_validation() {
+        let chan = ChannelLogin::parse(" #JuliaPixel").unwrap();
+        assert_eq!(chan, "juliapixel");
+        assert_eq!(chan.irc_param(), "#juliapixel");
+        assert_eq!(chan.to_string(), "juliapixel");
+        assert_eq!(
+            "@Julia_Pixel".parse::<UserLogin>().unwrap().into_channel(),
+            "julia_pixel"
+        );
+
+        assert_eq!(ChannelLogin::parse("#"), Err(IdentifierError::Empty));
+        assert_eq!(ChannelLogin::parse(""), Err(IdentifierError::Empty));
+        assert_eq!(
+            ChannelLogin::parse("#foo,#bar"),
+            Err(IdentifierError::InvalidCharacter(','))
+        );
+        assert_eq!(
+            UserLogin::parse("a".repeat(26).as_str()),
+            Err(IdentifierError::TooLong { len: 26, max: 25 })
+        );
+        // new doesn't normalize
+        assert_eq!(
+            ChannelLogin::new("Foo"),
+            Err(IdentifierError::InvalidCharacter('F'))
+        );
+
+        assert_eq!(
+            ChannelLogin::from_irc_param("#foo").unwrap().borrowed(),
+            ChannelLogin::parse("foo").unwrap().borrowed()
+        );
+        assert!(ChannelLogin::from_irc_param("foo").is_err());
+        assert_eq!(
+            ChannelLogin::from_irc_param("#Rust-Lang")
+                .unwrap()
+                .borrowed(),
+            ChannelLogin::parse_irc("rust-lang").unwrap().borrowed()
+        );
+
+        // other IRC networks
+        assert_eq!(ChannelLogin::parse_irc("#Rust-Lang").unwrap(), "rust-lang");
+        assert!(ChannelLogin::parse("#rust-lang").is_err());
+        assert!(ChannelLogin::parse_irc("#a,#b").is_err());
+        assert!(UserLogin::new_irc("Ferris[m]").is_ok());
+        assert_eq!(UserLogin::parse_irc("@Ferris[m]").unwrap(), "ferris[m]");
+        assert_eq!(
+            ChannelLogin::parse_irc(&"a".repeat(65)),
+            Err(IdentifierError::TooLong { len: 65, max: 64 })
+        );
+
+        assert!(UserId::parse("12345678").is_ok());
+        assert_eq!(
+            UserId::parse("1234a"),
+            Err(IdentifierError::InvalidCharacter('a'))
+        );
+        assert_eq!(UserId::new(""), Err(IdentifierError::Empty));
+    }
+
+    #[test]
+    fn login_lookup() {
+        let channels: HashSet<ChannelLogin> = ["foo", "bar"]
+            .into_iter()
+            .map(|c| c.parse().unwrap())
+            .collect();
+        assert!(channels.contains("foo"));
+        assert!(!channels.contains("baz"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize_irc_names() {
+        let chan: ChannelLogin = serde_json::from_str(r##""#Rust-Lang""##).unwrap();
+        assert_eq!(chan, "rust-lang");
+        let user: UserLogin = serde_json::from_str(r#""Ferris[m]""#).unwrap();
+        assert_eq!(user, "ferris[m]");
+        // round trips
+        let json = serde_json::to_string(&chan).unwrap();
+        assert_eq!(serde_json::from_str::<ChannelLogin>(&json).unwrap(), chan);
+
+        assert!(serde_json::from_str::<ChannelLogin>(r#""a,b""#).is_err());
+        assert!(serde_json::from_str::<UserId>(r#""1234a""#).is_err());
+    }
+}
@@ -0,0 +1,204 @@
+use ::metrics::{Unit, describe_counter, describe_gauge, describe_histogram};
+
+/// Counter of IRC messages received, labeled by `command`. Unknown commands
+/// share the `other` label and numerics without a command of their own are
+/// grouped by their first digit, like `4xx`
+pub const MESSAGES_RECEIVED: &str = "twixel_messages_received_total";
+/// Counter of IRC messages sent, labeled by `command` like
+/// [MESSAGES_RECEIVED]
+pub const MESSAGES_SENT: &str = "twixel_messages_sent_total";
+/// Counter of received lines that weren't valid IRC messages
+pub const PARSE_ERRORS: &str = "twixel_parse_errors_total";
+/// Counter of connections restarted
+pub const RECONNECTS: &str = "twixel_reconnects_total";
+/// Histogram of the time between sending a `PING` and receiving its `PONG`,
+/// in seconds. Connections send one periodically, see
+/// [set_keepalive](crate::Connection::set_keepalive)
+pub const PING_LATENCY: &str = "twixel_ping_latency_seconds";
+/// Counter of `NOTICE`s received, labeled by their `msg-id` as `kind`
+pub const NOTICES: &str = "twixel_notices_total";
+/// Gauge of the messages waiting to be sent, labeled by `queue`
+pub const SEND_QUEUE_DEPTH: &str = "twixel_send_queue_depth";
+/// Gauge of the connections in a pool
+pub const POOL_CONNECTIONS: &str = "twixel_pool_connections";
+/// Counter of messages dropped by a pool's [Dedup](crate::connection::Dedup)
+pub const DUPLICATES_DROPPED: &str = "twixel_duplicates_dropped_total";
+/// Counter of connections of a pool that failed to receive
+pub const RECEIVE_ERRORS: &str = "twixel_receive_errors_total";
+/// Counter of received messages a `Client` dropped because
+/// they weren't read in time
+pub const EVENTS_DROPPED: &str = "twixel_events_dropped_total";
+
+/// Describes the metrics recorded by this crate to the installed recorder.
+///
+/// Metrics are recorded through the [metrics](::metrics) facade and do
+/// nothing until a recorder, such as a Prometheus exporter, is installed with
+/// [set_global_recorder](::metrics::set_global_recorder)
+pub fn describe() {
+    describe_counter!(MESSAGES_RECEIVED, "IRC messages received");
+    describe_counter!(MESSAGES_SENT, "IRC messages sent");
+    describe_counter!(PARSE_ERRORS, "received lines that weren't valid IRC");
+    describe_counter!(RECONNECTS, "connections restarted");
+    describe_histogram!(
+        PING_LATENCY,
+        Unit::Seconds,
+        "time between sending a PING and receiving its PONG"
+    );
+    describe_counter!(NOTICES, "NOTICEs received");
+    describe_gauge!(SEND_QUEUE_DEPTH, "messages waiting to be sent");
+    describe_gauge!(POOL_CONNECTIONS, "connections in the pool");
+    describe_counter!(
+        DUPLICATES_DROPPED,
+        "messages received on several connections"
+    );
+    describe_counter!(RECEIVE_ERRORS, "connections that failed to receive");
+    describe_counter!(
+        EVENTS_DROPPED,
+        "received messages that weren't read in time"
+    );
+}
+
+/// Value of the `command` label of a message, out of a fixed set so servers
+/// can't make up new ones
+#[cfg(feature = "connection")]
+pub(crate) fn command_label(command: &crate::IrcCommand) -> ::metrics::SharedString {
+    const NUMERICS: [&str; 10] = [
+        "0xx", "1xx", "2xx", "3xx", "4xx", "5xx", "6xx", "7xx", "8xx", "9xx",
+    ];
+
+    ::metrics::SharedString::const_str(match command {
+        crate::IrcCommand::Numeric(n) => NUMERICS[usize::from(*n / 100).min(9)],
+        crate::IrcCommand::Other(_) => "other",
+        known => known.known_name().unwrap_or("other"),
+    })
+}
+
+#[cfg(all(test, feature = "tokio-runtime"))]
+mod tests {
+    use std::time::Duration;
+
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+    use crate::{
+        ChannelLogin, Connection, IrcCommand,
+        auth::Anonymous,
+        connection::{ConnectionEvent, LifecycleEvent},
+        testing::MockServer,
+    };
+
+    #[tokio::test]
+    async fn connection_metrics() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        // tests run on a single thread
+        let _guard = ::metrics::set_default_local_recorder(&recorder);
+
+        let server = MockServer::start().await.unwrap();
+        let mut conn: Connection<_> = Connection::with_profile(
+            [ChannelLogin::parse("foo").unwrap()],
+            Anonymous,
+            server.profile(),
+        );
+        conn.start().await.unwrap();
+        server.wait_for_command(IrcCommand::Join).await;
+        server.send_raw(":tmi.twitch.tv");
+        server.send_raw("@msg-id=msg_banned :tmi.twitch.tv NOTICE #foo :You are banned");
+        server.send_raw(":tmi.twitch.tv 433 * justinfan :Nickname is already in use");
+        server.send_raw(":tmi.twitch.tv SOMETHINGNEW #foo :hi");
+        server.send_privmsg("foo", "bar", "hello");
+        loop {
+            if let Ok(msg) = conn.receive().await
+                && msg.get_command() == IrcCommand::PrivMsg
+            {
+                break;
+            }
+        }
+
+        // counters are reset by taking a snapshot
+        let snapshot = snapshotter.snapshot().into_vec();
+        let counter = |name: &str, label: Option<(&str, &str)>| {
+            snapshot.iter().find_map(|(key, _, _, value)| {
+                let key = key.key();
+                let labeled =
+                    label.is_none_or(|(k, v)| key.labels().any(|l| l.key() == k && l.value() == v));
+                match value {
+                    DebugValue::Counter(count) if key.name() == name && labeled => Some(*count),
+                    _ => None,
+                }
+            })
+        };
+        assert_eq!(counter(super::PARSE_ERRORS, None), Some(1));
+        assert_eq!(
+            counter(super::NOTICES, Some(("kind", "msg_banned"))),
+            Some(1)
+        );
+        assert_eq!(
+            counter(super::MESSAGES_RECEIVED, Some(("command", "PRIVMSG"))),
+            Some(1)
+        );
+        assert_eq!(
+            counter(super::MESSAGES_RECEIVED, Some(("command", "4xx"))),
+            Some(1)
+        );
+        assert_eq!(
+            counter(super::MESSAGES_RECEIVED, Some(("command", "other"))),
+            Some(1)
+        );
+        assert_eq!(
+            counter(super::MESSAGES_SENT, Some(("command", "JOIN"))),
+            Some(1)
+        );
+    }
+
+    #[tokio::test]
+    async fn ping_latency() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let _guard = ::metrics::set_default_local_recorder(&recorder);
+
+        let server = MockServer::start().await.unwrap();
+        let mut conn: Connection<_> = Connection::with_profile(
+            [ChannelLogin::parse("foo").unwrap()],
+            Anonymous,
+            server.profile(),
+        );
+        conn.set_keepalive(Some(Duration::from_millis(50)));
+        let mut events = conn.subscribe();
+        conn.start().await.unwrap();
+
+        // the keepalive PING is answered
+        let measured = async {
+            loop {
+                match events.try_recv() {
+                    Ok(LifecycleEvent {
+                        event: ConnectionEvent::LatencyMeasured(latency),
+                        ..
+                    }) => return latency,
+                    Ok(_) => {}
+                    Err(_) => {
+                        conn.receive().await.unwrap();
+                    }
+                }
+            }
+        };
+        let latency = tokio::time::timeout(Duration::from_secs(5), measured)
+            .await
+            .unwrap();
+
+        let recorded = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .find_map(|(key, _, _, value)| match value {
+                DebugValue::Histogram(values) if key.key().name() == super::PING_LATENCY => {
+                    Some(values)
+                }
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(
+            recorded.iter().map(|v| v.into_inner()).collect::<Vec<_>>(),
+            [latency.as_secs_f64()]
+        );
+    }
+}
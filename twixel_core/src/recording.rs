@@ -0,0 +1,524 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use futures_util::Stream;
+use thiserror::Error;
+
+use crate::{
+    IrcMessage,
+    auth::is_public_authenticate,
+    irc_message::error::IrcMessageParseError,
+    runtime::{self, Timer},
+    time::system_time_to_millis,
+};
+
+/// Size a log file can grow to before it is rotated, 64 MiB
+const DEFAULT_MAX_FILE_SIZE: u64 = 64 * 1024 * 1024;
+/// Amount of rotated log files kept besides the current one
+const DEFAULT_MAX_FILES: usize = 10;
+
+/// Whether a recorded line was received from or sent to the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Received from the server, written as `<`
+    Received,
+    /// Sent to the server, written as `>`
+    Sent,
+}
+
+impl Direction {
+    fn as_str(self) -> &'static str {
+        match self {
+            Direction::Received => "<",
+            Direction::Sent => ">",
+        }
+    }
+}
+
+/// A single line of a chat log
+///
+/// Recorded lines are written as `<unix millis> <connection index> <direction>
+/// <raw IRC line>`, such as `1680318910689 0 < PING :tmi.twitch.tv`. Plain raw
+/// IRC lines, like the ones in `logs/logs.txt`, are read as received by the
+/// first connection with no timestamp
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogEntry {
+    /// When the line was received or sent, in milliseconds since the unix epoch
+    pub timestamp: Option<i64>,
+    /// Index of the connection in its [ConnectionPool](crate::ConnectionPool)
+    pub connection: usize,
+    /// Whether the line was received or sent
+    pub direction: Direction,
+    /// The raw IRC line, without `\r\n`
+    pub line: String,
+}
+
+impl LogEntry {
+    /// Reads a line of a chat log
+    pub fn parse(line: &str) -> Self {
+        let line = line.trim_end_matches(['\r', '\n']);
+        let mut parts = line.splitn(4, ' ');
+        let recorded = (|| {
+            let timestamp = parts.next()?.parse().ok()?;
+            let connection = parts.next()?.parse().ok()?;
+            let direction = match parts.next()? {
+                "<" => Direction::Received,
+                ">" => Direction::Sent,
+                _ => return None,
+            };
+            Some(Self {
+                timestamp: Some(timestamp),
+                connection,
+                direction,
+                line: parts.next()?.to_owned(),
+            })
+        })();
+
+        recorded.unwrap_or_else(|| Self {
+            timestamp: None,
+            connection: 0,
+            direction: Direction::Received,
+            line: line.to_owned(),
+        })
+    }
+}
+
+impl LogEntry {
+    /// Parses the recorded line along with the index of its connection
+    fn into_message(self) -> Result<(IrcMessage, usize), ReplayError> {
+        // lines are recorded without the CRLF they were received with
+        let line = self.line + "\r\n";
+        Ok((IrcMessage::new(line)?, self.connection))
+    }
+}
+
+impl std::fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.timestamp.unwrap_or_default(),
+            self.connection,
+            self.direction.as_str(),
+            self.line
+        )
+    }
+}
+
+/// Writes every raw IRC line received and sent by connections to log files,
+/// rotating them once they grow too large.
+///
+/// The current log is written to the path it was created with, and rotated
+/// logs are renamed to `<path>.1`, `<path>.2` and so on, `<path>.1` being the
+/// most recent one. `PASS` lines are recorded with their token redacted, and
+/// SASL `AUTHENTICATE` lines with their credentials redacted.
+///
+/// Lines are buffered and written in batches, the buffer is written out when
+/// the log is rotated, [flushed](Recorder::flush) or dropped
+#[derive(Debug)]
+pub struct Recorder {
+    path: PathBuf,
+    file: BufWriter<File>,
+    written: u64,
+    max_file_size: u64,
+    max_files: usize,
+}
+
+impl Recorder {
+    /// Creates a [Recorder] that appends to the log at `path`
+    pub fn create(path: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let path = path.into();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let written = file.metadata()?.len();
+        Ok(Self {
+            path,
+            file: BufWriter::new(file),
+            written,
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            max_files: DEFAULT_MAX_FILES,
+        })
+    }
+
+    /// Sets the size in bytes a log can grow to before it is rotated, 64 MiB by
+    /// default
+    pub fn max_file_size(mut self, bytes: u64) -> Self {
+        self.max_file_size = bytes;
+        self
+    }
+
+    /// Sets how many rotated logs are kept besides the current one, 10 by
+    /// default
+    pub fn max_files(mut self, count: usize) -> Self {
+        self.max_files = count;
+        self
+    }
+
+    /// The path of the current log
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Records a raw IRC line received or sent by the connection at
+    /// `connection` at the current time
+    pub fn record(
+        &mut self,
+        connection: usize,
+        direction: Direction,
+        line: &str,
+    ) -> std::io::Result<()> {
+        let line = line.trim_end_matches(['\r', '\n']);
+        let line = match direction {
+            Direction::Sent if line.starts_with("PASS ") => "PASS [redacted]",
+            Direction::Sent
+                if line
+                    .strip_prefix("AUTHENTICATE ")
+                    .is_some_and(|param| !is_public_authenticate(param)) =>
+            {
+                "AUTHENTICATE [redacted]"
+            }
+            _ => line,
+        };
+        self.write(&LogEntry {
+            timestamp: Some(system_time_to_millis(SystemTime::now())),
+            connection,
+            direction,
+            line: line.to_owned(),
+        })
+    }
+
+    /// Writes an entry to the log as is
+    pub fn write(&mut self, entry: &LogEntry) -> std::io::Result<()> {
+        let out = format!("{entry}\n");
+        if self.written > 0 && self.written + out.len() as u64 > self.max_file_size {
+            self.rotate()?;
+        }
+        self.file.write_all(out.as_bytes())?;
+        self.written += out.len() as u64;
+        Ok(())
+    }
+
+    /// Writes the buffered lines out to the log
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        self.file.flush()?;
+        if self.max_files == 0 {
+            self.file = BufWriter::new(File::create(&self.path)?);
+        } else {
+            for i in (1..self.max_files).rev() {
+                let from = rotated_path(&self.path, i);
+                if from.exists() {
+                    std::fs::rename(from, rotated_path(&self.path, i + 1))?;
+                }
+            }
+            std::fs::rename(&self.path, rotated_path(&self.path, 1))?;
+            self.file = BufWriter::new(File::create(&self.path)?);
+        }
+        self.written = 0;
+        Ok(())
+    }
+}
+
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(format!(".{index}"));
+    path.into()
+}
+
+/// Error returned when replaying a chat log
+#[derive(Debug, Error)]
+pub enum ReplayError {
+    /// The log couldn't be read
+    #[error("failed to read log: {0}")]
+    Io(#[from] std::io::Error),
+    /// A recorded line wasn't a valid IRC message
+    #[error("failed to parse recorded message: {0}")]
+    Parse(#[from] IrcMessageParseError),
+}
+
+/// Error returned when a [SpeedFactor] isn't finite and above zero
+#[derive(Debug, Error, Clone, Copy, PartialEq)]
+#[error("speed factors must be finite and above zero, got {0}")]
+pub struct InvalidSpeedFactor(pub f64);
+
+/// How many times faster than recorded a [Replayer] streams messages, always
+/// finite and above zero
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpeedFactor(f64);
+
+impl SpeedFactor {
+    /// Checks that `factor` is finite and above zero
+    pub fn new(factor: f64) -> Result<Self, InvalidSpeedFactor> {
+        if factor.is_finite() && factor > 0.0 {
+            Ok(Self(factor))
+        } else {
+            Err(InvalidSpeedFactor(factor))
+        }
+    }
+
+    /// The factor as an [f64]
+    pub fn get(self) -> f64 {
+        self.0
+    }
+}
+
+impl TryFrom<f64> for SpeedFactor {
+    type Error = InvalidSpeedFactor;
+
+    fn try_from(value: f64) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
+/// How fast a [Replayer] streams messages
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Pace {
+    /// Messages are returned as soon as they are read
+    #[default]
+    AsFastAsPossible,
+    /// Messages are spaced out like they were when recorded, lines without a
+    /// timestamp are returned immediately
+    RealTime,
+    /// Like [RealTime](Pace::RealTime), but sped up by the given factor
+    SpedUp(SpeedFactor),
+}
+
+/// Reads chat logs written by a [Recorder], or files of plain raw IRC lines
+pub struct Replayer {
+    lines: Box<dyn Iterator<Item = std::io::Result<String>> + Send>,
+    timer: Arc<dyn Timer>,
+}
+
+impl Replayer {
+    /// Reads a single log file
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Ok(Self::from_reader(BufReader::new(File::open(path)?)))
+    }
+
+    /// Reads the log at `path` along with the logs rotated from it, oldest
+    /// first
+    pub fn open_rotated(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let path = path.as_ref();
+        let mut files = vec![];
+        for i in (1..).map(|i| rotated_path(path, i)) {
+            if !i.exists() {
+                break;
+            }
+            files.push(File::open(i)?);
+        }
+        files.reverse();
+        files.push(File::open(path)?);
+
+        Ok(Self {
+            lines: Box::new(files.into_iter().flat_map(|f| BufReader::new(f).lines())),
+            timer: runtime::default_timer(),
+        })
+    }
+
+    /// Reads a log from any reader
+    pub fn from_reader(reader: impl BufRead + Send + 'static) -> Self {
+        Self {
+            lines: Box::new(reader.lines()),
+            timer: runtime::default_timer(),
+        }
+    }
+
+    /// Sets the timer [stream](Self::stream) waits with, the
+    /// [default timer](runtime::default_timer) otherwise
+    pub fn timer(mut self, timer: Arc<dyn Timer>) -> Self {
+        self.timer = timer;
+        self
+    }
+
+    /// Parses the received messages of the log along with the index of the
+    /// connection that received them, sent lines are skipped
+    pub fn messages(self) -> impl Iterator<Item = Result<(IrcMessage, usize), ReplayError>> {
+        self.filter_map(|entry| match entry {
+            Ok(entry) if entry.line.is_empty() || entry.direction == Direction::Sent => None,
+            Ok(entry) => Some(entry.into_message()),
+            Err(e) => Some(Err(e.into())),
+        })
+    }
+
+    /// Streams the received messages of the log at the given [Pace], like
+    /// [messages](Self::messages) does
+    pub fn stream(
+        self,
+        pace: Pace,
+    ) -> impl Stream<Item = Result<(IrcMessage, usize), ReplayError>> + Send {
+        let speed = match pace {
+            Pace::AsFastAsPossible => None,
+            Pace::RealTime => Some(1.0),
+            Pace::SpedUp(factor) => Some(factor.get()),
+        };
+        let timer = Arc::clone(&self.timer);
+        let entries = self.filter(|entry| {
+            entry.as_ref().map_or(true, |e| {
+                !e.line.is_empty() && e.direction == Direction::Received
+            })
+        });
+
+        futures_util::stream::unfold((entries, None::<i64>), move |(mut entries, mut last)| {
+            let timer = Arc::clone(&timer);
+            async move {
+                let entry = match entries.next()? {
+                    Ok(entry) => entry,
+                    Err(e) => return Some((Err(e.into()), (entries, last))),
+                };
+                if let (Some(speed), Some(timestamp)) = (speed, entry.timestamp) {
+                    if let Some(last) = last {
+                        let wait = (timestamp - last).max(0) as f64 / speed / 1000.0;
+                        // very slow factors can overflow a Duration
+                        let wait = Duration::try_from_secs_f64(wait).unwrap_or(Duration::MAX);
+                        timer.sleep(wait).await;
+                    }
+                    last = Some(timestamp);
+                }
+                Some((entry.into_message(), (entries, last)))
+            }
+        })
+    }
+}
+
+impl Iterator for Replayer {
+    type Item = std::io::Result<LogEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.lines
+            .next()
+            .map(|line| line.map(|l| LogEntry::parse(&l)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use futures_util::StreamExt;
+
+    use super::{
+        Direction, InvalidSpeedFactor, LogEntry, Pace, Recorder, Replayer, SpeedFactor,
+        rotated_path,
+    };
+    use crate::IrcCommand;
+
+    #[test]
+    fn log_entries() {
+        let entry = LogEntry::parse("1680318910689 2 > PRIVMSG #foo :hi there\r\n");
+        assert_eq!(entry.timestamp, Some(1680318910689));
+        assert_eq!(entry.connection, 2);
+        assert_eq!(entry.direction, Direction::Sent);
+        assert_eq!(entry.line, "PRIVMSG #foo :hi there");
+        assert_eq!(
+            entry.to_string(),
+            "1680318910689 2 > PRIVMSG #foo :hi there"
+        );
+
+        let raw = LogEntry::parse(":tmi.twitch.tv 001 justinfan6969 :Welcome, GLHF!");
+        assert_eq!(raw.timestamp, None);
+        assert_eq!(raw.direction, Direction::Received);
+        assert_eq!(raw.line, ":tmi.twitch.tv 001 justinfan6969 :Welcome, GLHF!");
+    }
+
+    #[tokio::test]
+    async fn record_rotate_and_replay() {
+        let dir = std::env::temp_dir().join(format!("twixel-recording-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("chat.log");
+        let _ = std::fs::remove_file(&path);
+
+        let mut recorder = Recorder::create(&path)
+            .unwrap()
+            .max_file_size(120)
+            .max_files(1);
+        recorder
+            .record(0, Direction::Sent, "PASS oauth:secret\r\n")
+            .unwrap();
+        recorder
+            .record(0, Direction::Sent, "AUTHENTICATE PLAIN\r\n")
+            .unwrap();
+        recorder
+            .record(0, Direction::Sent, "AUTHENTICATE c2VjcmV0\r\n")
+            .unwrap();
+        // nothing is written until the buffer is flushed
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+        recorder.flush().unwrap();
+        let log = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(log.lines().count(), 3);
+        assert!(log.contains("AUTHENTICATE PLAIN"), "{log}");
+        assert!(!log.contains("secret"), "{log}");
+        assert!(!log.contains("c2VjcmV0"), "{log}");
+
+        for i in 0..4 {
+            recorder
+                .record(
+                    i % 2,
+                    Direction::Received,
+                    &format!(":a!a@a.tmi.twitch.tv PRIVMSG #foo :{i}"),
+                )
+                .unwrap();
+        }
+        recorder.flush().unwrap();
+
+        // with a single rotated file kept, the oldest lines are gone
+        assert!(rotated_path(&path, 1).exists());
+        assert!(!rotated_path(&path, 2).exists());
+        let all: Vec<_> = Replayer::open_rotated(&path)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(all.last().unwrap().connection, 1);
+
+        let replayed: Vec<_> = Replayer::open_rotated(&path)
+            .unwrap()
+            .stream(Pace::AsFastAsPossible)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        let parsed: Vec<_> = Replayer::open_rotated(&path)
+            .unwrap()
+            .messages()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(replayed.len(), parsed.len());
+        assert_eq!(parsed.last().unwrap().0.get_param(1), Some("3"));
+        assert!(
+            parsed
+                .iter()
+                .all(|(msg, _)| msg.get_command() == IrcCommand::PrivMsg)
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    // paused time only applies to the tokio timer
+    #[cfg(feature = "tokio-runtime")]
+    #[tokio::test(start_paused = true)]
+    async fn real_time_pace() {
+        let log = "1000 0 < PING :a\n1500 0 > PONG :a\n3000 0 < PING :b\n";
+        let start = tokio::time::Instant::now();
+        let msgs: Vec<_> = Replayer::from_reader(log.as_bytes())
+            .stream(Pace::SpedUp(SpeedFactor::new(2.0).unwrap()))
+            .collect()
+            .await;
+        assert_eq!(msgs.len(), 2);
+        assert_eq!(start.elapsed(), std::time::Duration::from_secs(1));
+    }
+
+    #[test]
+    fn speed_factor_validation() {
+        assert_eq!(SpeedFactor::new(2.0).unwrap().get(), 2.0);
+        for factor in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(matches!(
+                SpeedFactor::new(factor),
+                Err(InvalidSpeedFactor(f)) if f.to_bits() == factor.to_bits()
+            ));
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use std::{fmt::Debug, sync::Arc, time::Duration};
+
+#[cfg(feature = "connection")]
+use async_tungstenite::tungstenite::{Error as WsError, Message as WsMessage};
+
+#[cfg(feature = "connection")]
+use crate::connection::Proxy;
+use futures_util::future::BoxFuture;
+#[cfg(feature = "connection")]
+use futures_util::{Sink, Stream, stream::FusedStream};
+
+/// Waits for durations of time on an async runtime
+pub trait Timer: Debug + Send + Sync {
+    /// Returns a future that completes after `duration`
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()>;
+}
+
+/// Runs background tasks on an async runtime
+pub trait Spawner: Debug + Send + Sync {
+    /// Runs `task` in the background until it completes
+    fn spawn(&self, task: BoxFuture<'static, ()>);
+}
+
+/// A websocket that sends and receives
+/// [tungstenite](async_tungstenite::tungstenite) messages
+#[cfg(feature = "connection")]
+pub trait WebSocket:
+    Stream<Item = Result<WsMessage, WsError>>
+    + Sink<WsMessage, Error = WsError>
+    + FusedStream
+    + Send
+    + Unpin
+{
+}
+
+#[cfg(feature = "connection")]
+impl<T> WebSocket for T where
+    T: Stream<Item = Result<WsMessage, WsError>>
+        + Sink<WsMessage, Error = WsError>
+        + FusedStream
+        + Send
+        + Unpin
+{
+}
+
+/// Opens websockets on an async runtime
+#[cfg(feature = "connection")]
+pub trait Connector: Debug + Send + Sync {
+    /// Connects to the websocket at `url`, using TLS for `wss://` URLs
+    fn connect<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<Box<dyn WebSocket>, WsError>>;
+
+    /// Connects to the websocket at `url` through `proxy`. Proxies are not
+    /// supported unless this is implemented
+    fn connect_proxied<'a>(
+        &'a self,
+        url: &'a str,
+        proxy: &'a Proxy,
+    ) -> BoxFuture<'a, Result<Box<dyn WebSocket>, WsError>> {
+        let _ = (url, proxy);
+        Box::pin(async {
+            Err(WsError::Io(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "this runtime doesn't support proxies",
+            )))
+        })
+    }
+}
+
+/// An async runtime [Connection](crate::Connection)s run on, which provides
+/// their sockets, timers and background tasks
+#[cfg(feature = "connection")]
+pub trait Runtime: Connector + Timer + Spawner {}
+
+#[cfg(feature = "connection")]
+impl<T: Connector + Timer + Spawner> Runtime for T {}
+
+/// The [tokio](https://tokio.rs) runtime
+#[cfg(feature = "tokio-runtime")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Tokio;
+
+#[cfg(feature = "tokio-runtime")]
+impl Timer for Tokio {
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+#[cfg(feature = "tokio-runtime")]
+impl Spawner for Tokio {
+    fn spawn(&self, task: BoxFuture<'static, ()>) {
+        tokio::spawn(task);
+    }
+}
+
+#[cfg(feature = "tokio-runtime")]
+impl Connector for Tokio {
+    fn connect<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<Box<dyn WebSocket>, WsError>> {
+        Box::pin(async move {
+            let (socket, _resp) = async_tungstenite::tokio::connect_async(url).await?;
+            Ok(Box::new(socket) as Box<dyn WebSocket>)
+        })
+    }
+
+    fn connect_proxied<'a>(
+        &'a self,
+        url: &'a str,
+        proxy: &'a Proxy,
+    ) -> BoxFuture<'a, Result<Box<dyn WebSocket>, WsError>> {
+        use async_tungstenite::tokio::{TokioAdapter, client_async_tls_with_connector_and_config};
+
+        Box::pin(async move {
+            let (host, port) = crate::connection::proxy::target(url)?;
+            let stream = tokio::net::TcpStream::connect((proxy.host(), proxy.port())).await?;
+            let mut stream = TokioAdapter::new(stream);
+            proxy.tunnel(&mut stream, &host, port).await?;
+            let (socket, _resp) =
+                client_async_tls_with_connector_and_config(url, stream.into_inner(), None, None)
+                    .await?;
+            Ok(Box::new(socket) as Box<dyn WebSocket>)
+        })
+    }
+}
+
+/// The [smol](https://github.com/smol-rs/smol) runtime, or any executor
+/// running [async-io](https://docs.rs/async-io) futures
+#[cfg(feature = "smol-runtime")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Smol;
+
+#[cfg(feature = "smol-runtime")]
+impl Timer for Smol {
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        Box::pin(async move {
+            async_io::Timer::after(duration).await;
+        })
+    }
+}
+
+/// Tasks run on their own thread, as there is no global executor to share
+#[cfg(feature = "smol-runtime")]
+impl Spawner for Smol {
+    fn spawn(&self, task: BoxFuture<'static, ()>) {
+        std::thread::spawn(move || async_io::block_on(task));
+    }
+}
+
+#[cfg(feature = "smol-runtime")]
+impl Connector for Smol {
+    fn connect<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<Box<dyn WebSocket>, WsError>> {
+        Box::pin(async move {
+            let (socket, _resp) = async_tungstenite::smol::connect_async(url).await?;
+            Ok(Box::new(socket) as Box<dyn WebSocket>)
+        })
+    }
+
+    fn connect_proxied<'a>(
+        &'a self,
+        url: &'a str,
+        proxy: &'a Proxy,
+    ) -> BoxFuture<'a, Result<Box<dyn WebSocket>, WsError>> {
+        use async_tungstenite::smol::client_async_tls_with_connector_and_config;
+
+        Box::pin(async move {
+            let (host, port) = crate::connection::proxy::target(url)?;
+            let mut stream = async_net::TcpStream::connect((proxy.host(), proxy.port())).await?;
+            proxy.tunnel(&mut stream, &host, port).await?;
+            let (socket, _resp) =
+                client_async_tls_with_connector_and_config(url, stream, None, None).await?;
+            Ok(Box::new(socket) as Box<dyn WebSocket>)
+        })
+    }
+}
+
+/// Used when no runtime feature is enabled: it can't sleep, spawn tasks or open
+/// sockets, so connections need a runtime to be set
+#[cfg(not(any(feature = "tokio-runtime", feature = "smol-runtime")))]
+#[derive(Debug, Clone, Copy, Default)]
+struct NoRuntime;
+
+#[cfg(not(any(feature = "tokio-runtime", feature = "smol-runtime")))]
+impl Timer for NoRuntime {
+    fn sleep(&self, _duration: Duration) -> BoxFuture<'static, ()> {
+        panic!(
+            "no runtime to sleep on, enable the tokio-runtime or smol-runtime feature or set one \
+             on the connection"
+        )
+    }
+}
+
+#[cfg(not(any(feature = "tokio-runtime", feature = "smol-runtime")))]
+impl Spawner for NoRuntime {
+    fn spawn(&self, _task: BoxFuture<'static, ()>) {
+        panic!(
+            "no runtime to spawn tasks on, enable the tokio-runtime or smol-runtime feature or \
+             set one on the connection"
+        )
+    }
+}
+
+#[cfg(all(
+    feature = "connection",
+    not(any(feature = "tokio-runtime", feature = "smol-runtime"))
+))]
+impl Connector for NoRuntime {
+    fn connect<'a>(&'a self, _url: &'a str) -> BoxFuture<'a, Result<Box<dyn WebSocket>, WsError>> {
+        Box::pin(async {
+            Err(WsError::Io(std::io::Error::other(
+                "no runtime to connect with, enable the tokio-runtime or smol-runtime feature \
+                 or set one on the connection",
+            )))
+        })
+    }
+}
+
+#[cfg(feature = "tokio-runtime")]
+type DefaultRuntime = Tokio;
+#[cfg(all(feature = "smol-runtime", not(feature = "tokio-runtime")))]
+type DefaultRuntime = Smol;
+#[cfg(not(any(feature = "tokio-runtime", feature = "smol-runtime")))]
+type DefaultRuntime = NoRuntime;
+
+/// The runtime used by default: [Tokio] if the `tokio-runtime` feature is
+/// enabled, otherwise [Smol] if `smol-runtime` is
+#[cfg(feature = "connection")]
+pub fn default_runtime() -> Arc<dyn Runtime> {
+    Arc::new(DefaultRuntime::default())
+}
+
+/// The timer used by default, see [default_runtime]
+pub fn default_timer() -> Arc<dyn Timer> {
+    Arc::new(DefaultRuntime::default())
+}
+
+#[cfg(all(test, feature = "smol-runtime"))]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{Smol, Spawner, Timer};
+
+    #[test]
+    fn smol_timer() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        Smol.spawn(Box::pin(async move {
+            let start = Instant::now();
+            Smol.sleep(Duration::from_millis(20)).await;
+            tx.send(start.elapsed()).unwrap();
+        }));
+        assert!(rx.recv().unwrap() >= Duration::from_millis(20));
+    }
+
+    #[cfg(feature = "tokio-runtime")]
+    #[tokio::test]
+    async fn smol_connection() {
+        use std::sync::Arc;
+
+        use crate::{ChannelLogin, Connection, IrcCommand, auth::Anonymous, testing::MockServer};
+
+        let server = MockServer::start().await.unwrap();
+        let profile = server.profile();
+
+        // the client runs on its own thread, outside of the tokio runtime
+        let (tx, client) = tokio::sync::oneshot::channel();
+        std::thread::spawn(move || {
+            let received = async_io::block_on(async {
+                let mut conn: Connection<_> = Connection::with_profile(
+                    [ChannelLogin::parse("foo").unwrap()],
+                    Anonymous,
+                    profile,
+                );
+                conn.set_runtime(Arc::new(Smol));
+                conn.start().await.unwrap();
+                loop {
+                    let msg = conn.receive().await.unwrap();
+                    if msg.get_command() == IrcCommand::PrivMsg {
+                        return msg.get_param(1).unwrap().to_owned();
+                    }
+                }
+            });
+            tx.send(received).unwrap();
+        });
+
+        server.wait_for_command(IrcCommand::Join).await;
+        server.send_privmsg("foo", "bar", "hello");
+        assert_eq!(client.await.unwrap(), "hello");
+    }
+}
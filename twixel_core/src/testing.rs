@@ -0,0 +1,463 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use async_tungstenite::tungstenite::Message as WsMessage;
+use futures_util::StreamExt;
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::{mpsc, watch},
+    task::JoinHandle,
+};
+
+use crate::{IrcCommand, IrcMessage, connection::ServerProfile};
+
+/// A message a client sent to a [MockServer]
+#[derive(Debug, Clone)]
+pub struct ClientMessage {
+    /// Index of the client that sent the message, in the order clients
+    /// connected
+    pub client: usize,
+    /// The message
+    pub message: IrcMessage,
+    /// When the message was received
+    pub received_at: Instant,
+}
+
+enum Outgoing {
+    Line(String),
+    Close,
+}
+
+struct MockClient {
+    tx: mpsc::UnboundedSender<Outgoing>,
+    nick: Option<String>,
+    connected: bool,
+}
+
+#[derive(Default)]
+struct MockState {
+    clients: Vec<MockClient>,
+    received: Vec<ClientMessage>,
+}
+
+/// A local websocket server that speaks enough of Twitch's IRC protocol to
+/// test clients against without reaching Twitch.
+///
+/// It acknowledges `CAP REQ`, welcomes clients after `NICK`, echoes `JOIN` and
+/// `PART` along with `ROOMSTATE` and `USERSTATE`, answers `PRIVMSG` with
+/// `USERSTATE` and `PING` with `PONG`, and records every message clients sent.
+/// Tests can inject messages into every client or a single one.
+///
+/// The server stops when dropped
+pub struct MockServer {
+    url: String,
+    state: Arc<Mutex<MockState>>,
+    changes: watch::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+impl MockServer {
+    /// Starts a new [MockServer] on a random local port
+    pub async fn start() -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("ws://{}", listener.local_addr()?);
+        let state = Arc::new(Mutex::new(MockState::default()));
+        let (changes, _) = watch::channel(());
+
+        let task = tokio::spawn({
+            let state = Arc::clone(&state);
+            let changes = changes.clone();
+            async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(handle_client(stream, Arc::clone(&state), changes.clone()));
+                }
+            }
+        });
+
+        Ok(Self {
+            url,
+            state,
+            changes,
+            task,
+        })
+    }
+
+    /// The websocket URL of the server
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// A [ServerProfile] that connects to this server as if it was Twitch
+    pub fn profile(&self) -> ServerProfile {
+        ServerProfile::TwitchAt(self.url.clone())
+    }
+
+    /// Every message clients sent so far
+    pub fn received(&self) -> Vec<ClientMessage> {
+        self.state.lock().unwrap().received.clone()
+    }
+
+    /// Amount of clients that ever connected
+    pub fn client_count(&self) -> usize {
+        self.state.lock().unwrap().clients.len()
+    }
+
+    /// Amount of clients that are currently connected
+    pub fn connected_count(&self) -> usize {
+        let state = self.state.lock().unwrap();
+        state.clients.iter().filter(|c| c.connected).count()
+    }
+
+    /// Waits until a client sends a message that matches `predicate`, returns
+    /// the first matching message if one was already received
+    pub async fn wait_for(
+        &self,
+        mut predicate: impl FnMut(&ClientMessage) -> bool,
+    ) -> ClientMessage {
+        let mut changes = self.changes.subscribe();
+        loop {
+            if let Some(found) = self
+                .state
+                .lock()
+                .unwrap()
+                .received
+                .iter()
+                .find(|m| predicate(m))
+            {
+                return found.clone();
+            }
+            changes.changed().await.expect("the mock server stopped");
+        }
+    }
+
+    /// Waits until a client sends a message with the command `command`
+    pub async fn wait_for_command(&self, command: IrcCommand) -> ClientMessage {
+        self.wait_for(|m| m.message.get_command() == command).await
+    }
+
+    /// Waits until `count` clients are connected at once
+    pub async fn wait_for_connected(&self, count: usize) {
+        let mut changes = self.changes.subscribe();
+        while self.connected_count() < count {
+            changes.changed().await.expect("the mock server stopped");
+        }
+    }
+
+    /// Sends a raw IRC line to every connected client, `\r\n` is added if
+    /// missing
+    pub fn send_raw(&self, line: &str) {
+        let state = self.state.lock().unwrap();
+        for client in state.clients.iter().filter(|c| c.connected) {
+            let _ = client.tx.send(Outgoing::Line(terminate(line)));
+        }
+    }
+
+    /// Sends a raw IRC line to a single client, `\r\n` is added if missing
+    pub fn send_raw_to(&self, client: usize, line: &str) {
+        let state = self.state.lock().unwrap();
+        if let Some(client) = state.clients.get(client) {
+            let _ = client.tx.send(Outgoing::Line(terminate(line)));
+        }
+    }
+
+    /// Sends a `PRIVMSG` from `sender` in `channel` to every connected client
+    pub fn send_privmsg(&self, channel: &str, sender: &str, text: &str) {
+        let id = self.state.lock().unwrap().received.len();
+        self.send_raw(&format!(
+            "@badge-info=;badges=;color=;display-name={sender};emotes=;first-msg=0;flags=;\
+             id=00000000-0000-0000-0000-{id:012};mod=0;returning-chatter=0;room-id=1;\
+             subscriber=0;tmi-sent-ts={};turbo=0;user-id=2;user-type= \
+             :{sender}!{sender}@{sender}.tmi.twitch.tv PRIVMSG #{channel} :{text}",
+            crate::time::system_time_to_millis(std::time::SystemTime::now()),
+        ));
+    }
+
+    /// Sends a `NOTICE` with the `msg-id` tag `msg_id` in `channel` to every
+    /// connected client
+    pub fn send_notice(&self, channel: &str, msg_id: &str, text: &str) {
+        self.send_raw(&format!(
+            "@msg-id={msg_id} :tmi.twitch.tv NOTICE #{channel} :{text}"
+        ));
+    }
+
+    /// Sends a `RECONNECT` to every connected client
+    pub fn send_reconnect(&self) {
+        self.send_raw(":tmi.twitch.tv RECONNECT");
+    }
+
+    /// Sends a `PING` to every connected client
+    pub fn send_ping(&self) {
+        self.send_raw("PING :tmi.twitch.tv");
+    }
+
+    /// Closes the connection to a client
+    pub fn disconnect(&self, client: usize) {
+        let state = self.state.lock().unwrap();
+        if let Some(client) = state.clients.get(client) {
+            let _ = client.tx.send(Outgoing::Close);
+        }
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+        let state = self.state.lock().unwrap();
+        for client in state.clients.iter() {
+            let _ = client.tx.send(Outgoing::Close);
+        }
+    }
+}
+
+fn terminate(line: &str) -> String {
+    if line.ends_with("\r\n") {
+        line.to_owned()
+    } else {
+        format!("{line}\r\n")
+    }
+}
+
+async fn handle_client(
+    stream: TcpStream,
+    state: Arc<Mutex<MockState>>,
+    changes: watch::Sender<()>,
+) {
+    let Ok(ws) = async_tungstenite::tokio::accept_async(stream).await else {
+        return;
+    };
+    let (mut sink, mut stream) = ws.split();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+
+    let idx = {
+        let mut state = state.lock().unwrap();
+        state.clients.push(MockClient {
+            tx: tx.clone(),
+            nick: None,
+            connected: true,
+        });
+        state.clients.len() - 1
+    };
+    changes.send_replace(());
+
+    loop {
+        tokio::select! {
+            outgoing = rx.recv() => match outgoing {
+                Some(Outgoing::Line(line)) => {
+                    if sink.send(WsMessage::Text(line.into())).await.is_err() {
+                        break;
+                    }
+                }
+                Some(Outgoing::Close) | None => {
+                    let _ = sink.close(None).await;
+                    break;
+                }
+            },
+            incoming = stream.next() => match incoming {
+                Some(Ok(WsMessage::Text(text))) => {
+                    for line in text.split("\r\n").filter(|l| !l.is_empty()) {
+                        let Ok(message) = IrcMessage::new(line.to_owned()) else {
+                            continue;
+                        };
+                        for reply in respond(&state, idx, &message) {
+                            let _ = tx.send(Outgoing::Line(reply));
+                        }
+                        state.lock().unwrap().received.push(ClientMessage {
+                            client: idx,
+                            message,
+                            received_at: Instant::now(),
+                        });
+                        changes.send_replace(());
+                    }
+                }
+                Some(Ok(WsMessage::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+
+    state.lock().unwrap().clients[idx].connected = false;
+    changes.send_replace(());
+}
+
+/// What Twitch would answer to a message sent by a client
+fn respond(state: &Mutex<MockState>, client: usize, message: &IrcMessage) -> Vec<String> {
+    let mut state = state.lock().unwrap();
+    let nick = state.clients[client]
+        .nick
+        .clone()
+        .unwrap_or_else(|| "justinfan".into());
+    let prefix = format!(":{nick}!{nick}@{nick}.tmi.twitch.tv");
+    let userstate = |channel: &str| {
+        format!(
+            "@badge-info=;badges=;color=;display-name={nick};emote-sets=0;mod=0;subscriber=0;\
+             user-type= :tmi.twitch.tv USERSTATE {channel}\r\n"
+        )
+    };
+    let anonymous = nick.starts_with("justinfan");
+
+    match message.get_command() {
+        IrcCommand::Nick => {
+            let nick = message.get_param(0).unwrap_or_default().to_owned();
+            let replies = [
+                format!(":tmi.twitch.tv 001 {nick} :Welcome, GLHF!\r\n"),
+                format!(":tmi.twitch.tv 002 {nick} :Your host is tmi.twitch.tv\r\n"),
+                format!(":tmi.twitch.tv 003 {nick} :This server is rather new\r\n"),
+                format!(":tmi.twitch.tv 004 {nick} :-\r\n"),
+                format!(":tmi.twitch.tv 375 {nick} :-\r\n"),
+                format!(":tmi.twitch.tv 372 {nick} :You are in a maze of twisty passages.\r\n"),
+                format!(":tmi.twitch.tv 376 {nick} :>\r\n"),
+            ];
+            state.clients[client].nick = Some(nick);
+            replies.into()
+        }
+        IrcCommand::Cap if message.get_param(0) == Some("REQ") => {
+            let caps = message.get_param(1).unwrap_or_default();
+            vec![format!(":tmi.twitch.tv CAP * ACK :{caps}\r\n")]
+        }
+        IrcCommand::Join => {
+            let mut replies = vec![];
+            for channel in message.get_param(0).unwrap_or_default().split(',') {
+                replies.push(format!("{prefix} JOIN {channel}\r\n"));
+                replies.push(format!(
+                    ":{nick}.tmi.twitch.tv 353 {nick} = {channel} :{nick}\r\n"
+                ));
+                replies.push(format!(
+                    ":{nick}.tmi.twitch.tv 366 {nick} {channel} :End of /NAMES list\r\n"
+                ));
+                if !anonymous {
+                    replies.push(userstate(channel));
+                }
+                replies.push(format!(
+                    "@emote-only=0;followers-only=-1;r9k=0;room-id=1;slow=0;subs-only=0 \
+                     :tmi.twitch.tv ROOMSTATE {channel}\r\n"
+                ));
+            }
+            replies
+        }
+        IrcCommand::Part => message
+            .get_param(0)
+            .unwrap_or_default()
+            .split(',')
+            .map(|channel| format!("{prefix} PART {channel}\r\n"))
+            .collect(),
+        IrcCommand::PrivMsg if !anonymous => message
+            .get_param(0)
+            .map(|channel| vec![userstate(channel)])
+            .unwrap_or_default(),
+        IrcCommand::Ping => vec![format!(
+            ":tmi.twitch.tv PONG tmi.twitch.tv :{}\r\n",
+            message.get_param(0).unwrap_or_default()
+        )],
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Connection, ConnectionPool, IrcCommand,
+        auth::{Anonymous, OAuth},
+        login::ChannelLogin,
+    };
+
+    use super::MockServer;
+
+    #[tokio::test]
+    async fn handshake_and_injection() {
+        let server = MockServer::start().await.unwrap();
+        let auth = OAuth {
+            oauth: "token".into(),
+            nick: "julia".into(),
+        };
+        let mut conn: Connection<_> = Connection::with_profile(
+            [ChannelLogin::parse("foo").unwrap()],
+            auth,
+            server.profile(),
+        );
+        conn.start().await.unwrap();
+
+        let join = server.wait_for_command(IrcCommand::Join).await;
+        assert_eq!(join.message.get_param(0), Some("#foo"));
+        let sent: Vec<_> = server
+            .received()
+            .into_iter()
+            .map(|m| m.message.inner().to_owned())
+            .collect();
+        assert_eq!(
+            sent,
+            [
+                "PASS oauth:token",
+                "NICK julia",
+                "CAP REQ :twitch.tv/commands twitch.tv/tags",
+                "JOIN #foo"
+            ]
+        );
+
+        let mut commands = vec![];
+        loop {
+            let msg = conn.receive().await.unwrap();
+            commands.push(msg.get_command());
+            if msg.get_command() == IrcCommand::RoomState {
+                break;
+            }
+        }
+        assert!(commands.contains(&IrcCommand::AuthSuccessful));
+        assert!(commands.contains(&IrcCommand::UserState));
+
+        server.send_privmsg("foo", "bar", "hi julia");
+        let msg = conn.receive().await.unwrap();
+        assert_eq!(msg.get_command(), IrcCommand::PrivMsg);
+        assert_eq!(msg.get_param(1), Some("hi julia"));
+
+        conn.send(crate::MessageBuilder::privmsg(
+            &ChannelLogin::parse("foo").unwrap(),
+            "hi bar",
+        ))
+        .await
+        .unwrap();
+        server
+            .wait_for(|m| m.message.get_param(1) == Some("hi bar"))
+            .await;
+        assert_eq!(
+            conn.receive().await.unwrap().get_command(),
+            IrcCommand::UserState
+        );
+
+        server.send_reconnect();
+        assert_eq!(
+            conn.receive().await.unwrap().get_command(),
+            IrcCommand::Reconnect
+        );
+        conn.restart().await.unwrap();
+        server.wait_for_connected(1).await;
+        assert_eq!(server.client_count(), 2);
+        server
+            .wait_for(|m| m.client == 1 && m.message.get_command() == IrcCommand::Join)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn pool_spreads_channels() {
+        let server = MockServer::start().await.unwrap();
+        let channels = (0..150).map(|i| ChannelLogin::parse(&format!("chan{i}")).unwrap());
+        let pool: ConnectionPool<_> =
+            ConnectionPool::with_profile(channels, Anonymous, server.profile())
+                .await
+                .unwrap();
+
+        server.wait_for_connected(2).await;
+        assert_eq!(pool.get_conn_idx("chan0"), Some(0));
+        assert_eq!(pool.get_conn_idx("chan149"), Some(1));
+
+        // connections start one after the other, so clients connect in order
+        for (client, expected) in [(0, 100), (1, 50)] {
+            let join = server
+                .wait_for(|m| m.client == client && m.message.get_command() == IrcCommand::Join)
+                .await;
+            let count = join.message.get_param(0).unwrap().split(',').count();
+            assert_eq!(count, expected);
+        }
+    }
+}
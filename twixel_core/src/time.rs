@@ -0,0 +1,231 @@
+use std::{
+    collections::VecDeque,
+    ops::Deref,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::IrcMessage;
+
+/// Converts milliseconds since the unix epoch into a [SystemTime]
+pub fn millis_to_system_time(millis: i64) -> Option<SystemTime> {
+    if millis >= 0 {
+        UNIX_EPOCH.checked_add(Duration::from_millis(millis as u64))
+    } else {
+        UNIX_EPOCH.checked_sub(Duration::from_millis(millis.unsigned_abs()))
+    }
+}
+
+/// Converts a [SystemTime] into milliseconds since the unix epoch
+pub fn system_time_to_millis(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(after) => after.as_millis() as i64,
+        Err(before) => -(before.duration().as_millis() as i64),
+    }
+}
+
+/// Parses an [IRCv3 server-time](https://ircv3.net/specs/extensions/server-time)
+/// timestamp, such as `2011-10-19T16:40:51.620Z`, into milliseconds since the
+/// unix epoch
+pub(crate) fn parse_server_time(time: &str) -> Option<i64> {
+    let num = |s: &str| -> Option<i64> {
+        s.bytes()
+            .all(|b| b.is_ascii_digit())
+            .then(|| s.parse().ok())
+            .flatten()
+    };
+
+    let time = time.strip_suffix('Z')?;
+    let (date, time) = time.split_once('T')?;
+
+    let mut date = date.splitn(3, '-');
+    let (year, month, day) = (num(date.next()?)?, num(date.next()?)?, num(date.next()?)?);
+
+    let (time, fraction) = time.split_once('.').unwrap_or((time, "0"));
+    let mut time = time.splitn(3, ':');
+    let (hour, minute, second) = (num(time.next()?)?, num(time.next()?)?, num(time.next()?)?);
+    // only milliseconds are kept
+    let millis = num(&format!("{fraction:0<3}")[..3])?;
+
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 {
+        return None;
+    }
+
+    // days since the unix epoch, from Howard Hinnant's `days_from_civil`
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    Some((((days * 24 + hour) * 60 + minute) * 60 + second) * 1000 + millis)
+}
+
+/// Estimates the offset between the local clock and Twitch's clock from the
+/// timestamps of received messages.
+///
+/// The difference between when a message was received and when Twitch says it
+/// was sent is the clock offset plus the network latency, so the smallest
+/// difference among recent messages is used as the estimate
+#[derive(Debug, Clone)]
+pub struct ClockOffset {
+    samples: VecDeque<i64>,
+    window: usize,
+}
+
+impl Default for ClockOffset {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClockOffset {
+    /// Default amount of recent messages the offset is estimated from
+    pub const DEFAULT_WINDOW: usize = 128;
+
+    /// Creates a new [ClockOffset] with no samples
+    pub fn new() -> Self {
+        Self::with_window(Self::DEFAULT_WINDOW)
+    }
+
+    /// Creates a new [ClockOffset] that estimates the offset from the last
+    /// `window` messages
+    pub fn with_window(window: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(window),
+            window: window.max(1),
+        }
+    }
+
+    /// Adds a sample from a message received at `received_at`, returns the
+    /// difference between the local and server time in milliseconds for this
+    /// message, or `None` if it had no timestamp
+    pub fn observe<C: Deref<Target = str>>(
+        &mut self,
+        msg: &IrcMessage<C>,
+        received_at: SystemTime,
+    ) -> Option<i64> {
+        Some(self.observe_millis(msg.get_timestamp_millis()?, received_at))
+    }
+
+    /// Adds a sample from a message sent at `server_millis` according to the
+    /// server and received at `received_at`, returns the difference between the
+    /// local and server time in milliseconds
+    pub fn observe_millis(&mut self, server_millis: i64, received_at: SystemTime) -> i64 {
+        let sample = system_time_to_millis(received_at) - server_millis;
+        if self.samples.len() >= self.window {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+        sample
+    }
+
+    /// How far ahead of the server's clock the local clock is, in milliseconds,
+    /// or `None` if no messages have been observed yet
+    pub fn offset_millis(&self) -> Option<i64> {
+        self.samples.iter().copied().min()
+    }
+
+    /// Estimated time between a message being sent by the server and being
+    /// received at `received_at`, with the clock offset accounted for
+    pub fn latency<C: Deref<Target = str>>(
+        &self,
+        msg: &IrcMessage<C>,
+        received_at: SystemTime,
+    ) -> Option<Duration> {
+        let sample = system_time_to_millis(received_at) - msg.get_timestamp_millis()?;
+        Some(Duration::from_millis(
+            (sample - self.offset_millis()?).max(0) as u64,
+        ))
+    }
+
+    /// Converts a local time into the server's time
+    pub fn to_server_time(&self, local: SystemTime) -> SystemTime {
+        let offset = self.offset_millis().unwrap_or_default();
+        millis_to_system_time(system_time_to_millis(local) - offset).unwrap_or(local)
+    }
+
+    /// Converts a server time into the local time
+    pub fn to_local_time(&self, server: SystemTime) -> SystemTime {
+        let offset = self.offset_millis().unwrap_or_default();
+        millis_to_system_time(system_time_to_millis(server) + offset).unwrap_or(server)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use crate::IrcMessage;
+
+    use super::{ClockOffset, millis_to_system_time, parse_server_time};
+
+    #[test]
+    fn server_time() {
+        assert_eq!(parse_server_time("1970-01-01T00:00:00.000Z"), Some(0));
+        assert_eq!(
+            parse_server_time("2011-10-19T16:40:51.620Z"),
+            Some(1319042451620)
+        );
+        assert_eq!(
+            parse_server_time("2024-02-29T23:59:59Z"),
+            Some(1709251199000)
+        );
+        assert_eq!(parse_server_time("2024-02-29 23:59:59Z"), None);
+        assert_eq!(parse_server_time("2024-13-01T00:00:00Z"), None);
+    }
+
+    #[test]
+    fn timestamps() {
+        let tmi: IrcMessage = "@tmi-sent-ts=1680318910689 :a!a@a.tmi.twitch.tv PRIVMSG #a :hi\r\n"
+            .parse()
+            .unwrap();
+        assert_eq!(tmi.get_timestamp_millis(), Some(1680318910689));
+        assert_eq!(
+            tmi.get_system_time(),
+            Some(UNIX_EPOCH + Duration::from_millis(1680318910689))
+        );
+        #[cfg(feature = "chrono")]
+        assert_eq!(
+            tmi.get_timestamp().unwrap().timestamp_millis(),
+            1680318910689
+        );
+
+        let sent: IrcMessage = "@sent-ts=1680318910001 :tmi.twitch.tv USERNOTICE #a\r\n"
+            .parse()
+            .unwrap();
+        assert_eq!(sent.get_timestamp_millis(), Some(1680318910001));
+
+        let server_time: IrcMessage =
+            "@time=2011-10-19T16:40:51.620Z :irc.example.com NOTICE * :hi\r\n"
+                .parse()
+                .unwrap();
+        assert_eq!(server_time.get_timestamp_millis(), Some(1319042451620));
+    }
+
+    #[test]
+    fn clock_offset() {
+        let msg: IrcMessage = "@tmi-sent-ts=10000 :a!a@a.tmi.twitch.tv PRIVMSG #a :hi\r\n"
+            .parse()
+            .unwrap();
+
+        let mut clock = ClockOffset::with_window(2);
+        assert_eq!(clock.offset_millis(), None);
+
+        let at = |ms| millis_to_system_time(ms).unwrap();
+        assert_eq!(clock.observe(&msg, at(10500)), Some(500));
+        clock.observe_millis(20000, at(20300));
+        assert_eq!(clock.offset_millis(), Some(300));
+        assert_eq!(
+            clock.latency(&msg, at(10500)),
+            Some(Duration::from_millis(200))
+        );
+        assert_eq!(clock.to_server_time(at(1300)), at(1000));
+        assert_eq!(clock.to_local_time(at(1000)), at(1300));
+
+        // older samples fall out of the window
+        clock.observe_millis(30000, at(30400));
+        clock.observe_millis(40000, at(40400));
+        assert_eq!(clock.offset_millis(), Some(400));
+    }
+}
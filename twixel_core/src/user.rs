@@ -1,18 +1,37 @@
+use crate::irc_message::iter::BadgeIter;
+
 bitflags::bitflags! {
     /// Bitflags indicating a user's roles in a channel
+    ///
+    /// Use [is_at_least](ChannelRoles::is_at_least) to check for a minimum
+    /// level of authority in the channel, e.g.
+    /// `roles.is_at_least(ChannelRoles::Moderator)` is `true` for moderators,
+    /// the lead moderator and the broadcaster, but not for Twitch staff
     #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-    #[derive(Clone, Copy, Default, Debug)]
-    pub struct ChannelRoles: u8 {
-        /// Whether the user is a moderator
-        const Moderator = 1;
-        /// Whether the user is a VIP
-        const Vip = 1 << 1;
+    #[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Hash)]
+    pub struct ChannelRoles: u16 {
+        /// Whether the user is a Twitch partner
+        const Partner = 1;
+        /// Whether the user is an artist for the channel
+        const Artist = 1 << 1;
         /// Whether the user is a subscriber
         const Subscriber = 1 << 2;
-        /// Whether the user is the broadcaster
-        const Broadcaster = 1 << 3;
+        /// Whether the user is one of the channel's founders
+        const Founder = 1 << 3;
+        /// Whether the user is a VIP
+        const Vip = 1 << 4;
+        /// Whether the user is a moderator
+        const Moderator = 1 << 5;
         /// Whether the user is the lead moderator
-        const LeadModerator = 1 << 4;
+        const LeadModerator = 1 << 6;
+        /// Whether the user is the broadcaster
+        const Broadcaster = 1 << 7;
+        /// Whether the user is a global moderator
+        const GlobalMod = 1 << 8;
+        /// Whether the user is a Twitch admin
+        const Admin = 1 << 9;
+        /// Whether the user is a Twitch staff member
+        const Staff = 1 << 10;
     }
 }
 
@@ -27,4 +46,341 @@ impl ChannelRoles {
     pub fn is_privileged(&self) -> bool {
         self.intersects(Self::PRIVILEGED_MASK)
     }
+
+    /// The role with the most authority in the channel, if there is any.
+    /// Users can be ordered by authority by comparing this
+    pub fn role(&self) -> Option<Role> {
+        Role::ALL
+            .into_iter()
+            .rev()
+            .find(|r| self.contains(r.flag()))
+    }
+
+    /// The role with the most authority in the channel, if there is any
+    pub fn highest(&self) -> Option<ChannelRoles> {
+        self.role().map(Role::flag)
+    }
+
+    /// `true` if these roles have at least as much authority in the channel as
+    /// `role`, e.g. the lead moderator is at least a [Moderator](Self::Moderator)
+    ///
+    /// For roles that carry no channel authority, such as
+    /// [Staff](Self::Staff) or [Partner](Self::Partner), this is the same as
+    /// [contains](Self::contains)
+    pub fn is_at_least(&self, role: ChannelRoles) -> bool {
+        match Role::ALL.into_iter().find(|r| r.flag() == role) {
+            Some(min) => self.role().is_some_and(|r| r >= min),
+            None => self.contains(role),
+        }
+    }
+
+    /// Roles implied by a set of badges
+    pub fn from_badges<'a>(badges: impl IntoIterator<Item = Badge<'a>>) -> Self {
+        badges
+            .into_iter()
+            .map(|b| b.role())
+            .fold(Self::empty(), Self::union)
+    }
+}
+
+/// A role that carries authority in a channel, ordered from least to most
+/// authoritative. Partner, artist and the sitewide Twitch roles grant none
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Role {
+    /// A subscriber
+    Subscriber,
+    /// One of the channel's founders
+    Founder,
+    /// A VIP
+    Vip,
+    /// A moderator
+    Moderator,
+    /// The lead moderator
+    LeadModerator,
+    /// The broadcaster
+    Broadcaster,
+}
+
+impl Role {
+    /// Every role, from least to most authoritative
+    pub const ALL: [Role; 6] = [
+        Role::Subscriber,
+        Role::Founder,
+        Role::Vip,
+        Role::Moderator,
+        Role::LeadModerator,
+        Role::Broadcaster,
+    ];
+
+    /// The [ChannelRoles] flag of this role
+    pub const fn flag(self) -> ChannelRoles {
+        match self {
+            Role::Subscriber => ChannelRoles::Subscriber,
+            Role::Founder => ChannelRoles::Founder,
+            Role::Vip => ChannelRoles::Vip,
+            Role::Moderator => ChannelRoles::Moderator,
+            Role::LeadModerator => ChannelRoles::LeadModerator,
+            Role::Broadcaster => ChannelRoles::Broadcaster,
+        }
+    }
+}
+
+impl From<Role> for ChannelRoles {
+    fn from(role: Role) -> Self {
+        role.flag()
+    }
+}
+
+/// A chat badge, merged with its `badge-info`, if there is any
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Badge<'a> {
+    /// The broadcaster of the channel
+    Broadcaster,
+    /// A moderator of the channel
+    Moderator,
+    /// The lead moderator of the channel
+    LeadModerator,
+    /// A VIP of the channel
+    Vip,
+    /// A subscriber of the channel
+    Subscriber {
+        /// The subscription tier, from 1 to 3
+        tier: u8,
+        /// How many months the badge being shown represents
+        badge_months: u32,
+        /// The exact amount of months the user has been subscribed for, from
+        /// `badge-info`
+        months: Option<u32>,
+    },
+    /// One of the first subscribers of the channel
+    Founder {
+        /// The exact amount of months the user has been subscribed for, from
+        /// `badge-info`
+        months: Option<u32>,
+    },
+    /// A Twitch staff member
+    Staff,
+    /// A Twitch admin
+    Admin,
+    /// A global moderator
+    GlobalMod,
+    /// A Twitch partner
+    Partner,
+    /// An artist for the channel
+    Artist,
+    /// A bits cheerer badge
+    Bits {
+        /// The amount of bits the badge represents
+        amount: u32,
+    },
+    /// A predictions badge
+    Predictions {
+        /// Which outcome the user predicted, such as `blue-1` or `pink-2`
+        outcome: &'a str,
+        /// The name of the predicted outcome from `badge-info`, with escape
+        /// sequences not removed
+        info: Option<&'a str>,
+    },
+    /// Any other badge
+    Other {
+        /// The badge's name
+        name: &'a str,
+        /// The badge's version
+        version: &'a str,
+        /// The badge's `badge-info`, if present, with escape sequences not
+        /// removed
+        info: Option<&'a str>,
+    },
+}
+
+impl<'a> Badge<'a> {
+    /// Parses a badge from its name and version, along with its `badge-info`
+    pub fn new(name: &'a str, version: &'a str, info: Option<&'a str>) -> Self {
+        match name {
+            "broadcaster" => Self::Broadcaster,
+            "moderator" => Self::Moderator,
+            "lead_moderator" => Self::LeadModerator,
+            "vip" => Self::Vip,
+            "subscriber" => {
+                let version: u32 = version.parse().unwrap_or_default();
+                Self::Subscriber {
+                    tier: match version / 1000 {
+                        3 => 3,
+                        2 => 2,
+                        _ => 1,
+                    },
+                    badge_months: version % 1000,
+                    months: info.and_then(|i| i.parse().ok()),
+                }
+            }
+            "founder" => Self::Founder {
+                months: info.and_then(|i| i.parse().ok()),
+            },
+            "staff" => Self::Staff,
+            "admin" => Self::Admin,
+            "global_mod" => Self::GlobalMod,
+            "partner" => Self::Partner,
+            "artist-badge" => Self::Artist,
+            "bits" => match version.parse() {
+                Ok(amount) => Self::Bits { amount },
+                Err(_) => Self::Other {
+                    name,
+                    version,
+                    info,
+                },
+            },
+            "predictions" => Self::Predictions {
+                outcome: version,
+                info,
+            },
+            _ => Self::Other {
+                name,
+                version,
+                info,
+            },
+        }
+    }
+
+    /// Parses every badge in a `badges` tag, merging them with the ones in a
+    /// `badge-info` tag
+    pub fn parse_list(badges: &'a str, badge_info: Option<&'a str>) -> impl Iterator<Item = Self> {
+        BadgeIter::new(badges).map(move |(name, version)| {
+            let info = badge_info.and_then(|i| {
+                BadgeIter::new(i)
+                    .find(|(info_name, _)| *info_name == name)
+                    .map(|(_, info)| info)
+            });
+            Self::new(name, version, info)
+        })
+    }
+
+    /// The role in the channel this badge implies, empty for badges that don't
+    /// imply any role
+    pub fn role(&self) -> ChannelRoles {
+        match self {
+            Badge::Broadcaster => ChannelRoles::Broadcaster,
+            Badge::Moderator => ChannelRoles::Moderator,
+            Badge::LeadModerator => ChannelRoles::LeadModerator,
+            Badge::Vip => ChannelRoles::Vip,
+            Badge::Subscriber { .. } => ChannelRoles::Subscriber,
+            Badge::Founder { .. } => ChannelRoles::Founder.union(ChannelRoles::Subscriber),
+            Badge::Staff => ChannelRoles::Staff,
+            Badge::Admin => ChannelRoles::Admin,
+            Badge::GlobalMod => ChannelRoles::GlobalMod,
+            Badge::Partner => ChannelRoles::Partner,
+            Badge::Artist => ChannelRoles::Artist,
+            Badge::Bits { .. } | Badge::Predictions { .. } | Badge::Other { .. } => {
+                ChannelRoles::empty()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Badge, ChannelRoles, Role};
+
+    #[test]
+    fn badge_parsing() {
+        let badges: Vec<Badge> = Badge::parse_list(
+            "broadcaster/1,subscriber/3012,bits/1000,predictions/blue-1,glhf-pledge/1",
+            Some("subscriber/16,predictions/Yes\\sit\\swill"),
+        )
+        .collect();
+
+        assert_eq!(
+            badges,
+            [
+                Badge::Broadcaster,
+                Badge::Subscriber {
+                    tier: 3,
+                    badge_months: 12,
+                    months: Some(16)
+                },
+                Badge::Bits { amount: 1000 },
+                Badge::Predictions {
+                    outcome: "blue-1",
+                    info: Some("Yes\\sit\\swill")
+                },
+                Badge::Other {
+                    name: "glhf-pledge",
+                    version: "1",
+                    info: None
+                },
+            ]
+        );
+
+        let founder: Vec<Badge> = Badge::parse_list("founder/0", Some("founder/37")).collect();
+        assert_eq!(founder, [Badge::Founder { months: Some(37) }]);
+    }
+
+    #[test]
+    fn role_ordering() {
+        let roles =
+            ChannelRoles::from_badges(Badge::parse_list("moderator/1,founder/0,partner/1", None));
+        assert_eq!(
+            roles,
+            ChannelRoles::Moderator
+                | ChannelRoles::Founder
+                | ChannelRoles::Subscriber
+                | ChannelRoles::Partner
+        );
+        assert_eq!(roles.highest(), Some(ChannelRoles::Moderator));
+
+        assert!(roles.is_at_least(ChannelRoles::Moderator));
+        assert!(!roles.is_at_least(ChannelRoles::LeadModerator));
+        assert!(roles.is_at_least(ChannelRoles::Partner));
+        assert!(
+            !ChannelRoles::Vip
+                .union(ChannelRoles::Subscriber)
+                .is_at_least(ChannelRoles::Moderator)
+        );
+        assert!(ChannelRoles::Broadcaster.is_at_least(ChannelRoles::Moderator));
+        assert!(!ChannelRoles::empty().is_at_least(ChannelRoles::Subscriber));
+    }
+
+    #[test]
+    fn sort_by_role() {
+        let mut users = [
+            ChannelRoles::Moderator | ChannelRoles::Subscriber,
+            ChannelRoles::Staff,
+            ChannelRoles::Broadcaster,
+            ChannelRoles::Vip | ChannelRoles::Founder | ChannelRoles::Subscriber,
+            ChannelRoles::LeadModerator | ChannelRoles::Moderator,
+            ChannelRoles::Subscriber,
+        ]
+        .map(|roles| roles.role());
+        users.sort();
+
+        assert_eq!(
+            users,
+            [
+                None,
+                Some(Role::Subscriber),
+                Some(Role::Vip),
+                Some(Role::Moderator),
+                Some(Role::LeadModerator),
+                Some(Role::Broadcaster),
+            ]
+        );
+        assert!(Role::Founder > Role::Subscriber);
+        assert_eq!(ChannelRoles::from(Role::Vip), ChannelRoles::Vip);
+    }
+
+    #[test]
+    fn staff_have_no_channel_authority() {
+        let staff = ChannelRoles::from_badges(Badge::parse_list(
+            "staff/1,admin/1,global_mod/1,partner/1",
+            None,
+        ));
+        assert_eq!(staff.highest(), None);
+        assert!(!staff.is_at_least(ChannelRoles::Moderator));
+        assert!(!staff.is_at_least(ChannelRoles::Vip));
+        assert!(staff.is_at_least(ChannelRoles::Staff));
+
+        let staff_sub = staff | ChannelRoles::Subscriber;
+        assert_eq!(staff_sub.highest(), Some(ChannelRoles::Subscriber));
+        assert!(!staff_sub.is_at_least(ChannelRoles::Broadcaster));
+    }
 }